@@ -1,10 +1,21 @@
-use mrt_dump::error::DumpError;
-
 pub use crate::mrt_compose::*;
+pub use crate::mrt_dump::error::{DumpError, RecordContext};
+pub use crate::mrt_dump::{ExtraAttributes, PathAttribute, RawAttribute};
+pub use crate::mrt_dump::attr_values::*;
+pub use crate::mrt_dump::nlri::*;
+pub use crate::mrt_validate::*;
+pub use crate::mrt_write::*;
 
 mod mrt_dump;
 mod mrt_compose;
+mod mrt_validate;
+mod mrt_write;
 
 pub trait MrtDump {
     fn to_bytes(&self, subtype: u16) -> Result<Vec<u8>, DumpError>;
-}
\ No newline at end of file
+}
+
+/// Encoding of records and messages together with the [ExtraAttributes] of their attribute lists.
+pub(crate) trait MrtDumpWithAttributes {
+    fn to_bytes_with_attributes(&self, subtype: u16, extra: &ExtraAttributes) -> Result<Vec<u8>, DumpError>;
+}
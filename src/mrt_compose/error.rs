@@ -1,3 +1,5 @@
+use bgp_models::mrt::EntryType;
+use bgp_models::network::NetworkPrefix;
 use thiserror::Error;
 
 use crate::DumpError;
@@ -7,7 +9,34 @@ pub enum ComposeError {
     #[error(transparent)]
     DumpError(#[from] DumpError),
 
-    #[allow(dead_code)]
-    #[error("{0}")]
-    ComposeError(String),
+    /// The record type cannot be added to the composer.
+    #[error("unsupported record {entry_type:?}/{subtype}")]
+    UnsupportedRecord {
+        entry_type: EntryType,
+        subtype: u16,
+    },
+
+    /// More peers than a peer index table can hold.
+    #[error("peer index table exceeds {} peers", u16::MAX)]
+    TooManyPeers,
+
+    /// A RIB record was added before any peer index table.
+    #[error("RIB record for {prefix} added before any peer index table")]
+    MissingPeerIndexTable {
+        prefix: NetworkPrefix,
+    },
+
+    /// A RIB entry references a peer that is not in the peer index table.
+    #[error("RIB entry for {prefix} references unknown peer index {peer_index}")]
+    InconsistentPeer {
+        prefix: NetworkPrefix,
+        peer_index: u16,
+    },
+
+    /// A field required by another field of the element is not set.
+    #[error("BGP elem for {prefix}: {field} is missing")]
+    MissingField {
+        prefix: NetworkPrefix,
+        field: &'static str,
+    },
 }
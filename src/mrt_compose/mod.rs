@@ -1,48 +1,151 @@
+use std::net::IpAddr;
+
 use bgp_models::prelude::*;
 
+use crate::PathAttribute;
+
+pub use error::ComposeError;
 pub use rib_composer::TableDumpComposer;
+pub use rib_merger::TableDumpMerger;
 pub use updates_composer::BgpUpdatesComposer;
 
-use crate::mrt_compose::error::ComposeError;
-
 mod updates_composer;
 mod rib_composer;
+mod rib_merger;
 mod error;
 
 pub trait MrtCompose {
     fn add_elem(&mut self, elem: &BgpElem) -> Result<(), ComposeError>;
+    // part of the public API, kept as is
+    #[allow(clippy::ptr_arg)]
     fn add_elems(&mut self, elems: &Vec<BgpElem>) -> Result<(), ComposeError>;
     fn export_bytes(&mut self) -> Result<Vec<u8>, ComposeError>;
 }
 
-pub(crate) fn elem_to_attributes(elem: &BgpElem) -> Vec<Attribute> {
-    let mut attrs = vec![];
+/// How composers handle communities with ASNs that do not fit in their 2-byte ASN field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CommunityRangePolicy {
+    /// Fail to encode the record.
+    #[default]
+    Error,
+    /// Convert communities to a form that can carry the 4-byte ASN:
+    /// - regular communities `asn:value` become large communities `asn:value:0`;
+    /// - two-octet AS specific extended communities become four-octet AS specific extended
+    ///   communities of the same subtype, if the local administrator fits in two octets.
+    Convert,
+}
 
-    if let Some(next_hop) = &elem.next_hop {
-        attrs.push(
-            Attribute {
+/// Convert a community with a 4-byte ASN according to `policy`, keeping all others unchanged.
+fn convert_community(comm: &MetaCommunity, policy: CommunityRangePolicy) -> MetaCommunity {
+    if policy == CommunityRangePolicy::Error {
+        return *comm
+    }
+    match comm {
+        MetaCommunity::Community(Community::Custom(asn, value)) if asn.asn > u16::MAX as u32 => {
+            MetaCommunity::LargeCommunity(LargeCommunity::new(asn.asn, [*value as u32, 0]))
+        }
+        MetaCommunity::ExtendedCommunity(ExtendedCommunity::TransitiveTwoOctetAsSpecific(c)) |
+        MetaCommunity::ExtendedCommunity(ExtendedCommunity::NonTransitiveTwoOctetAsSpecific(c))
+            if c.global_administrator.asn > u16::MAX as u32 && c.local_administrator[..2] == [0, 0] => {
+            let converted = FourOctetAsSpecific {
+                // 0x00/0x40 (two-octet AS specific) to 0x02/0x42 (four-octet AS specific)
+                ec_type: c.ec_type | 0x02,
+                ec_subtype: c.ec_subtype,
+                global_administrator: c.global_administrator,
+                local_administrator: [c.local_administrator[2], c.local_administrator[3]],
+            };
+            MetaCommunity::ExtendedCommunity(match comm {
+                MetaCommunity::ExtendedCommunity(ExtendedCommunity::TransitiveTwoOctetAsSpecific(_)) => {
+                    ExtendedCommunity::TransitiveFourOctetAsSpecific(converted)
+                }
+                _ => ExtendedCommunity::NonTransitiveFourOctetAsSpecific(converted),
+            })
+        }
+        _ => *comm,
+    }
+}
+
+/// Next hop attribute of a route to `prefix`.
+///
+/// IPv4 routes with an IPv4 next hop use NEXT_HOP. All other routes use MP_REACH_NLRI carrying
+/// `prefixes`: IPv6 routes, where an IPv4 next hop is written as IPv4-mapped IPv6 address, and
+/// IPv4 routes with an IPv6 next hop.
+/// https://datatracker.ietf.org/doc/html/rfc8950#section-4
+pub(crate) fn next_hop_attribute(prefix: &NetworkPrefix, next_hop: NextHopAddress, prefixes: Vec<NetworkPrefix>) -> Attribute {
+    let afi = match prefix.prefix.is_ipv4() {
+        true => Afi::Ipv4,
+        false => Afi::Ipv6,
+    };
+    let next_hop = match (afi, next_hop) {
+        (Afi::Ipv4, NextHopAddress::Ipv4(ip)) => {
+            return Attribute {
                 attr_type: AttrType::NEXT_HOP,
-                value: AttributeValue::NextHop(*next_hop),
+                value: AttributeValue::NextHop(IpAddr::from(ip)),
                 flag: 64, // transitive
             }
-        );
+        }
+        (Afi::Ipv6, NextHopAddress::Ipv4(ip)) => NextHopAddress::Ipv6(ip.to_ipv6_mapped()),
+        (_, v) => v,
+    };
+    Attribute {
+        attr_type: AttrType::MP_REACHABLE_NLRI,
+        value: AttributeValue::MpReachNlri(Nlri {
+            afi,
+            safi: Safi::Unicast,
+            next_hop: Some(next_hop),
+            prefixes,
+        }),
+        flag: 128, // optional
     }
+}
+
+pub(crate) fn to_next_hop_address(ip: &IpAddr) -> NextHopAddress {
+    match ip {
+        IpAddr::V4(ip) => NextHopAddress::Ipv4(*ip),
+        IpAddr::V6(ip) => NextHopAddress::Ipv6(*ip),
+    }
+}
+
+/// Add `extra` attributes, which replace attributes of the same type code in `attributes`.
+///
+/// The attributes records cannot hold are returned instead, with their positions in `attributes`
+/// for [ExtraAttributes](crate::ExtraAttributes).
+pub(crate) fn add_extra_attributes(attributes: &mut Vec<Attribute>, extra: &[PathAttribute]) -> Vec<(usize, PathAttribute)> {
+    attributes.retain(|a| !extra.iter().any(|e| e.type_code() == a.attr_type as u8));
+    let mut others = vec![];
+    for attr in extra {
+        match attr {
+            PathAttribute::Attribute(a) => attributes.push(a.clone()),
+            _ => others.push((attributes.len(), attr.clone())),
+        }
+    }
+    others
+}
+
+/// Convert the path attributes of an elem. The next hop is left to the composers, as its encoding
+/// depends on the record type and address families.
+pub(crate) fn elem_to_attributes(elem: &BgpElem, policy: CommunityRangePolicy) -> Result<Vec<Attribute>, ComposeError> {
+    let mut attrs = vec![];
 
     if let Some(comms) = &elem.communities{
         let mut reg_comms = vec![];
         let mut ext_comms = vec![];
+        let mut ipv6_ext_comms = vec![];
         let mut lrg_comms = vec![];
 
         for comm in comms {
-            match comm {
+            match convert_community(comm, policy) {
                 MetaCommunity::Community(c) => {
-                    reg_comms.push (*c);
+                    reg_comms.push (c);
+                }
+                MetaCommunity::ExtendedCommunity(c @ ExtendedCommunity::Ipv6AddressSpecific(_)) => {
+                    ipv6_ext_comms.push (c);
                 }
                 MetaCommunity::ExtendedCommunity(c) => {
-                    ext_comms.push (*c);
+                    ext_comms.push (c);
                 }
                 MetaCommunity::LargeCommunity(c) => {
-                    lrg_comms.push (*c);
+                    lrg_comms.push (c);
                 }
             }
         }
@@ -65,6 +168,15 @@ pub(crate) fn elem_to_attributes(elem: &BgpElem) -> Vec<Attribute> {
                 }
             );
         }
+        if !ipv6_ext_comms.is_empty() {
+            attrs.push(
+                Attribute {
+                    attr_type: AttrType::IPV6_ADDRESS_SPECIFIC_EXTENDED_COMMUNITIES,
+                    value: AttributeValue::ExtendedCommunities(ipv6_ext_comms),
+                    flag: 192, // optional transitive
+                }
+            );
+        }
         if !lrg_comms.is_empty() {
             attrs.push(
                 Attribute {
@@ -77,7 +189,7 @@ pub(crate) fn elem_to_attributes(elem: &BgpElem) -> Vec<Attribute> {
     }
 
     if let Some(aggr_asn) = &elem.aggr_asn{
-        let aggr_ip = &elem.aggr_ip.unwrap();
+        let aggr_ip = elem.aggr_ip.as_ref().ok_or(ComposeError::MissingField { prefix: elem.prefix, field: "aggr_ip" })?;
         attrs.push(
             Attribute {
                 attr_type: AttrType::AGGREGATOR,
@@ -88,10 +200,11 @@ pub(crate) fn elem_to_attributes(elem: &BgpElem) -> Vec<Attribute> {
     }
 
     if let Some(as_path) = &elem.as_path {
+        // the encoder adds AS4_PATH when writing for 2-byte ASN sessions
         attrs.push(
             Attribute {
-                attr_type: AttrType::AS4_PATH,
-                value: AttributeValue::As4Path(as_path.clone()),
+                attr_type: AttrType::AS_PATH,
+                value: AttributeValue::AsPath(as_path.clone()),
                 flag: 64, // transitive
             }
         );
@@ -135,6 +248,30 @@ pub(crate) fn elem_to_attributes(elem: &BgpElem) -> Vec<Attribute> {
         );
     }
 
-    attrs
+    Ok(attrs)
 }
 
+/// An IPv4 announcement for composer tests, which override the fields they need.
+#[cfg(test)]
+pub(crate) fn test_elem() -> BgpElem {
+    use std::net::Ipv4Addr;
+    use std::str::FromStr;
+
+    BgpElem {
+        timestamp: 12.1,
+        elem_type: ElemType::ANNOUNCE,
+        peer_ip: IpAddr::from(Ipv4Addr::from([1,2,3,4])),
+        peer_asn: Asn::from(100),
+        prefix: NetworkPrefix::from_str("10.2.2.0/24").unwrap(),
+        next_hop: Some(IpAddr::from(Ipv4Addr::from([4,3,2,1]))),
+        as_path: Some(AsPath{ segments: vec![AsPathSegment::AsSequence(vec![Asn::from(1)])] }),
+        origin_asns: None,
+        origin: Some(Origin::IGP),
+        local_pref: None,
+        med: None,
+        communities: None,
+        atomic: None,
+        aggr_asn: None,
+        aggr_ip: None,
+    }
+}
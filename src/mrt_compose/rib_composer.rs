@@ -1,28 +1,65 @@
 use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
 use std::net::Ipv4Addr;
 
 use bgp_models::prelude::*;
 use ipnetwork::IpNetwork;
-use num_traits::ToPrimitive;
 
-use crate::{elem_to_attributes, MrtCompose, MrtDump};
+use crate::{CommunityRangePolicy, elem_to_attributes, ExtraAttributes, MrtCompose, MrtDumpWithAttributes, PathAttribute};
+use crate::mrt_compose::{add_extra_attributes, next_hop_attribute, to_next_hop_address};
 use crate::mrt_compose::error::ComposeError;
+use crate::mrt_dump::normalize_prefix;
 
 pub struct TableDumpComposer {
-    mrt_records: Option<Vec<MrtRecord>>,
-    rib_entries: BTreeMap<IpNetwork, Vec<RibEntry>>,
+    mrt_records: Option<Vec<(MrtRecord, ExtraAttributes)>>,
+    /// RIB entries of each prefix, with the attributes the entries cannot hold
+    rib_entries: BTreeMap<IpNetwork, (Vec<RibEntry>, ExtraAttributes)>,
     peers: BTreeMap<String, (usize, Peer)>,
     ts_sec: u32,
+    community_policy: CommunityRangePolicy,
+    strict_prefixes: bool,
 }
 
 impl TableDumpComposer {
     pub fn new() -> Self {
-        TableDumpComposer{ mrt_records: None , rib_entries: BTreeMap::new(), peers: BTreeMap::new(), ts_sec: 0 }
+        TableDumpComposer{
+            mrt_records: None,
+            rib_entries: BTreeMap::new(),
+            peers: BTreeMap::new(),
+            ts_sec: 0,
+            community_policy: CommunityRangePolicy::default(),
+            strict_prefixes: false,
+        }
     }
-}
 
-impl MrtCompose for TableDumpComposer {
-    fn add_elem(&mut self, elem: &BgpElem) -> Result<(), ComposeError> {
+    /// Set how communities with 4-byte ASNs in regular and two-octet AS specific extended
+    /// communities are handled, see [CommunityRangePolicy].
+    pub fn set_community_range_policy(&mut self, policy: CommunityRangePolicy) {
+        self.community_policy = policy;
+    }
+
+    /// Reject prefixes with host bits set instead of clearing the host bits.
+    ///
+    /// Without strict mode, elems of prefixes that only differ in host bits end up in the same
+    /// RIB record.
+    pub fn set_strict_prefixes(&mut self, strict: bool) {
+        self.strict_prefixes = strict;
+    }
+
+    /// Add an elem with an explicit next hop instead of [BgpElem::next_hop], e.g. an IPv6 global
+    /// and link-local next hop pair.
+    pub fn add_elem_with_next_hop(&mut self, elem: &BgpElem, next_hop: NextHopAddress) -> Result<(), ComposeError> {
+        self.add_rib_entry(elem, Some(next_hop), &[])
+    }
+
+    /// Add an elem with additional attributes that [BgpElem] cannot express, e.g. attributes from
+    /// [AttrValueDump](crate::AttrValueDump) values. They replace attributes of the same type
+    /// derived from the elem.
+    pub fn add_elem_with_attributes(&mut self, elem: &BgpElem, attributes: &[PathAttribute]) -> Result<(), ComposeError> {
+        self.add_rib_entry(elem, elem.next_hop.as_ref().map(to_next_hop_address), attributes)
+    }
+
+    fn add_rib_entry(&mut self, elem: &BgpElem, next_hop: Option<NextHopAddress>, extra: &[PathAttribute]) -> Result<(), ComposeError> {
         // reset mrt records cache, force recompute new mrt records when exporting to bytes.
         self.mrt_records = None;
 
@@ -30,7 +67,13 @@ impl MrtCompose for TableDumpComposer {
             self.ts_sec = elem.timestamp as u32;
         }
 
-        let attributes =  elem_to_attributes(elem);
+        let mut attributes =  elem_to_attributes(elem, self.community_policy)?;
+        if let Some(next_hop) = next_hop {
+            // RIB entries carry the abbreviated MP_REACH_NLRI without prefixes
+            // https://datatracker.ietf.org/doc/html/rfc6396#section-4.3.4
+            attributes.insert(0, next_hop_attribute(&elem.prefix, next_hop, vec![]));
+        }
+        let others = add_extra_attributes(&mut attributes, extra);
         let peer_ip_str = elem.peer_ip.to_string();
 
         /*
@@ -51,6 +94,10 @@ impl MrtCompose for TableDumpComposer {
         };
 
         let peers_len = self.peers.len();
+        // peer indices are 16-bit, and so is the peer count of the peer index table
+        if peers_len >= u16::MAX as usize && !self.peers.contains_key(&peer_ip_str) {
+            return Err(ComposeError::TooManyPeers)
+        }
         let (pid, _peer) = self.peers.entry(peer_ip_str)
             .or_insert((peers_len,
                         Peer {
@@ -61,7 +108,11 @@ impl MrtCompose for TableDumpComposer {
                         }
             ));
 
-        let entries = self.rib_entries.entry(elem.prefix.prefix).or_insert(vec![]);
+        let prefix = normalize_prefix(&elem.prefix, self.strict_prefixes)?;
+        let (entries, extra_attributes) = self.rib_entries.entry(prefix.prefix).or_default();
+        for (position, attr) in others {
+            extra_attributes.insert(entries.len(), position, attr);
+        }
         entries.push(
             RibEntry{
                 peer_index: *pid as u16,
@@ -72,6 +123,12 @@ impl MrtCompose for TableDumpComposer {
 
         Ok(())
     }
+}
+
+impl MrtCompose for TableDumpComposer {
+    fn add_elem(&mut self, elem: &BgpElem) -> Result<(), ComposeError> {
+        self.add_rib_entry(elem, elem.next_hop.as_ref().map(to_next_hop_address), &[])
+    }
 
     fn add_elems(&mut self, elems: &Vec<BgpElem>) -> Result<(), ComposeError> {
         for elem in elems {
@@ -83,8 +140,8 @@ impl MrtCompose for TableDumpComposer {
     fn export_bytes(&mut self) -> Result<Vec<u8>, ComposeError> {
         if let Some(records) = &self.mrt_records {
             let mut buffer = vec![];
-            for msg in records {
-                let bytes = msg.to_bytes(0)?;
+            for (index, (msg, extra)) in records.iter().enumerate() {
+                let bytes = msg.to_bytes_with_attributes(0, extra).map_err(|e| e.with_record_index(index))?;
                 buffer.extend(bytes);
             }
             return Ok(buffer)
@@ -97,17 +154,17 @@ impl MrtCompose for TableDumpComposer {
         for (pid, peer) in self.peers.values() {
             peers_map.insert(*pid as u32, peer.clone());
         }
-        let peer_count = peers_map.len() as u16;
+        let peer_count = u16::try_from(peers_map.len()).map_err(|_| ComposeError::TooManyPeers)?;
 
         let header = CommonHeader{
             timestamp: self.ts_sec,
             microsecond_timestamp: None,
             entry_type: EntryType::TABLE_DUMP_V2,
-            entry_subtype: TableDumpV2Type::PeerIndexTable.to_u16().unwrap(),
+            entry_subtype: TableDumpV2Type::PeerIndexTable as u16,
             length: 0
         };
 
-        mrt_records.push(
+        mrt_records.push((
             MrtRecord{
                 common_header: header,
                 message: MrtMessage::TableDumpV2Message(
@@ -121,10 +178,11 @@ impl MrtCompose for TableDumpComposer {
                         }
                     )
                 )
-            }
-        );
+            },
+            ExtraAttributes::new(),
+        ));
 
-        for (prefix, rib_entries) in &self.rib_entries {
+        for (prefix, (rib_entries, extra_attributes)) in &self.rib_entries {
             let rib_type = match prefix.is_ipv4() {
                 true => TableDumpV2Type::RibIpv4Unicast,
                 false => TableDumpV2Type:: RibIpv6Unicast
@@ -134,12 +192,12 @@ impl MrtCompose for TableDumpComposer {
                 timestamp: self.ts_sec,
                 microsecond_timestamp: None,
                 entry_type: EntryType::TABLE_DUMP_V2,
-                entry_subtype: rib_type.to_u16().unwrap(),
+                entry_subtype: rib_type as u16,
                 length: 0
             };
-            let new_rib_entries: Vec<RibEntry> = rib_entries.iter().map(|e| e.clone()).collect();
+            let new_rib_entries: Vec<RibEntry> = rib_entries.to_vec();
 
-            mrt_records.push(
+            mrt_records.push((
                 MrtRecord{
                     common_header: header,
                     message: MrtMessage::TableDumpV2Message(
@@ -148,35 +206,45 @@ impl MrtCompose for TableDumpComposer {
                                 rib_type,
                                 sequence_number: 0,
                                 prefix: NetworkPrefix{
-                                    prefix: prefix.clone(),
+                                    prefix: *prefix,
                                     path_id: 0
                                 },
                                 rib_entries: new_rib_entries
                             }
                         )
                     )
-                }
-            );
+                },
+                extra_attributes.clone(),
+            ));
         }
 
         let mut buffer = vec![];
-        for msg in &mrt_records {
-            let bytes = msg.to_bytes(0)?;
+        for (index, (msg, extra)) in mrt_records.iter().enumerate() {
+            let bytes = msg.to_bytes_with_attributes(0, extra).map_err(|e| e.with_record_index(index))?;
             buffer.extend(bytes);
         }
 
         self.mrt_records = Some(mrt_records);
-        return Ok(buffer)
+        Ok(buffer)
+    }
+}
+
+impl Default for TableDumpComposer {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::net::{IpAddr, Ipv4Addr};
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
     use std::str::FromStr;
 
     use bgpkit_parser::parse_mrt_record;
 
+    use crate::{Aigp, AttrValueDump, DumpError, RawMrtRecord, RawRecordIterator};
+    use crate::mrt_compose::test_elem;
+
     use super::*;
 
     #[test]
@@ -231,4 +299,148 @@ mod tests {
 
         dbg!(&record);
     }
+
+    #[test]
+    fn test_compose_host_bits() {
+        let elem = |prefix: &str| BgpElem { prefix: NetworkPrefix::from_str(prefix).unwrap(), ..test_elem() };
+
+        let mut composer = TableDumpComposer::new();
+        composer.add_elem(&elem("10.2.2.1/24")).unwrap();
+        composer.add_elem(&elem("10.2.2.0/24")).unwrap();
+        let bytes = composer.export_bytes().unwrap();
+
+        let mut data = bytes.as_slice();
+        // peer index table, then a single RIB record for the normalized prefix
+        parse_mrt_record(&mut data).unwrap();
+        match parse_mrt_record(&mut data).unwrap().message {
+            MrtMessage::TableDumpV2Message(TableDumpV2Message::RibAfiEntries(rib)) => {
+                assert_eq!(rib.prefix, NetworkPrefix::from_str("10.2.2.0/24").unwrap());
+            }
+            m => panic!("unexpected message {:?}", m),
+        }
+        assert!(data.is_empty());
+
+        let mut composer = TableDumpComposer::new();
+        composer.set_strict_prefixes(true);
+        assert!(matches!(
+            composer.add_elem(&elem("10.2.2.1/24")),
+            Err(ComposeError::DumpError(DumpError::HostBitsSet(_)))
+        ));
+        composer.add_elem(&elem("10.2.2.0/24")).unwrap();
+    }
+
+    #[test]
+    fn test_compose_too_many_peers() {
+        let mut composer = TableDumpComposer::new();
+        let mut elem = test_elem();
+        for i in 1..=u16::MAX as u32 {
+            elem.peer_ip = IpAddr::from(Ipv4Addr::from(i));
+            composer.add_elem(&elem).unwrap();
+        }
+        // known peers can still be added
+        elem.prefix = NetworkPrefix::from_str("10.3.0.0/16").unwrap();
+        composer.add_elem(&elem).unwrap();
+        elem.peer_ip = IpAddr::from(Ipv4Addr::from([255, 255, 255, 255]));
+        assert!(matches!(composer.add_elem(&elem), Err(ComposeError::TooManyPeers)));
+        composer.export_bytes().unwrap();
+    }
+
+    #[test]
+    fn test_compose_ipv6_next_hop() {
+        let global = Ipv6Addr::from_str("2001:db8::1").unwrap();
+        let link_local = Ipv6Addr::from_str("fe80::1").unwrap();
+        let mut elem = test_elem();
+        elem.peer_ip = IpAddr::from(global);
+        elem.prefix = NetworkPrefix::from_str("2001:db8:1::/48").unwrap();
+        elem.next_hop = Some(IpAddr::from(global));
+        let mut elem2 = elem.clone();
+        elem2.peer_ip = IpAddr::from(Ipv6Addr::from_str("2001:db8::2").unwrap());
+
+        let mut composer = TableDumpComposer::new();
+        composer.add_elem(&elem).unwrap();
+        composer.add_elem_with_next_hop(&elem2, NextHopAddress::Ipv6LinkLocal(global, link_local)).unwrap();
+        let bytes = composer.export_bytes().unwrap();
+
+        let mut data = bytes.as_slice();
+        parse_mrt_record(&mut data).unwrap();
+        let rib = match parse_mrt_record(&mut data).unwrap().message {
+            MrtMessage::TableDumpV2Message(TableDumpV2Message::RibAfiEntries(rib)) => rib,
+            m => panic!("unexpected message {:?}", m),
+        };
+        let next_hops: Vec<Option<NextHopAddress>> = rib.rib_entries.iter().map(|entry| {
+            assert!(!entry.attributes.iter().any(|a| a.attr_type == AttrType::NEXT_HOP));
+            entry.attributes.iter().find_map(|a| match &a.value {
+                AttributeValue::MpReachNlri(nlri) => {
+                    assert_eq!(nlri.prefixes, vec![rib.prefix]);
+                    Some(nlri.next_hop)
+                }
+                _ => None,
+            }).unwrap()
+        }).collect();
+        assert_eq!(next_hops, vec![
+            Some(NextHopAddress::Ipv6(global)),
+            Some(NextHopAddress::Ipv6LinkLocal(global, link_local)),
+        ]);
+    }
+
+    #[test]
+    fn test_compose_ipv4_with_ipv6_next_hop() {
+        let next_hop = Ipv6Addr::from_str("2001:db8::1").unwrap();
+        let mut elem = test_elem();
+        elem.peer_ip = IpAddr::from(next_hop);
+        elem.next_hop = Some(IpAddr::from(next_hop));
+
+        let mut composer = TableDumpComposer::new();
+        composer.add_elem(&elem).unwrap();
+        let bytes = composer.export_bytes().unwrap();
+
+        let mut data = bytes.as_slice();
+        parse_mrt_record(&mut data).unwrap();
+        let record = parse_mrt_record(&mut data).unwrap();
+        assert_eq!(record.common_header.entry_subtype, TableDumpV2Type::RibIpv4Unicast as u16);
+        let rib = match record.message {
+            MrtMessage::TableDumpV2Message(TableDumpV2Message::RibAfiEntries(rib)) => rib,
+            m => panic!("unexpected message {:?}", m),
+        };
+        let attributes = &rib.rib_entries[0].attributes;
+        assert!(!attributes.iter().any(|a| a.attr_type == AttrType::NEXT_HOP));
+        let nlri = attributes.iter().find_map(|a| match &a.value {
+            AttributeValue::MpReachNlri(nlri) => Some(nlri),
+            _ => None,
+        }).unwrap();
+        assert_eq!(nlri.afi, Afi::Ipv4);
+        assert_eq!(nlri.next_hop, Some(NextHopAddress::Ipv6(next_hop)));
+    }
+
+    #[test]
+    fn test_compose_extra_attributes() {
+        let origin = Attribute { attr_type: AttrType::ORIGIN, value: AttributeValue::Origin(Origin::INCOMPLETE), flag: 0x40 };
+        let extra = vec![
+            Aigp::with_metric(10).to_attribute().unwrap(),
+            // replaces the origin of the elem
+            origin.clone().into(),
+        ];
+
+        let mut composer = TableDumpComposer::new();
+        composer.add_elem(&test_elem()).unwrap();
+        composer.add_elem_with_attributes(&BgpElem { peer_ip: IpAddr::from(Ipv4Addr::from([1, 2, 3, 5])), ..test_elem() }, &extra).unwrap();
+        let bytes = composer.export_bytes().unwrap();
+
+        // only the second RIB entry has the AIGP attribute
+        let aigp = [0x80, 26, 11, 1, 0, 11, 0, 0, 0, 0, 0, 0, 0, 10];
+        assert_eq!(bytes.windows(aigp.len()).filter(|w| *w == aigp).count(), 1);
+        let records = RawRecordIterator::new(bytes.as_slice()).collect::<Result<Vec<RawMrtRecord>, _>>().unwrap();
+        assert!(records[1].extra_attributes().list(0).is_empty());
+        assert_eq!(records[1].extra_attributes().list(1).len(), 1);
+
+        let mut data = bytes.as_slice();
+        parse_mrt_record(&mut data).unwrap();
+        let rib = match parse_mrt_record(&mut data).unwrap().message {
+            MrtMessage::TableDumpV2Message(TableDumpV2Message::RibAfiEntries(rib)) => rib,
+            m => panic!("unexpected message {:?}", m),
+        };
+        let origins: Vec<&Attribute> = rib.rib_entries[1].attributes.iter().filter(|a| a.attr_type == AttrType::ORIGIN).collect();
+        assert_eq!(origins, vec![&origin]);
+        assert_eq!(rib.rib_entries[0].attributes.len(), rib.rib_entries[1].attributes.len());
+    }
 }
@@ -0,0 +1,391 @@
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::net::{IpAddr, Ipv4Addr};
+
+use bgp_models::prelude::*;
+use ipnetwork::IpNetwork;
+
+use crate::MrtDump;
+use crate::mrt_compose::error::ComposeError;
+use crate::mrt_dump::normalize_prefix;
+
+/// Merge multiple TABLE_DUMP_V2 RIB dumps into one RIB dump on record level.
+///
+/// Each RIB dump is added record by record, starting with its PEER_INDEX_TABLE. Peers from all
+/// tables are unioned into one peer index table (peers with the same BGP ID, address and ASN are
+/// merged), the peer indices of the RIB entries are remapped accordingly, and the RIB entries of
+/// the same prefix are combined into one RIB record. Attributes are carried over untouched.
+///
+/// A non-add-path RIB record holds at most one entry per peer. If a merged peer has an entry for
+/// the same prefix in more than one dump, the entry with the latest originated time is kept (the
+/// first one added on a tie) and the conflict is reported by [TableDumpMerger::conflicts].
+pub struct TableDumpMerger {
+    collector_bgp_id: Option<Ipv4Addr>,
+    peers: Vec<Peer>,
+    peer_ids: HashMap<(Ipv4Addr, IpAddr, u32), u16>,
+    /// peer index remapping for the RIB dump currently being added
+    current_peer_map: Option<HashMap<u16, u16>>,
+    rib_entries: BTreeMap<(IpNetwork, u16), (TableDumpV2Type, Vec<RibEntry>)>,
+    conflicts: Vec<(NetworkPrefix, u16)>,
+    ts_sec: u32,
+}
+
+impl TableDumpMerger {
+    pub fn new() -> Self {
+        TableDumpMerger {
+            collector_bgp_id: None,
+            peers: vec![],
+            peer_ids: HashMap::new(),
+            current_peer_map: None,
+            rib_entries: BTreeMap::new(),
+            conflicts: vec![],
+            ts_sec: 0,
+        }
+    }
+
+    /// Add one TABLE_DUMP_V2 record.
+    ///
+    /// A PEER_INDEX_TABLE record starts a new RIB dump; all following RIB records are interpreted
+    /// against that table until the next PEER_INDEX_TABLE record is added.
+    pub fn add_record(&mut self, record: &MrtRecord) -> Result<(), ComposeError> {
+        let msg = match &record.message {
+            MrtMessage::TableDumpV2Message(m) => m,
+            _ => {
+                return Err(ComposeError::UnsupportedRecord {
+                    entry_type: record.common_header.entry_type,
+                    subtype: record.common_header.entry_subtype,
+                })
+            }
+        };
+
+        if self.ts_sec == 0 || record.common_header.timestamp < self.ts_sec {
+            self.ts_sec = record.common_header.timestamp;
+        }
+
+        match msg {
+            TableDumpV2Message::PeerIndexTable(table) => self.add_peer_index_table(table),
+            TableDumpV2Message::RibAfiEntries(rib) => self.add_rib_entries(rib),
+            TableDumpV2Message::RibGenericEntries(_) => {
+                Err(ComposeError::UnsupportedRecord {
+                    entry_type: record.common_header.entry_type,
+                    subtype: record.common_header.entry_subtype,
+                })
+            }
+        }
+    }
+
+    pub fn add_records(&mut self, records: &[MrtRecord]) -> Result<(), ComposeError> {
+        for record in records {
+            self.add_record(record)?;
+        }
+        Ok(())
+    }
+
+    /// Prefixes and merged peer indices that had more than one RIB entry in a non-add-path RIB
+    /// record, in the order the conflicting entries were added.
+    pub fn conflicts(&self) -> &[(NetworkPrefix, u16)] {
+        &self.conflicts
+    }
+
+    fn add_peer_index_table(&mut self, table: &PeerIndexTable) -> Result<(), ComposeError> {
+        if self.collector_bgp_id.is_none() {
+            self.collector_bgp_id = Some(table.collector_bgp_id);
+        }
+
+        let mut old_ids: Vec<&u32> = table.peers_map.keys().collect();
+        old_ids.sort();
+
+        let mut peer_map = HashMap::new();
+        for old_id in old_ids {
+            let peer = &table.peers_map[old_id];
+            let key = (peer.peer_bgp_id, peer.peer_address, peer.peer_asn.asn);
+            let new_id = match self.peer_ids.get(&key) {
+                Some(id) => {
+                    // keep the wider encodings if the same peer shows up with different peer types
+                    self.peers[*id as usize].peer_type |= peer.peer_type;
+                    *id
+                }
+                None => {
+                    let id = self.peers.len();
+                    // peer indices are 16-bit, and so is the peer count of the merged table
+                    if id >= u16::MAX as usize {
+                        return Err(ComposeError::TooManyPeers)
+                    }
+                    self.peers.push(peer.clone());
+                    self.peer_ids.insert(key, id as u16);
+                    id as u16
+                }
+            };
+            peer_map.insert(*old_id as u16, new_id);
+        }
+
+        self.current_peer_map = Some(peer_map);
+        Ok(())
+    }
+
+    fn add_rib_entries(&mut self, rib: &RibAfiEntries) -> Result<(), ComposeError> {
+        let peer_map = match &self.current_peer_map {
+            Some(m) => m,
+            None => {
+                return Err(ComposeError::MissingPeerIndexTable { prefix: rib.prefix })
+            }
+        };
+
+        let subtype = rib.rib_type as u16;
+        let add_path = matches!(
+            rib.rib_type,
+            TableDumpV2Type::RibIpv4UnicastAddPath | TableDumpV2Type::RibIpv4MulticastAddPath |
+            TableDumpV2Type::RibIpv6UnicastAddPath | TableDumpV2Type::RibIpv6MulticastAddPath
+        );
+        let prefix = normalize_prefix(&rib.prefix, false)?;
+        let (_, entries) = self.rib_entries.entry((prefix.prefix, subtype))
+            .or_insert_with(|| (rib.rib_type, vec![]));
+        for entry in &rib.rib_entries {
+            let peer_index = match peer_map.get(&entry.peer_index) {
+                Some(i) => *i,
+                None => {
+                    return Err(ComposeError::InconsistentPeer { prefix: rib.prefix, peer_index: entry.peer_index })
+                }
+            };
+            let merged = RibEntry {
+                peer_index,
+                originated_time: entry.originated_time,
+                attributes: entry.attributes.clone(),
+            };
+            match entries.iter_mut().find(|e| !add_path && e.peer_index == peer_index) {
+                Some(existing) => {
+                    self.conflicts.push((prefix, peer_index));
+                    if merged.originated_time > existing.originated_time {
+                        *existing = merged;
+                    }
+                }
+                None => entries.push(merged),
+            }
+        }
+        Ok(())
+    }
+
+    /// Export the merged RIB dump: one PEER_INDEX_TABLE record followed by the RIB records.
+    pub fn export_bytes(&mut self) -> Result<Vec<u8>, ComposeError> {
+        let mut mrt_records = vec![];
+
+        let peers_map: HashMap<u32, Peer> = self.peers.iter().enumerate()
+            .map(|(pid, peer)| (pid as u32, peer.clone()))
+            .collect();
+        let peer_count = u16::try_from(peers_map.len()).map_err(|_| ComposeError::TooManyPeers)?;
+
+        mrt_records.push(
+            MrtRecord {
+                common_header: CommonHeader {
+                    timestamp: self.ts_sec,
+                    microsecond_timestamp: None,
+                    entry_type: EntryType::TABLE_DUMP_V2,
+                    entry_subtype: TableDumpV2Type::PeerIndexTable as u16,
+                    length: 0,
+                },
+                message: MrtMessage::TableDumpV2Message(
+                    TableDumpV2Message::PeerIndexTable(
+                        PeerIndexTable {
+                            collector_bgp_id: self.collector_bgp_id.unwrap_or(Ipv4Addr::from([0, 0, 0, 0])),
+                            view_name_length: 0,
+                            view_name: "".to_string(),
+                            peer_count,
+                            peers_map,
+                        }
+                    )
+                ),
+            }
+        );
+
+        for (sequence_number, ((prefix, subtype), (rib_type, rib_entries))) in self.rib_entries.iter().enumerate() {
+            mrt_records.push(
+                MrtRecord {
+                    common_header: CommonHeader {
+                        timestamp: self.ts_sec,
+                        microsecond_timestamp: None,
+                        entry_type: EntryType::TABLE_DUMP_V2,
+                        entry_subtype: *subtype,
+                        length: 0,
+                    },
+                    message: MrtMessage::TableDumpV2Message(
+                        TableDumpV2Message::RibAfiEntries(
+                            RibAfiEntries {
+                                rib_type: *rib_type,
+                                sequence_number: sequence_number as u32,
+                                prefix: NetworkPrefix::new(*prefix, 0),
+                                rib_entries: rib_entries.clone(),
+                            }
+                        )
+                    ),
+                }
+            );
+        }
+
+        let mut buffer = vec![];
+        for (index, msg) in mrt_records.iter().enumerate() {
+            buffer.extend(msg.to_bytes(0).map_err(|e| e.with_record_index(index))?);
+        }
+        Ok(buffer)
+    }
+}
+
+impl Default for TableDumpMerger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bgpkit_parser::parse_mrt_record;
+
+    use super::*;
+
+    fn peer(ip: [u8; 4], asn: u32) -> Peer {
+        Peer {
+            peer_type: 2,
+            peer_bgp_id: Ipv4Addr::from(ip),
+            peer_address: IpAddr::from(Ipv4Addr::from(ip)),
+            peer_asn: Asn::from(asn),
+        }
+    }
+
+    fn peer_table_record(peers: Vec<Peer>) -> MrtRecord {
+        let peers_map: HashMap<u32, Peer> = peers.into_iter().enumerate().map(|(i, p)| (i as u32, p)).collect();
+        MrtRecord {
+            common_header: CommonHeader {
+                timestamp: 10,
+                microsecond_timestamp: None,
+                entry_type: EntryType::TABLE_DUMP_V2,
+                entry_subtype: 1,
+                length: 0,
+            },
+            message: MrtMessage::TableDumpV2Message(TableDumpV2Message::PeerIndexTable(PeerIndexTable {
+                collector_bgp_id: Ipv4Addr::from([9, 9, 9, 9]),
+                view_name_length: 0,
+                view_name: "".to_string(),
+                peer_count: peers_map.len() as u16,
+                peers_map,
+            })),
+        }
+    }
+
+    fn rib_record(prefix: &str, peer_indices: &[u16]) -> MrtRecord {
+        let rib_entries = peer_indices.iter().map(|i| RibEntry {
+            peer_index: *i,
+            originated_time: 5,
+            attributes: vec![
+                Attribute {
+                    attr_type: AttrType::ORIGIN,
+                    value: AttributeValue::Origin(Origin::IGP),
+                    flag: 64,
+                },
+                Attribute {
+                    attr_type: AttrType::AS_PATH,
+                    value: AttributeValue::AsPath(AsPath::from_segments(vec![
+                        AsPathSegment::AsSequence([*i as u32 + 1, 65000].map(|a| a.into()).to_vec())
+                    ])),
+                    flag: 64,
+                },
+            ],
+        }).collect();
+        MrtRecord {
+            common_header: CommonHeader {
+                timestamp: 10,
+                microsecond_timestamp: None,
+                entry_type: EntryType::TABLE_DUMP_V2,
+                entry_subtype: 2,
+                length: 0,
+            },
+            message: MrtMessage::TableDumpV2Message(TableDumpV2Message::RibAfiEntries(RibAfiEntries {
+                rib_type: TableDumpV2Type::RibIpv4Unicast,
+                sequence_number: 0,
+                prefix: NetworkPrefix::from_str(prefix).unwrap(),
+                rib_entries,
+            })),
+        }
+    }
+
+    #[test]
+    fn test_merge_ribs() {
+        let mut merger = TableDumpMerger::new();
+        merger.add_records(&[
+            peer_table_record(vec![peer([1, 1, 1, 1], 1), peer([2, 2, 2, 2], 2)]),
+            rib_record("10.0.0.0/8", &[0, 1]),
+            rib_record("10.1.0.0/16", &[1]),
+        ]).unwrap();
+        // the shared peer has a newer entry for 10.0.0.0/8 in the second dump
+        let mut newer = rib_record("10.0.0.0/8", &[0, 1]);
+        if let MrtMessage::TableDumpV2Message(TableDumpV2Message::RibAfiEntries(r)) = &mut newer.message {
+            r.rib_entries[1].originated_time = 6;
+        }
+        merger.add_records(&[
+            peer_table_record(vec![peer([3, 3, 3, 3], 3), peer([2, 2, 2, 2], 2)]),
+            newer,
+        ]).unwrap();
+        assert_eq!(merger.conflicts(), [(NetworkPrefix::from_str("10.0.0.0/8").unwrap(), 1)]);
+
+        let bytes = merger.export_bytes().unwrap();
+        let mut input = bytes.as_slice();
+
+        let record = parse_mrt_record(&mut input).unwrap();
+        match record.message {
+            MrtMessage::TableDumpV2Message(TableDumpV2Message::PeerIndexTable(t)) => {
+                assert_eq!(t.peer_count, 3);
+                assert_eq!(t.peers_map.get(&2).unwrap().peer_asn, 3);
+            }
+            _ => panic!("first record must be a peer index table"),
+        }
+
+        let record = parse_mrt_record(&mut input).unwrap();
+        match record.message {
+            MrtMessage::TableDumpV2Message(TableDumpV2Message::RibAfiEntries(r)) => {
+                assert_eq!(r.prefix, NetworkPrefix::from_str("10.0.0.0/8").unwrap());
+                let indices: Vec<u16> = r.rib_entries.iter().map(|e| e.peer_index).collect();
+                assert_eq!(indices, vec![0, 1, 2]);
+                assert_eq!(r.rib_entries[1].originated_time, 6);
+            }
+            _ => panic!("expecting RIB record"),
+        }
+
+        let record = parse_mrt_record(&mut input).unwrap();
+        match record.message {
+            MrtMessage::TableDumpV2Message(TableDumpV2Message::RibAfiEntries(r)) => {
+                assert_eq!(r.sequence_number, 1);
+                assert_eq!(r.rib_entries.len(), 1);
+            }
+            _ => panic!("expecting RIB record"),
+        }
+        assert!(input.is_empty());
+    }
+
+    #[test]
+    fn test_merge_without_peer_table() {
+        let mut merger = TableDumpMerger::new();
+        assert!(matches!(
+            merger.add_record(&rib_record("10.0.0.0/8", &[0])),
+            Err(ComposeError::MissingPeerIndexTable { .. })
+        ));
+
+        merger.add_record(&peer_table_record(vec![peer([1, 1, 1, 1], 1)])).unwrap();
+        assert!(matches!(
+            merger.add_record(&rib_record("10.0.0.0/8", &[1])),
+            Err(ComposeError::InconsistentPeer { peer_index: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_merge_too_many_peers() {
+        let mut merger = TableDumpMerger::new();
+        let peers = (1..=u16::MAX as u32).map(|i| peer(i.to_be_bytes(), i)).collect();
+        merger.add_record(&peer_table_record(peers)).unwrap();
+        assert!(merger.export_bytes().is_ok());
+
+        assert!(matches!(
+            merger.add_record(&peer_table_record(vec![peer([255, 255, 255, 255], 1)])),
+            Err(ComposeError::TooManyPeers)
+        ));
+    }
+}
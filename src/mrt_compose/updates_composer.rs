@@ -1,51 +1,123 @@
 use std::net::IpAddr;
-use std::str::FromStr;
 
 use bgp_models::prelude::*;
-use num_traits::ToPrimitive;
 
-use crate::{elem_to_attributes, MrtCompose, MrtDump};
+use crate::{CommunityRangePolicy, elem_to_attributes, ExtraAttributes, MrtCompose, MrtDumpWithAttributes, PathAttribute};
+use crate::mrt_dump::{AS_TRANS, normalize_prefix};
 use crate::mrt_compose::error::ComposeError;
+use crate::mrt_compose::{add_extra_attributes, next_hop_attribute, to_next_hop_address};
 
 pub struct BgpUpdatesComposer {
     mrt_records: Vec<MrtRecord>,
+    /// attributes of each record in `mrt_records` that the record cannot hold
+    extra_attributes: Vec<ExtraAttributes>,
+    asn_len: AsnLength,
+    community_policy: CommunityRangePolicy,
+    strict_prefixes: bool,
 }
 
 impl BgpUpdatesComposer {
     pub fn new() -> Self {
-        BgpUpdatesComposer{ mrt_records: vec![] }
+        Self::with_asn_length(AsnLength::Bits32)
     }
-}
 
-impl MrtCompose for BgpUpdatesComposer {
-    /// add single [BgpElem] as a BGP message entry
-    fn add_elem(&mut self, elem: &BgpElem) -> Result<(), ComposeError> {
+    /// Compose messages of a session with the given ASN length.
+    ///
+    /// With [AsnLength::Bits16], BGP4MP_MESSAGE records are produced the way a speaker without
+    /// 4-byte ASN support sends them: 4-byte ASNs are replaced by AS_TRANS in AS_PATH and
+    /// AGGREGATOR, and AS4_PATH and AS4_AGGREGATOR are added.
+    pub fn with_asn_length(asn_len: AsnLength) -> Self {
+        BgpUpdatesComposer{
+            mrt_records: vec![],
+            extra_attributes: vec![],
+            asn_len,
+            community_policy: CommunityRangePolicy::default(),
+            strict_prefixes: false,
+        }
+    }
+
+    /// Set how communities with 4-byte ASNs in regular and two-octet AS specific extended
+    /// communities are handled, see [CommunityRangePolicy].
+    pub fn set_community_range_policy(&mut self, policy: CommunityRangePolicy) {
+        self.community_policy = policy;
+    }
+
+    /// Reject prefixes with host bits set instead of clearing the host bits.
+    pub fn set_strict_prefixes(&mut self, strict: bool) {
+        self.strict_prefixes = strict;
+    }
+
+    /// Add an elem with an explicit next hop instead of [BgpElem::next_hop], e.g. an IPv6 global
+    /// and link-local next hop pair.
+    pub fn add_elem_with_next_hop(&mut self, elem: &BgpElem, next_hop: NextHopAddress) -> Result<(), ComposeError> {
+        self.add_update(elem, Some(next_hop), &[])
+    }
+
+    /// Add an elem with additional attributes that [BgpElem] cannot express, e.g. attributes from
+    /// [AttrValueDump](crate::AttrValueDump) values. They replace attributes of the same type
+    /// derived from the elem.
+    pub fn add_elem_with_attributes(&mut self, elem: &BgpElem, attributes: &[PathAttribute]) -> Result<(), ComposeError> {
+        self.add_update(elem, elem.next_hop.as_ref().map(to_next_hop_address), attributes)
+    }
+
+    fn add_update(&mut self, elem: &BgpElem, next_hop: Option<NextHopAddress>, extra: &[PathAttribute]) -> Result<(), ComposeError> {
 
-        let t = elem.timestamp;
-        let t_str= format!("{:.6}", t);
-        let fields = t_str.split(".").collect::<Vec<&str>>();
-        let msec = u32::from_str(fields.get(1).unwrap()).unwrap();
+        // split the timestamp into seconds and microseconds
+        let t = (elem.timestamp * 1_000_000.0).round() as u64;
+        let (ts_sec, msec) = ((t / 1_000_000) as u32, (t % 1_000_000) as u32);
+
+        let msg_type = match self.asn_len {
+            AsnLength::Bits16 => Bgp4MpType::Bgp4MpMessage,
+            AsnLength::Bits32 => Bgp4MpType::Bgp4MpMessageAs4,
+        };
 
         let header = CommonHeader{
-            timestamp: t as u32,
+            timestamp: ts_sec,
             microsecond_timestamp: Some(msec),
             entry_type: EntryType::BGP4MP_ET,
-            entry_subtype: Bgp4MpType::Bgp4MpMessageAs4.to_u16().unwrap(),
+            entry_subtype: msg_type as u16,
             length: 0
         };
 
-        let (a,w) = match elem.elem_type{
-            ElemType::ANNOUNCE => {(vec![elem.prefix], vec![])}
-            ElemType::WITHDRAW => {(vec![], vec![elem.prefix])}
+        let prefix = normalize_prefix(&elem.prefix, self.strict_prefixes)?;
+        let mut attributes = elem_to_attributes(elem, self.community_policy)?;
+
+        // only IPv4 routes with IPv4 next hops use the NLRI fields of the update message, all
+        // others are carried in MP_REACH_NLRI and MP_UNREACH_NLRI
+        let (a,w) = match (elem.elem_type, next_hop) {
+            (ElemType::ANNOUNCE, Some(next_hop)) => {
+                let attr = next_hop_attribute(&prefix, next_hop, vec![prefix]);
+                let announced = match attr.attr_type {
+                    AttrType::NEXT_HOP => vec![prefix],
+                    _ => vec![],
+                };
+                attributes.insert(0, attr);
+                (announced, vec![])
+            }
+            (ElemType::ANNOUNCE, None) if !prefix.prefix.is_ipv4() => {
+                attributes.insert(0, ipv6_nlri_attribute(AttrType::MP_REACHABLE_NLRI, prefix));
+                (vec![], vec![])
+            }
+            (ElemType::ANNOUNCE, None) => (vec![prefix], vec![]),
+            (ElemType::WITHDRAW, _) if !prefix.prefix.is_ipv4() => {
+                attributes.insert(0, ipv6_nlri_attribute(AttrType::MP_UNREACHABLE_NLRI, prefix));
+                (vec![], vec![])
+            }
+            (ElemType::WITHDRAW, _) => (vec![], vec![prefix]),
         };
+        let mut extra_attributes = ExtraAttributes::new();
+        for (position, attr) in add_extra_attributes(&mut attributes, extra) {
+            extra_attributes.insert(0, position, attr);
+        }
 
         let msg = BgpUpdateMessage{
             withdrawn_prefixes: w,
-            attributes: elem_to_attributes(elem),
+            attributes,
             announced_prefixes: a
         };
 
-        let afi = match elem.prefix.prefix.is_ipv4() {
+        // the AFI of the peer and local addresses, which may differ from the route's AFI
+        let afi = match elem.peer_ip.is_ipv4() {
             true => Afi::Ipv4,
             false => Afi::Ipv6,
         };
@@ -55,27 +127,61 @@ impl MrtCompose for BgpUpdatesComposer {
             false => IpAddr::from([ 0,0,0,0, 0,0,0,0, 0,0,0,0, 0,0,0,0 ])
         };
 
+        // a peer with a 4-byte ASN shows up as AS_TRANS on a 2-byte session
+        let peer_asn = match self.asn_len {
+            AsnLength::Bits16 if elem.peer_asn.asn > u16::MAX as u32 => Asn{ asn: AS_TRANS, len: AsnLength::Bits16 },
+            _ => Asn{ asn: elem.peer_asn.asn, len: self.asn_len },
+        };
+        let message = Bgp4MpMessage{
+            msg_type,
+            peer_asn,
+            local_asn: Asn{ asn: 0, len: self.asn_len },
+            interface_index: 0,
+            afi,
+            peer_ip: elem.peer_ip,
+            local_ip,
+            bgp_message: BgpMessage::Update(msg)
+        };
+
         self.mrt_records.push (
             MrtRecord{
                 common_header: header,
                 message: MrtMessage::Bgp4Mp(
-                    Bgp4Mp::Bgp4MpMessageAs4(
-                        Bgp4MpMessage{
-                            msg_type: Bgp4MpType::Bgp4MpMessageAs4,
-                            peer_asn: elem.peer_asn,
-                            local_asn: Asn{ asn: 0, len: elem.peer_asn.len },
-                            interface_index: 0,
-                            afi,
-                            peer_ip: elem.peer_ip,
-                            local_ip,
-                            bgp_message: BgpMessage::Update(msg)
-                        }
-                    )
+                    match self.asn_len {
+                        AsnLength::Bits16 => Bgp4Mp::Bgp4MpMessage(message),
+                        AsnLength::Bits32 => Bgp4Mp::Bgp4MpMessageAs4(message),
+                    }
                 )
             }
         );
+        self.extra_attributes.push(extra_attributes);
         Ok(())
     }
+}
+
+/// MP_REACH_NLRI without next hop or MP_UNREACH_NLRI for a single IPv6 unicast prefix.
+fn ipv6_nlri_attribute(attr_type: AttrType, prefix: NetworkPrefix) -> Attribute {
+    let nlri = Nlri {
+        afi: Afi::Ipv6,
+        safi: Safi::Unicast,
+        next_hop: None,
+        prefixes: vec![prefix],
+    };
+    Attribute {
+        attr_type,
+        value: match attr_type {
+            AttrType::MP_REACHABLE_NLRI => AttributeValue::MpReachNlri(nlri),
+            _ => AttributeValue::MpUnreachNlri(nlri),
+        },
+        flag: 128, // optional
+    }
+}
+
+impl MrtCompose for BgpUpdatesComposer {
+    /// add single [BgpElem] as a BGP message entry
+    fn add_elem(&mut self, elem: &BgpElem) -> Result<(), ComposeError> {
+        self.add_update(elem, elem.next_hop.as_ref().map(to_next_hop_address), &[])
+    }
 
     fn add_elems(&mut self, elems: &Vec<BgpElem>) -> Result<(), ComposeError> {
         for elem in elems {
@@ -86,21 +192,30 @@ impl MrtCompose for BgpUpdatesComposer {
 
     fn export_bytes(&mut self) -> Result<Vec<u8>, ComposeError> {
         let mut buffer = vec![];
-        for msg in &self.mrt_records {
-            let bytes = msg.to_bytes(0)?;
+        for (index, (msg, extra)) in self.mrt_records.iter().zip(&self.extra_attributes).enumerate() {
+            let bytes = msg.to_bytes_with_attributes(0, extra).map_err(|e| e.with_record_index(index))?;
             buffer.extend(bytes);
         }
         Ok(buffer)
     }
 }
 
+impl Default for BgpUpdatesComposer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use std::net::Ipv4Addr;
+    use std::net::{Ipv4Addr, Ipv6Addr};
     use std::str::FromStr;
 
     use bgpkit_parser::parse_mrt_record;
 
+    use crate::{Aigp, AttrValueDump, DumpError, OnlyToCustomer, TypedExtendedCommunity};
+    use crate::mrt_compose::test_elem;
+
     use super::*;
 
     #[test]
@@ -137,4 +252,256 @@ mod tests {
 
         dbg!(&record);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_compose_long_as_path() {
+        let mut asns: Vec<Asn> = vec![Asn::from(65000); 599];
+        asns.push(Asn::from(400000));
+        let mut elem = test_elem();
+        elem.as_path = Some(AsPath::from_segments(vec![AsPathSegment::AsSequence(asns.clone())]));
+
+        let mut composer = BgpUpdatesComposer::new();
+        composer.add_elem(&elem).unwrap();
+        let bytes = composer.export_bytes().unwrap();
+
+        let record = parse_mrt_record(&mut bytes.as_slice()).unwrap();
+        let attributes = match record.message {
+            MrtMessage::Bgp4Mp(Bgp4Mp::Bgp4MpMessage(m) | Bgp4Mp::Bgp4MpMessageAs4(m)) => match m.bgp_message {
+                BgpMessage::Update(u) => u.attributes,
+                _ => panic!("expecting update message"),
+            },
+            _ => panic!("expecting BGP4MP message"),
+        };
+        let path = attributes.iter().find_map(|a| match &a.value {
+            AttributeValue::AsPath(p) | AttributeValue::As4Path(p) => Some(p.clone()),
+            _ => None,
+        }).unwrap();
+
+        assert_eq!(path.segments.len(), 3);
+        let parsed_asns: Vec<u32> = path.segments.iter().flat_map(|s| match s {
+            AsPathSegment::AsSequence(v) => v.iter().map(|a| a.asn).collect::<Vec<u32>>(),
+            _ => panic!("expecting AS_SEQUENCE segments"),
+        }).collect();
+        assert_eq!(parsed_asns, asns.iter().map(|a| a.asn).collect::<Vec<u32>>());
+    }
+
+    #[test]
+    fn test_compose_two_octet_asn_session() {
+        let mut elem = test_elem();
+        elem.as_path = Some(AsPath::from_segments(vec![AsPathSegment::AsSequence([100, 400000].map(|i|{i.into()}).to_vec())]));
+        elem.aggr_asn = Some(Asn::from(400000));
+        elem.aggr_ip = Some(IpAddr::from(Ipv4Addr::from([4,3,2,2])));
+
+        let mut composer = BgpUpdatesComposer::with_asn_length(AsnLength::Bits16);
+        composer.add_elem(&elem).unwrap();
+        let bytes = composer.export_bytes().unwrap();
+
+        let record = parse_mrt_record(&mut bytes.as_slice()).unwrap();
+        assert_eq!(record.common_header.entry_subtype, 1);
+        let attributes = match record.message {
+            MrtMessage::Bgp4Mp(Bgp4Mp::Bgp4MpMessage(m)) => match m.bgp_message {
+                BgpMessage::Update(u) => u.attributes,
+                _ => panic!("expecting update message"),
+            },
+            _ => panic!("expecting BGP4MP message"),
+        };
+
+        let find = |t: AttrType| attributes.iter().find(|a| a.attr_type == t).unwrap().value.clone();
+        let as_trans = Asn{ asn: 23456, len: AsnLength::Bits16 };
+        assert_eq!(find(AttrType::AS_PATH), AttributeValue::AsPath(AsPath::from_segments(vec![
+            AsPathSegment::AsSequence(vec![Asn{ asn: 100, len: AsnLength::Bits16 }, as_trans])
+        ])));
+        assert_eq!(find(AttrType::AS4_PATH), AttributeValue::AsPath(AsPath::from_segments(vec![
+            AsPathSegment::AsSequence(vec![Asn::from(100), Asn::from(400000)])
+        ])));
+        assert_eq!(find(AttrType::AGGREGATOR), AttributeValue::Aggregator(as_trans, IpAddr::from(Ipv4Addr::from([4,3,2,2]))));
+        assert_eq!(find(AttrType::AS4_AGGREGATOR), AttributeValue::Aggregator(Asn::from(400000), IpAddr::from(Ipv4Addr::from([4,3,2,2]))));
+    }
+
+    #[test]
+    fn test_compose_errors_and_timestamps() {
+        let mut elem = BgpElem {
+            timestamp: 1638679800.999999,
+            elem_type: ElemType::WITHDRAW,
+            next_hop: None,
+            as_path: None,
+            origin: None,
+            ..test_elem()
+        };
+
+        let mut composer = BgpUpdatesComposer::new();
+        composer.add_elem(&elem).unwrap();
+        let header = &composer.mrt_records[0].common_header;
+        assert_eq!((header.timestamp, header.microsecond_timestamp), (1638679800, Some(999999)));
+
+        // aggregator ASN without aggregator IP
+        elem.aggr_asn = Some(Asn::from(100));
+        assert!(matches!(composer.add_elem(&elem), Err(ComposeError::MissingField { field: "aggr_ip", .. })));
+
+        // host bits are cleared, or rejected in strict mode
+        let elem = BgpElem { prefix: NetworkPrefix::from_str("10.2.2.1/24").unwrap(), ..test_elem() };
+        let mut composer = BgpUpdatesComposer::new();
+        composer.add_elem(&elem).unwrap();
+        composer.set_strict_prefixes(true);
+        assert!(matches!(
+            composer.add_elem(&elem),
+            Err(ComposeError::DumpError(DumpError::HostBitsSet(_)))
+        ));
+        assert_eq!(composer.mrt_records.len(), 1);
+    }
+
+    #[test]
+    fn test_compose_community_range() {
+        let two_octet_ec = |asn: u32, local: [u8; 4]| MetaCommunity::ExtendedCommunity(
+            ExtendedCommunity::TransitiveTwoOctetAsSpecific(TwoOctetAsSpecific {
+                ec_type: 0x00,
+                ec_subtype: 0x02,
+                global_administrator: Asn::from(asn),
+                local_administrator: local,
+            })
+        );
+        let mut elem = test_elem();
+        elem.communities = Some(vec![
+            MetaCommunity::Community(Community::Custom(Asn::from(100), 1)),
+            MetaCommunity::Community(Community::Custom(Asn::from(400000), 2)),
+            two_octet_ec(400000, [0, 0, 0, 3]),
+        ]);
+
+        // out of range values are an error by default
+        let mut composer = BgpUpdatesComposer::new();
+        composer.add_elem(&elem).unwrap();
+        assert!(matches!(
+            composer.export_bytes(),
+            Err(ComposeError::DumpError(DumpError::Record { source, .. })) if matches!(*source, DumpError::ValueOutOfRange { record: "COMMUNITIES", .. })
+        ));
+
+        let mut composer = BgpUpdatesComposer::new();
+        composer.set_community_range_policy(CommunityRangePolicy::Convert);
+        composer.add_elem(&elem).unwrap();
+        let bytes = composer.export_bytes().unwrap();
+        let record = parse_mrt_record(&mut bytes.as_slice()).unwrap();
+        let attributes = match record.message {
+            MrtMessage::Bgp4Mp(Bgp4Mp::Bgp4MpMessage(m)) => match m.bgp_message {
+                BgpMessage::Update(u) => u.attributes,
+                _ => panic!("expecting update message"),
+            },
+            _ => panic!("expecting BGP4MP message"),
+        };
+        let find = |t: AttrType| attributes.iter().find(|a| a.attr_type == t).unwrap().value.clone();
+        assert_eq!(find(AttrType::COMMUNITIES), AttributeValue::Communities(vec![Community::Custom(Asn{ asn: 100, len: AsnLength::Bits16 }, 1)]));
+        assert_eq!(find(AttrType::LARGE_COMMUNITIES), AttributeValue::LargeCommunities(vec![LargeCommunity::new(400000, [2, 0])]));
+        assert_eq!(find(AttrType::EXTENDED_COMMUNITIES), AttributeValue::ExtendedCommunities(vec![
+            ExtendedCommunity::TransitiveFourOctetAsSpecific(FourOctetAsSpecific {
+                ec_type: 0x02,
+                ec_subtype: 0x02,
+                global_administrator: Asn::from(400000),
+                local_administrator: [0, 3],
+            })
+        ]));
+
+        // local administrator does not fit in a four-octet AS specific extended community
+        elem.communities = Some(vec![two_octet_ec(400000, [0, 1, 0, 3])]);
+        let mut composer = BgpUpdatesComposer::new();
+        composer.set_community_range_policy(CommunityRangePolicy::Convert);
+        composer.add_elem(&elem).unwrap();
+        assert!(composer.export_bytes().is_err());
+    }
+
+    #[test]
+    fn test_compose_ipv6_next_hop() {
+        let next_hop = Ipv6Addr::from_str("2001:db8::1").unwrap();
+        let link_local = Ipv6Addr::from_str("fe80::1").unwrap();
+        let mut elem = test_elem();
+        elem.peer_ip = IpAddr::from(next_hop);
+        elem.next_hop = Some(IpAddr::from(next_hop));
+        let mut ipv6_withdrawal = elem.clone();
+        ipv6_withdrawal.elem_type = ElemType::WITHDRAW;
+        ipv6_withdrawal.prefix = NetworkPrefix::from_str("2001:db8:1::/48").unwrap();
+
+        let mut composer = BgpUpdatesComposer::new();
+        composer.add_elem(&elem).unwrap();
+        composer.add_elem_with_next_hop(&elem, NextHopAddress::Ipv6LinkLocal(next_hop, link_local)).unwrap();
+        composer.add_elem(&ipv6_withdrawal).unwrap();
+        let bytes = composer.export_bytes().unwrap();
+
+        let mut data = bytes.as_slice();
+        let mut updates = vec![];
+        while !data.is_empty() {
+            match parse_mrt_record(&mut data).unwrap().message {
+                MrtMessage::Bgp4Mp(Bgp4Mp::Bgp4MpMessage(m)) | MrtMessage::Bgp4Mp(Bgp4Mp::Bgp4MpMessageAs4(m)) => {
+                    match m.bgp_message {
+                        BgpMessage::Update(u) => updates.push(u),
+                        m => panic!("unexpected message {:?}", m),
+                    }
+                }
+                m => panic!("unexpected message {:?}", m),
+            }
+        }
+        assert_eq!(updates.len(), 3);
+
+        // IPv4 prefixes with IPv6 next hops are announced in MP_REACH_NLRI with AFI 1
+        let expected_next_hops = [NextHopAddress::Ipv6(next_hop), NextHopAddress::Ipv6LinkLocal(next_hop, link_local)];
+        for (update, expected_next_hop) in updates.iter().zip(expected_next_hops) {
+            assert!(update.announced_prefixes.is_empty());
+            assert!(!update.attributes.iter().any(|a| a.attr_type == AttrType::NEXT_HOP));
+            let nlri = update.attributes.iter().find_map(|a| match &a.value {
+                AttributeValue::MpReachNlri(nlri) => Some(nlri),
+                _ => None,
+            }).unwrap();
+            assert_eq!(nlri.afi, Afi::Ipv4);
+            assert_eq!(nlri.next_hop, Some(expected_next_hop));
+            assert_eq!(nlri.prefixes, vec![elem.prefix]);
+        }
+
+        // IPv6 withdrawals use MP_UNREACH_NLRI
+        assert!(updates[2].withdrawn_prefixes.is_empty());
+        let nlri = updates[2].attributes.iter().find_map(|a| match &a.value {
+            AttributeValue::MpUnreachNlri(nlri) => Some(nlri),
+            _ => None,
+        }).unwrap();
+        assert_eq!(nlri.afi, Afi::Ipv6);
+        assert_eq!(nlri.prefixes, vec![ipv6_withdrawal.prefix]);
+    }
+
+    #[test]
+    fn test_compose_extra_attributes() {
+        let elem = test_elem();
+        let extra = vec![
+            OnlyToCustomer(Asn::from(65000)).to_attribute().unwrap(),
+            Aigp::with_metric(10).to_attribute().unwrap(),
+            // replaces the origin of the elem
+            Attribute { attr_type: AttrType::ORIGIN, value: AttributeValue::Origin(Origin::INCOMPLETE), flag: 0x40 }.into(),
+        ];
+
+        let mut composer = BgpUpdatesComposer::new();
+        composer.add_elem_with_attributes(&elem, &extra).unwrap();
+        let bytes = composer.export_bytes().unwrap();
+
+        let contains = |needle: &[u8]| bytes.windows(needle.len()).any(|w| w == needle);
+        assert!(contains(&[0xC0, 35, 4, 0, 0, 0xFD, 0xE8]));
+        assert!(contains(&[0x80, 26, 11, 1, 0, 11, 0, 0, 0, 0, 0, 0, 0, 10]));
+        assert!(contains(&[0x40, 1, 1, 2]));
+        assert!(!contains(&[0x40, 1, 1, 0]));
+    }
+
+    #[test]
+    fn test_compose_ipv6_extended_communities() {
+        let address = Ipv6Addr::from_str("2001:db8::1").unwrap();
+        let mut elem = test_elem();
+        elem.communities = Some(vec![
+            TypedExtendedCommunity::route_target_as(65000, 1).to_meta_community().unwrap(),
+            TypedExtendedCommunity::route_target_ipv6(address, 1).to_meta_community().unwrap(),
+        ]);
+
+        let mut composer = BgpUpdatesComposer::new();
+        composer.add_elem(&elem).unwrap();
+        let bytes = composer.export_bytes().unwrap();
+
+        let contains = |needle: &[u8]| bytes.windows(needle.len()).any(|w| w == needle);
+        assert!(contains(&[0x40, 16, 8, 0x00, 0x02, 0xFD, 0xE8, 0, 0, 0, 1]));
+        let mut ipv6_attribute = vec![0xC0, 25, 20, 0x00, 0x02];
+        ipv6_attribute.extend(address.octets());
+        ipv6_attribute.extend([0, 1]);
+        assert!(contains(&ipv6_attribute));
+    }
+}
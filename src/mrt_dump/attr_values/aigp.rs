@@ -0,0 +1,77 @@
+use crate::DumpError;
+use crate::mrt_dump::attr_values::{AttrValueDump, FLAG_OPTIONAL};
+use crate::mrt_dump::utils::{length_u16, WriteUtils};
+
+/// attribute type code of AIGP
+const ATTR_TYPE_AIGP: u8 = 26;
+/// TLV type of the accumulated IGP metric
+const TLV_TYPE_AIGP: u8 = 1;
+
+/// Accumulated IGP Metric attribute.
+/// https://datatracker.ietf.org/doc/html/rfc7311#section-3
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Aigp {
+    pub tlvs: Vec<AigpTlv>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AigpTlv {
+    /// The accumulated IGP metric.
+    Metric(u64),
+    /// Any other TLV, written as is.
+    Unknown { tlv_type: u8, value: Vec<u8> },
+}
+
+impl Aigp {
+    /// AIGP attribute with a single metric TLV.
+    pub fn with_metric(metric: u64) -> Self {
+        Aigp { tlvs: vec![AigpTlv::Metric(metric)] }
+    }
+}
+
+impl AttrValueDump for Aigp {
+    fn type_code(&self) -> u8 {
+        ATTR_TYPE_AIGP
+    }
+
+    fn flag(&self) -> u8 {
+        FLAG_OPTIONAL
+    }
+
+    fn value_bytes(&self) -> Result<Vec<u8>, DumpError> {
+        let mut buffer: Vec<u8> = vec![];
+        for tlv in &self.tlvs {
+            let (tlv_type, value) = match tlv {
+                AigpTlv::Metric(metric) => (TLV_TYPE_AIGP, metric.to_be_bytes().to_vec()),
+                AigpTlv::Unknown { tlv_type, value } => (*tlv_type, value.clone()),
+            };
+            // the TLV length includes the type and length fields
+            buffer.push(tlv_type);
+            buffer.write_16b(length_u16(value.len() + 3, "AIGP", "TLV length")?)?;
+            buffer.extend(value);
+        }
+        Ok(buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mrt_dump::attr_values::assert_round_trip;
+
+    use super::*;
+
+    #[test]
+    fn test_aigp() {
+        let aigp = Aigp {
+            tlvs: vec![AigpTlv::Metric(100), AigpTlv::Unknown { tlv_type: 9, value: vec![1, 2] }],
+        };
+        let raw = aigp.to_raw_attribute().unwrap();
+        assert_eq!(raw.flag, 0x80);
+        assert_eq!(raw.attr_type, 26);
+        assert_eq!(raw.value, [1, 0, 11, 0, 0, 0, 0, 0, 0, 0, 100, 9, 0, 5, 1, 2]);
+        assert_round_trip(&aigp);
+
+        let too_long = Aigp { tlvs: vec![AigpTlv::Unknown { tlv_type: 9, value: vec![0; 65533] }] };
+        assert!(matches!(too_long.value_bytes(), Err(DumpError::LengthOverflow { .. })));
+    }
+}
@@ -0,0 +1,101 @@
+use bgp_models::bgp::AttrType;
+use bgp_models::network::{Asn, AsnLength};
+
+use crate::{DumpError, PathAttribute};
+use crate::mrt_dump::attr_values::{AttrValueDump, FLAG_OPTIONAL_TRANSITIVE};
+use crate::mrt_dump::attributes::MrtAttrDump;
+
+/// attribute type code of ATTR_SET
+const ATTR_TYPE_ATTR_SET: u8 = 128;
+
+/// ATTR_SET attribute, carrying the customer's path attributes across a provider network.
+/// https://datatracker.ietf.org/doc/html/rfc6368#section-5
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttrSet {
+    pub origin_asn: Asn,
+    /// The nested path attributes, which may include raw attributes and further ATTR_SETs.
+    pub attributes: Vec<PathAttribute>,
+}
+
+impl AttrValueDump for AttrSet {
+    fn type_code(&self) -> u8 {
+        ATTR_TYPE_ATTR_SET
+    }
+
+    fn flag(&self) -> u8 {
+        FLAG_OPTIONAL_TRANSITIVE
+    }
+
+    fn value_bytes(&self) -> Result<Vec<u8>, DumpError> {
+        let mut buffer: Vec<u8> = self.origin_asn.asn.to_be_bytes().to_vec();
+        for attr in &self.attributes {
+            // the nested attributes describe the route, not its reachability
+            let type_code = attr.type_code();
+            if type_code == AttrType::MP_REACHABLE_NLRI as u8 || type_code == AttrType::MP_UNREACHABLE_NLRI as u8 {
+                return Err(DumpError::UnsupportedType {
+                    record: "ATTR_SET",
+                    field: "nested attribute",
+                    value: type_code.to_string(),
+                })
+            }
+            // nested attributes are encoded with 4-byte ASNs, like the origin AS
+            buffer.extend(attr.to_bytes(false, AsnLength::Bits32, true, true, true)?);
+        }
+        Ok(buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bgp_models::prelude::*;
+
+    use crate::OnlyToCustomer;
+    use crate::mrt_dump::attr_values::assert_round_trip;
+
+    use super::*;
+
+    #[test]
+    fn test_attr_set() {
+        let inner = AttrSet {
+            origin_asn: Asn::from(65002),
+            attributes: vec![
+                Attribute { attr_type: AttrType::LOCAL_PREFERENCE, value: AttributeValue::LocalPreference(100), flag: 0x40 }.into(),
+            ],
+        };
+        let attr_set = AttrSet {
+            origin_asn: Asn::from(400000),
+            attributes: vec![
+                Attribute { attr_type: AttrType::ORIGIN, value: AttributeValue::Origin(Origin::IGP), flag: 0x40 }.into(),
+                Attribute {
+                    attr_type: AttrType::AS_PATH,
+                    value: AttributeValue::AsPath(AsPath::from_segments(vec![AsPathSegment::AsSequence(vec![Asn::from(400000)])])),
+                    flag: 0x40,
+                }.into(),
+                OnlyToCustomer(Asn::from(65001)).to_attribute().unwrap(),
+                inner.to_attribute().unwrap(),
+            ],
+        };
+
+        let raw = attr_set.to_raw_attribute().unwrap();
+        assert_eq!(raw.attr_type, 128);
+        assert_eq!(raw.flag, 0xC0);
+        assert_eq!(raw.value, [
+            0, 6, 0x1A, 0x80,
+            0x40, 1, 1, 0,
+            0x40, 2, 6, 2, 1, 0, 6, 0x1A, 0x80,
+            0xC0, 35, 4, 0, 0, 0xFD, 0xE9,
+            0xC0, 128, 11, 0, 0, 0xFD, 0xEA, 0x40, 5, 4, 0, 0, 0, 100,
+        ]);
+        assert_round_trip(&attr_set);
+
+        let invalid = AttrSet {
+            origin_asn: Asn::from(1),
+            attributes: vec![Attribute {
+                attr_type: AttrType::MP_UNREACHABLE_NLRI,
+                value: AttributeValue::MpUnreachNlri(Nlri { afi: Afi::Ipv4, safi: Safi::Unicast, next_hop: None, prefixes: vec![] }),
+                flag: 0x80,
+            }.into()],
+        };
+        assert!(matches!(invalid.value_bytes(), Err(DumpError::UnsupportedType { .. })));
+    }
+}
@@ -0,0 +1,157 @@
+use bgp_models::network::Asn;
+
+use crate::DumpError;
+use crate::mrt_dump::attr_values::{AttrValueDump, FLAG_OPTIONAL};
+use crate::mrt_dump::utils::{length_u16, WriteUtils};
+
+/// attribute type code of BGPsec_PATH
+const ATTR_TYPE_BGPSEC_PATH: u8 = 33;
+/// Confed_Segment flag of a Secure_Path segment
+pub const SECURE_PATH_CONFED_SEGMENT: u8 = 0x80;
+/// length of a Subject Key Identifier
+pub const SKI_LENGTH: usize = 20;
+/// maximum number of Signature_Blocks, one per algorithm suite during algorithm transitions
+const MAX_SIGNATURE_BLOCKS: usize = 2;
+
+/// BGPsec_PATH attribute.
+/// https://datatracker.ietf.org/doc/html/rfc8205#section-3
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BgpsecPath {
+    /// Secure_Path segments, the most recently added AS first.
+    pub secure_path: Vec<SecurePathSegment>,
+    pub signature_blocks: Vec<SignatureBlock>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SecurePathSegment {
+    /// number of times the AS is repeated in the path
+    pub pcount: u8,
+    pub flags: u8,
+    pub asn: Asn,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureBlock {
+    pub algorithm_suite: u8,
+    /// Signature segments in the order of the Secure_Path segments.
+    pub signatures: Vec<SignatureSegment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureSegment {
+    pub ski: [u8; SKI_LENGTH],
+    pub signature: Vec<u8>,
+}
+
+impl SecurePathSegment {
+    pub fn new(asn: Asn, pcount: u8) -> Self {
+        SecurePathSegment { pcount, flags: 0, asn }
+    }
+
+    pub fn is_confed_segment(&self) -> bool {
+        self.flags & SECURE_PATH_CONFED_SEGMENT != 0
+    }
+}
+
+impl AttrValueDump for BgpsecPath {
+    fn type_code(&self) -> u8 {
+        ATTR_TYPE_BGPSEC_PATH
+    }
+
+    fn flag(&self) -> u8 {
+        FLAG_OPTIONAL
+    }
+
+    fn value_bytes(&self) -> Result<Vec<u8>, DumpError> {
+        if self.signature_blocks.is_empty() || self.signature_blocks.len() > MAX_SIGNATURE_BLOCKS {
+            return Err(DumpError::InvalidArgument {
+                name: "BGPsec_PATH signature block count",
+                value: self.signature_blocks.len().to_string(),
+            })
+        }
+
+        let mut buffer: Vec<u8> = vec![];
+
+        // the Secure_Path and Signature_Block lengths include their own length fields
+        buffer.write_16b(length_u16(2 + self.secure_path.len() * 6, "BGPsec_PATH", "Secure_Path length")?)?;
+        for segment in &self.secure_path {
+            buffer.push(segment.pcount);
+            buffer.push(segment.flags);
+            buffer.write_32b(segment.asn.asn)?;
+        }
+
+        for block in &self.signature_blocks {
+            // each AS in the Secure_Path signs once per algorithm suite
+            if block.signatures.len() != self.secure_path.len() {
+                return Err(DumpError::InvalidArgument {
+                    name: "BGPsec_PATH signature count",
+                    value: format!("{} signatures for {} Secure_Path segments", block.signatures.len(), self.secure_path.len()),
+                })
+            }
+            let mut block_buf: Vec<u8> = vec![block.algorithm_suite];
+            for signature in &block.signatures {
+                block_buf.extend_from_slice(&signature.ski);
+                block_buf.write_16b(length_u16(signature.signature.len(), "BGPsec_PATH", "signature length")?)?;
+                block_buf.extend_from_slice(&signature.signature);
+            }
+            buffer.write_16b(length_u16(block_buf.len() + 2, "BGPsec_PATH", "Signature_Block length")?)?;
+            buffer.extend(block_buf);
+        }
+        Ok(buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::RawAttribute;
+    use crate::mrt_dump::attr_values::assert_round_trip;
+
+    use super::*;
+
+    fn bgpsec_path() -> BgpsecPath {
+        let mut confed = SecurePathSegment::new(Asn::from(65001), 1);
+        confed.flags = SECURE_PATH_CONFED_SEGMENT;
+        BgpsecPath {
+            secure_path: vec![SecurePathSegment::new(Asn::from(400000), 2), confed],
+            signature_blocks: vec![SignatureBlock {
+                algorithm_suite: 1,
+                signatures: vec![
+                    SignatureSegment { ski: [0xAA; SKI_LENGTH], signature: vec![1, 2, 3] },
+                    SignatureSegment { ski: [0xBB; SKI_LENGTH], signature: vec![4] },
+                ],
+            }],
+        }
+    }
+
+    #[test]
+    fn test_bgpsec_path_bytes() {
+        let mut expected: Vec<u8> = vec![
+            // Secure_Path
+            0, 14,
+            2, 0, 0, 6, 0x1A, 0x80,
+            1, 0x80, 0, 0, 0xFD, 0xE9,
+            // Signature_Block length and algorithm suite
+            0, 51, 1,
+        ];
+        expected.extend([0xAA; SKI_LENGTH]);
+        expected.extend([0, 3, 1, 2, 3]);
+        expected.extend([0xBB; SKI_LENGTH]);
+        expected.extend([0, 1, 4]);
+
+        let path = bgpsec_path();
+        assert!(path.secure_path[1].is_confed_segment());
+        let raw = path.to_raw_attribute().unwrap();
+        assert_eq!(raw, RawAttribute::new(33, 0x80, expected));
+
+        let mut invalid = path.clone();
+        invalid.signature_blocks[0].signatures.pop();
+        assert!(invalid.value_bytes().is_err());
+        invalid.signature_blocks.clear();
+        assert!(invalid.value_bytes().is_err());
+    }
+
+    #[test]
+    fn test_bgpsec_path_round_trip() {
+        assert_round_trip(&bgpsec_path());
+    }
+}
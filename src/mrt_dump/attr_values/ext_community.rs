@@ -0,0 +1,353 @@
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use bgp_models::prelude::*;
+
+use crate::DumpError;
+
+// extended community types and subtypes
+// https://www.iana.org/assignments/bgp-extended-communities/bgp-extended-communities.xhtml
+const EC_TYPE_TRANSITIVE_TWO_OCTET_AS: u8 = 0x00;
+const EC_TYPE_TRANSITIVE_IPV4: u8 = 0x01;
+const EC_TYPE_TRANSITIVE_FOUR_OCTET_AS: u8 = 0x02;
+const EC_TYPE_TRANSITIVE_OPAQUE: u8 = 0x03;
+const EC_TYPE_EVPN: u8 = 0x06;
+const EC_TYPE_NON_TRANSITIVE_TWO_OCTET_AS: u8 = 0x40;
+const EC_TYPE_FLOWSPEC_TWO_OCTET_AS: u8 = 0x80;
+const EC_TYPE_FLOWSPEC_IPV4: u8 = 0x81;
+const EC_TYPE_FLOWSPEC_FOUR_OCTET_AS: u8 = 0x82;
+/// IPv6 address specific communities are carried in their own attribute
+const EC_TYPE_TRANSITIVE_IPV6: u8 = 0x00;
+
+const EC_SUBTYPE_ROUTE_TARGET: u8 = 0x02;
+const EC_SUBTYPE_ROUTE_ORIGIN: u8 = 0x03;
+const EC_SUBTYPE_LINK_BANDWIDTH: u8 = 0x04;
+const EC_SUBTYPE_ENCAPSULATION: u8 = 0x0c;
+const EC_SUBTYPE_MAC_MOBILITY: u8 = 0x00;
+const EC_SUBTYPE_TRAFFIC_RATE: u8 = 0x06;
+const EC_SUBTYPE_TRAFFIC_ACTION: u8 = 0x07;
+const EC_SUBTYPE_REDIRECT: u8 = 0x08;
+const EC_SUBTYPE_TRAFFIC_MARKING: u8 = 0x09;
+
+const MAC_MOBILITY_STICKY: u8 = 0x01;
+const TRAFFIC_ACTION_SAMPLE: u8 = 0x02;
+const TRAFFIC_ACTION_TERMINAL: u8 = 0x01;
+const MAX_DSCP: u8 = 0x3F;
+
+/// Global administrator of route targets, route origins and flowspec redirects.
+///
+/// ASNs up to 65535 use the two-octet AS specific format with a 4-byte local administrator, all
+/// other administrators have a 2-byte local administrator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlobalAdministrator {
+    As(u32),
+    Ipv4(Ipv4Addr),
+    /// Encoded as IPv6 address specific community, which is only valid in the
+    /// IPV6_ADDRESS_SPECIFIC_EXTENDED_COMMUNITIES attribute.
+    /// https://datatracker.ietf.org/doc/html/rfc5701
+    Ipv6(Ipv6Addr),
+}
+
+/// Extended communities of well-known types and subtypes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TypedExtendedCommunity {
+    /// https://datatracker.ietf.org/doc/html/rfc4360#section-4
+    RouteTarget { global: GlobalAdministrator, local: u32 },
+    RouteOrigin { global: GlobalAdministrator, local: u32 },
+    /// Bandwidth of the link to the peer in bytes per second.
+    /// https://datatracker.ietf.org/doc/html/draft-ietf-idr-link-bandwidth
+    LinkBandwidth { asn: u16, bandwidth: f32 },
+    /// https://datatracker.ietf.org/doc/html/rfc9012#section-6
+    Encapsulation { tunnel_type: u16 },
+    /// https://datatracker.ietf.org/doc/html/rfc7432#section-7.7
+    MacMobility { sticky: bool, sequence: u32 },
+    /// Rate limit in bytes per second, 0 discards the traffic.
+    /// https://datatracker.ietf.org/doc/html/rfc8955#section-7.3
+    FlowspecTrafficRate { asn: u16, rate: f32 },
+    FlowspecTrafficAction { sample: bool, terminal: bool },
+    /// Redirect to the VRF with the route target, IPv6 administrators are not supported.
+    FlowspecRedirect { global: GlobalAdministrator, local: u32 },
+    FlowspecTrafficMarking { dscp: u8 },
+}
+
+fn check_local(local: u32, max: u32) -> Result<(), DumpError> {
+    if local > max {
+        return Err(DumpError::ValueOutOfRange {
+            record: "EXTENDED_COMMUNITIES",
+            field: "local administrator",
+            value: local as u64,
+            max: max as u64,
+        })
+    }
+    Ok(())
+}
+
+/// Build an AS or IPv4 address specific community of the given type class.
+fn administrator_community(
+    global: GlobalAdministrator,
+    local: u32,
+    ec_types: [u8; 3],
+    ec_subtype: u8,
+) -> Result<ExtendedCommunity, DumpError> {
+    let [two_octet_as, ipv4, four_octet_as] = ec_types;
+    match global {
+        GlobalAdministrator::As(asn) if asn <= u16::MAX as u32 => {
+            Ok(ExtendedCommunity::TransitiveTwoOctetAsSpecific(TwoOctetAsSpecific {
+                ec_type: two_octet_as,
+                ec_subtype,
+                global_administrator: Asn { asn, len: AsnLength::Bits16 },
+                local_administrator: local.to_be_bytes(),
+            }))
+        }
+        GlobalAdministrator::As(asn) => {
+            check_local(local, u16::MAX as u32)?;
+            Ok(ExtendedCommunity::TransitiveFourOctetAsSpecific(FourOctetAsSpecific {
+                ec_type: four_octet_as,
+                ec_subtype,
+                global_administrator: Asn { asn, len: AsnLength::Bits32 },
+                local_administrator: (local as u16).to_be_bytes(),
+            }))
+        }
+        GlobalAdministrator::Ipv4(address) => {
+            check_local(local, u16::MAX as u32)?;
+            Ok(ExtendedCommunity::TransitiveIpv4AddressSpecific(Ipv4AddressSpecific {
+                ec_type: ipv4,
+                ec_subtype,
+                global_administrator: address,
+                local_administrator: (local as u16).to_be_bytes(),
+            }))
+        }
+        GlobalAdministrator::Ipv6(address) => {
+            check_local(local, u16::MAX as u32)?;
+            Ok(ExtendedCommunity::Ipv6AddressSpecific(Ipv6AddressSpecific {
+                ec_type: EC_TYPE_TRANSITIVE_IPV6,
+                ec_subtype,
+                global_administrator: address,
+                local_administrator: (local as u16).to_be_bytes(),
+            }))
+        }
+    }
+}
+
+/// Flowspec communities use types the data model has no variant for, they are kept raw.
+fn raw_community(ec_type: u8, ec_subtype: u8, value: [u8; 6]) -> ExtendedCommunity {
+    let mut bytes = [0u8; 8];
+    bytes[0] = ec_type;
+    bytes[1] = ec_subtype;
+    bytes[2..].copy_from_slice(&value);
+    ExtendedCommunity::Raw(bytes)
+}
+
+fn as_and_float(asn: u16, value: f32) -> [u8; 6] {
+    let mut bytes = [0u8; 6];
+    bytes[..2].copy_from_slice(&asn.to_be_bytes());
+    bytes[2..].copy_from_slice(&value.to_be_bytes());
+    bytes
+}
+
+impl TypedExtendedCommunity {
+    pub fn route_target_as(asn: u32, local: u32) -> Self {
+        TypedExtendedCommunity::RouteTarget { global: GlobalAdministrator::As(asn), local }
+    }
+
+    pub fn route_target_ipv4(address: Ipv4Addr, local: u16) -> Self {
+        TypedExtendedCommunity::RouteTarget { global: GlobalAdministrator::Ipv4(address), local: local as u32 }
+    }
+
+    pub fn route_target_ipv6(address: Ipv6Addr, local: u16) -> Self {
+        TypedExtendedCommunity::RouteTarget { global: GlobalAdministrator::Ipv6(address), local: local as u32 }
+    }
+
+    pub fn route_origin_as(asn: u32, local: u32) -> Self {
+        TypedExtendedCommunity::RouteOrigin { global: GlobalAdministrator::As(asn), local }
+    }
+
+    pub fn link_bandwidth(asn: u16, bandwidth: f32) -> Self {
+        TypedExtendedCommunity::LinkBandwidth { asn, bandwidth }
+    }
+
+    /// Convert to the extended community that encodes to the wire format of this community.
+    pub fn to_extended_community(&self) -> Result<ExtendedCommunity, DumpError> {
+        let transitive_types = [EC_TYPE_TRANSITIVE_TWO_OCTET_AS, EC_TYPE_TRANSITIVE_IPV4, EC_TYPE_TRANSITIVE_FOUR_OCTET_AS];
+        match *self {
+            TypedExtendedCommunity::RouteTarget { global, local } => {
+                administrator_community(global, local, transitive_types, EC_SUBTYPE_ROUTE_TARGET)
+            }
+            TypedExtendedCommunity::RouteOrigin { global, local } => {
+                administrator_community(global, local, transitive_types, EC_SUBTYPE_ROUTE_ORIGIN)
+            }
+            TypedExtendedCommunity::LinkBandwidth { asn, bandwidth } => {
+                Ok(ExtendedCommunity::NonTransitiveTwoOctetAsSpecific(TwoOctetAsSpecific {
+                    ec_type: EC_TYPE_NON_TRANSITIVE_TWO_OCTET_AS,
+                    ec_subtype: EC_SUBTYPE_LINK_BANDWIDTH,
+                    global_administrator: Asn { asn: asn as u32, len: AsnLength::Bits16 },
+                    local_administrator: bandwidth.to_be_bytes(),
+                }))
+            }
+            TypedExtendedCommunity::Encapsulation { tunnel_type } => {
+                let mut value = [0u8; 6];
+                value[4..].copy_from_slice(&tunnel_type.to_be_bytes());
+                Ok(ExtendedCommunity::TransitiveOpaque(Opaque {
+                    ec_type: EC_TYPE_TRANSITIVE_OPAQUE,
+                    ec_subtype: EC_SUBTYPE_ENCAPSULATION,
+                    value,
+                }))
+            }
+            TypedExtendedCommunity::MacMobility { sticky, sequence } => {
+                let mut value = [0u8; 6];
+                if sticky {
+                    value[0] = MAC_MOBILITY_STICKY;
+                }
+                value[2..].copy_from_slice(&sequence.to_be_bytes());
+                Ok(raw_community(EC_TYPE_EVPN, EC_SUBTYPE_MAC_MOBILITY, value))
+            }
+            TypedExtendedCommunity::FlowspecTrafficRate { asn, rate } => {
+                Ok(raw_community(EC_TYPE_FLOWSPEC_TWO_OCTET_AS, EC_SUBTYPE_TRAFFIC_RATE, as_and_float(asn, rate)))
+            }
+            TypedExtendedCommunity::FlowspecTrafficAction { sample, terminal } => {
+                let mut value = [0u8; 6];
+                if sample {
+                    value[5] |= TRAFFIC_ACTION_SAMPLE;
+                }
+                if terminal {
+                    value[5] |= TRAFFIC_ACTION_TERMINAL;
+                }
+                Ok(raw_community(EC_TYPE_FLOWSPEC_TWO_OCTET_AS, EC_SUBTYPE_TRAFFIC_ACTION, value))
+            }
+            TypedExtendedCommunity::FlowspecRedirect { global: GlobalAdministrator::Ipv6(_), .. } => {
+                Err(DumpError::UnsupportedType {
+                    record: "EXTENDED_COMMUNITIES",
+                    field: "flowspec redirect global administrator",
+                    value: "IPv6".to_string(),
+                })
+            }
+            TypedExtendedCommunity::FlowspecRedirect { global, local } => {
+                let flowspec_types = [EC_TYPE_FLOWSPEC_TWO_OCTET_AS, EC_TYPE_FLOWSPEC_IPV4, EC_TYPE_FLOWSPEC_FOUR_OCTET_AS];
+                // same layout as a route target, with the flowspec types
+                let mut bytes = [0u8; 8];
+                match administrator_community(global, local, flowspec_types, EC_SUBTYPE_REDIRECT)? {
+                    ExtendedCommunity::TransitiveTwoOctetAsSpecific(c) => {
+                        bytes[..2].copy_from_slice(&[c.ec_type, c.ec_subtype]);
+                        bytes[2..4].copy_from_slice(&(c.global_administrator.asn as u16).to_be_bytes());
+                        bytes[4..].copy_from_slice(&c.local_administrator);
+                    }
+                    ExtendedCommunity::TransitiveFourOctetAsSpecific(c) => {
+                        bytes[..2].copy_from_slice(&[c.ec_type, c.ec_subtype]);
+                        bytes[2..6].copy_from_slice(&c.global_administrator.asn.to_be_bytes());
+                        bytes[6..].copy_from_slice(&c.local_administrator);
+                    }
+                    ExtendedCommunity::TransitiveIpv4AddressSpecific(c) => {
+                        bytes[..2].copy_from_slice(&[c.ec_type, c.ec_subtype]);
+                        bytes[2..6].copy_from_slice(&c.global_administrator.octets());
+                        bytes[6..].copy_from_slice(&c.local_administrator);
+                    }
+                    c => unreachable!("unexpected redirect community {:?}", c),
+                }
+                Ok(ExtendedCommunity::Raw(bytes))
+            }
+            TypedExtendedCommunity::FlowspecTrafficMarking { dscp } => {
+                if dscp > MAX_DSCP {
+                    return Err(DumpError::ValueOutOfRange {
+                        record: "EXTENDED_COMMUNITIES",
+                        field: "DSCP",
+                        value: dscp as u64,
+                        max: MAX_DSCP as u64,
+                    })
+                }
+                Ok(raw_community(EC_TYPE_FLOWSPEC_TWO_OCTET_AS, EC_SUBTYPE_TRAFFIC_MARKING, [0, 0, 0, 0, 0, dscp]))
+            }
+        }
+    }
+
+    /// Convert to a community that can be added to [BgpElem::communities].
+    pub fn to_meta_community(&self) -> Result<MetaCommunity, DumpError> {
+        Ok(MetaCommunity::ExtendedCommunity(self.to_extended_community()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::mrt_dump::attributes::MrtAttrDump;
+
+    use super::*;
+
+    fn encode(comm: TypedExtendedCommunity) -> Vec<u8> {
+        let attr_type = match comm.to_extended_community().unwrap() {
+            ExtendedCommunity::Ipv6AddressSpecific(_) => AttrType::IPV6_ADDRESS_SPECIFIC_EXTENDED_COMMUNITIES,
+            _ => AttrType::EXTENDED_COMMUNITIES,
+        };
+        let attr = Attribute {
+            attr_type,
+            value: AttributeValue::ExtendedCommunities(vec![comm.to_extended_community().unwrap()]),
+            flag: 0xC0,
+        };
+        attr.to_bytes(false, AsnLength::Bits32, true, true, true).unwrap()[3..].to_vec()
+    }
+
+    #[test]
+    fn test_route_targets() {
+        assert_eq!(encode(TypedExtendedCommunity::route_target_as(65000, 100)), [0x00, 0x02, 0xFD, 0xE8, 0, 0, 0, 100]);
+        assert_eq!(encode(TypedExtendedCommunity::route_target_as(400000, 100)), [0x02, 0x02, 0, 6, 0x1A, 0x80, 0, 100]);
+        assert_eq!(encode(TypedExtendedCommunity::route_origin_as(65000, 1)), [0x00, 0x03, 0xFD, 0xE8, 0, 0, 0, 1]);
+        assert_eq!(
+            encode(TypedExtendedCommunity::route_target_ipv4(Ipv4Addr::from([192, 0, 2, 1]), 7)),
+            [0x01, 0x02, 192, 0, 2, 1, 0, 7]
+        );
+
+        let address = Ipv6Addr::from_str("2001:db8::1").unwrap();
+        let mut expected = vec![0x00, 0x02];
+        expected.extend(address.octets());
+        expected.extend([0, 7]);
+        assert_eq!(encode(TypedExtendedCommunity::route_target_ipv6(address, 7)), expected);
+
+        // the local administrator of 4-byte ASN, IPv4 and IPv6 communities has 2 bytes
+        let too_large = TypedExtendedCommunity::RouteTarget { global: GlobalAdministrator::Ipv6(address), local: 65536 };
+        assert!(matches!(too_large.to_extended_community(), Err(DumpError::ValueOutOfRange { .. })));
+        assert!(TypedExtendedCommunity::route_target_as(400000, 65536).to_extended_community().is_err());
+
+        // IPv6 address specific communities are only valid in their own attribute
+        let attr = Attribute {
+            attr_type: AttrType::EXTENDED_COMMUNITIES,
+            value: AttributeValue::ExtendedCommunities(vec![
+                TypedExtendedCommunity::route_target_ipv6(address, 7).to_extended_community().unwrap(),
+            ]),
+            flag: 0xC0,
+        };
+        assert!(matches!(
+            attr.to_bytes(false, AsnLength::Bits32, true, true, true),
+            Err(DumpError::UnsupportedType { .. })
+        ));
+    }
+
+    #[test]
+    fn test_well_known_communities() {
+        let mut expected = vec![0x40, 0x04, 0xFD, 0xE8];
+        expected.extend(125_000_000f32.to_be_bytes());
+        assert_eq!(encode(TypedExtendedCommunity::link_bandwidth(65000, 125_000_000.0)), expected);
+
+        assert_eq!(encode(TypedExtendedCommunity::Encapsulation { tunnel_type: 8 }), [0x03, 0x0c, 0, 0, 0, 0, 0, 8]);
+        assert_eq!(
+            encode(TypedExtendedCommunity::MacMobility { sticky: true, sequence: 3 }),
+            [0x06, 0x00, 1, 0, 0, 0, 0, 3]
+        );
+        assert_eq!(
+            encode(TypedExtendedCommunity::FlowspecTrafficRate { asn: 65000, rate: 0.0 }),
+            [0x80, 0x06, 0xFD, 0xE8, 0, 0, 0, 0]
+        );
+        assert_eq!(
+            encode(TypedExtendedCommunity::FlowspecTrafficAction { sample: true, terminal: true }),
+            [0x80, 0x07, 0, 0, 0, 0, 0, 3]
+        );
+        assert_eq!(
+            encode(TypedExtendedCommunity::FlowspecRedirect { global: GlobalAdministrator::As(65000), local: 100 }),
+            [0x80, 0x08, 0xFD, 0xE8, 0, 0, 0, 100]
+        );
+        assert_eq!(
+            encode(TypedExtendedCommunity::FlowspecRedirect { global: GlobalAdministrator::Ipv4(Ipv4Addr::from([192, 0, 2, 1])), local: 100 }),
+            [0x81, 0x08, 192, 0, 2, 1, 0, 100]
+        );
+        assert_eq!(encode(TypedExtendedCommunity::FlowspecTrafficMarking { dscp: 46 }), [0x80, 0x09, 0, 0, 0, 0, 0, 46]);
+        assert!(TypedExtendedCommunity::FlowspecTrafficMarking { dscp: 64 }.to_extended_community().is_err());
+    }
+}
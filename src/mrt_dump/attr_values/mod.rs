@@ -0,0 +1,120 @@
+//! Path attributes that [AttributeValue](bgp_models::prelude::AttributeValue) does not model.
+//!
+//! Each value is added to messages and RIB entries as [PathAttribute], or encoded into a
+//! [RawAttribute].
+//! Well-known extended communities are built with [TypedExtendedCommunity].
+
+use crate::DumpError;
+use crate::mrt_dump::{PathAttribute, RawAttribute};
+use crate::mrt_dump::utils::{length_u16, WriteUtils};
+
+pub use aigp::{Aigp, AigpTlv};
+pub use attr_set::AttrSet;
+pub use bgpsec::{BgpsecPath, SecurePathSegment, SignatureBlock, SignatureSegment, SECURE_PATH_CONFED_SEGMENT, SKI_LENGTH};
+pub use ext_community::{GlobalAdministrator, TypedExtendedCommunity};
+pub use otc::OnlyToCustomer;
+pub use pmsi_tunnel::{
+    PmsiLabel, PmsiTunnel, PmsiTunnelId, PMSI_FLAG_LEAF_INFORMATION_REQUIRED, PMSI_TUNNEL_TYPE_BIDIR_PIM,
+    PMSI_TUNNEL_TYPE_INGRESS_REPLICATION, PMSI_TUNNEL_TYPE_MLDP_MP2MP, PMSI_TUNNEL_TYPE_MLDP_P2MP,
+    PMSI_TUNNEL_TYPE_NO_TUNNEL_INFO, PMSI_TUNNEL_TYPE_PIM_SM, PMSI_TUNNEL_TYPE_PIM_SSM, PMSI_TUNNEL_TYPE_RSVP_TE_P2MP,
+};
+pub use prefix_sid::{
+    PrefixSid, PrefixSidTlv, SrgbRange, Srv6ServiceDataSubSubTlv, Srv6ServiceSubTlv, Srv6SidInformation,
+    Srv6SidStructure,
+};
+pub use tunnel_encap::{
+    TunnelEncapsulation, TunnelSubTlv, TunnelTlv, TUNNEL_TYPE_GENEVE, TUNNEL_TYPE_MPLS_IN_GRE, TUNNEL_TYPE_MPLS_IN_UDP,
+    TUNNEL_TYPE_NVGRE, TUNNEL_TYPE_SR_POLICY, TUNNEL_TYPE_VXLAN,
+};
+
+mod aigp;
+mod attr_set;
+mod bgpsec;
+mod ext_community;
+mod otc;
+mod pmsi_tunnel;
+mod prefix_sid;
+mod tunnel_encap;
+
+/// attribute flags of optional non-transitive attributes
+pub(crate) const FLAG_OPTIONAL: u8 = 0x80;
+/// attribute flags of optional transitive attributes
+pub(crate) const FLAG_OPTIONAL_TRANSITIVE: u8 = 0xC0;
+
+pub trait AttrValueDump {
+    /// The attribute type code.
+    fn type_code(&self) -> u8;
+
+    /// The attribute flags, without the extended length bit.
+    fn flag(&self) -> u8;
+
+    /// Encode the attribute value.
+    fn value_bytes(&self) -> Result<Vec<u8>, DumpError>;
+
+    fn to_raw_attribute(&self) -> Result<RawAttribute, DumpError> {
+        Ok(RawAttribute::new(self.type_code(), self.flag(), self.value_bytes()?))
+    }
+
+    /// Check that the value can be encoded and turn it into a [PathAttribute] that can be added to
+    /// messages and RIB entries.
+    fn to_attribute(&self) -> Result<PathAttribute, DumpError> where Self: Clone + Into<PathAttribute> {
+        self.value_bytes()?;
+        Ok(self.clone().into())
+    }
+}
+
+/// Write a TLV with a 1-byte type and a 2-byte length of the value.
+pub(crate) fn write_tlv(buffer: &mut Vec<u8>, tlv_type: u8, value: &[u8], record: &'static str) -> Result<(), DumpError> {
+    buffer.push(tlv_type);
+    buffer.write_16b(length_u16(value.len(), record, "TLV length")?)?;
+    buffer.extend_from_slice(value);
+    Ok(())
+}
+
+/// Write `value` into a BGP4MP update and read it back. The parser does not keep these attributes,
+/// so it must come back as the [RawAttribute] it is encoded into, and the record must be written
+/// again unchanged.
+#[cfg(test)]
+pub(crate) fn assert_round_trip<T: AttrValueDump + Clone + Into<PathAttribute>>(value: &T) {
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::str::FromStr;
+
+    use bgp_models::prelude::*;
+
+    use crate::{ExtraAttributes, MrtDump, RawMrtRecord};
+
+    let record = MrtRecord {
+        common_header: CommonHeader {
+            timestamp: 1,
+            microsecond_timestamp: None,
+            entry_type: EntryType::BGP4MP,
+            entry_subtype: Bgp4MpType::Bgp4MpMessageAs4 as u16,
+            length: 0,
+        },
+        message: MrtMessage::Bgp4Mp(Bgp4Mp::Bgp4MpMessageAs4(Bgp4MpMessage {
+            msg_type: Bgp4MpType::Bgp4MpMessageAs4,
+            peer_asn: Asn::from(400000),
+            local_asn: Asn::from(65000),
+            interface_index: 0,
+            afi: Afi::Ipv4,
+            peer_ip: IpAddr::from(Ipv4Addr::from([10, 0, 0, 1])),
+            local_ip: IpAddr::from(Ipv4Addr::from([10, 0, 0, 2])),
+            bgp_message: BgpMessage::Update(BgpUpdateMessage {
+                withdrawn_prefixes: vec![],
+                attributes: vec![
+                    Attribute { attr_type: AttrType::ORIGIN, value: AttributeValue::Origin(Origin::IGP), flag: 0x40 },
+                ],
+                announced_prefixes: vec![NetworkPrefix::from_str("10.1.0.0/16").unwrap()],
+            }),
+        })),
+    };
+    let mut extra = ExtraAttributes::new();
+    extra.insert(0, 1, value.to_attribute().unwrap());
+    let bytes = RawMrtRecord::new(record, extra).to_bytes(0).unwrap();
+
+    let mut parsed = RawMrtRecord::from_bytes(bytes.clone()).unwrap();
+    assert_eq!(parsed.extra_attributes().list(0), [(1, PathAttribute::Raw(value.to_raw_attribute().unwrap()))]);
+
+    parsed.record_mut();
+    assert_eq!(parsed.to_bytes(0).unwrap(), bytes);
+}
@@ -0,0 +1,49 @@
+use bgp_models::network::Asn;
+
+use crate::DumpError;
+use crate::mrt_dump::attr_values::{AttrValueDump, FLAG_OPTIONAL_TRANSITIVE};
+
+/// attribute type code of ONLY_TO_CUSTOMER
+const ATTR_TYPE_OTC: u8 = 35;
+
+/// Only-To-Customer attribute with the ASN that marked the route.
+/// https://datatracker.ietf.org/doc/html/rfc9234#section-5
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OnlyToCustomer(pub Asn);
+
+impl AttrValueDump for OnlyToCustomer {
+    fn type_code(&self) -> u8 {
+        ATTR_TYPE_OTC
+    }
+
+    fn flag(&self) -> u8 {
+        FLAG_OPTIONAL_TRANSITIVE
+    }
+
+    fn value_bytes(&self) -> Result<Vec<u8>, DumpError> {
+        // always a 4-byte ASN, independent of the session
+        Ok(self.0.asn.to_be_bytes().to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bgp_models::network::AsnLength;
+
+    use crate::mrt_dump::attr_values::assert_round_trip;
+    use crate::mrt_dump::attributes::MrtAttrDump;
+
+    use super::*;
+
+    #[test]
+    fn test_otc() {
+        let attr = OnlyToCustomer(Asn::from(400000)).to_attribute().unwrap();
+        let bytes = attr.to_bytes(false, AsnLength::Bits16, true, true, true).unwrap();
+        assert_eq!(bytes, [0xC0, 35, 4, 0, 6, 0x1A, 0x80]);
+    }
+
+    #[test]
+    fn test_otc_round_trip() {
+        assert_round_trip(&OnlyToCustomer(Asn::from(400000)));
+    }
+}
@@ -0,0 +1,142 @@
+use std::net::IpAddr;
+
+use crate::DumpError;
+use crate::mrt_dump::attr_values::{AttrValueDump, FLAG_OPTIONAL_TRANSITIVE};
+use crate::mrt_dump::utils::WriteUtils;
+
+/// attribute type code of PMSI_TUNNEL
+const ATTR_TYPE_PMSI_TUNNEL: u8 = 22;
+
+/// PMSI tunnel types, https://datatracker.ietf.org/doc/html/rfc6514#section-5
+pub const PMSI_TUNNEL_TYPE_NO_TUNNEL_INFO: u8 = 0;
+pub const PMSI_TUNNEL_TYPE_RSVP_TE_P2MP: u8 = 1;
+pub const PMSI_TUNNEL_TYPE_MLDP_P2MP: u8 = 2;
+pub const PMSI_TUNNEL_TYPE_PIM_SSM: u8 = 3;
+pub const PMSI_TUNNEL_TYPE_PIM_SM: u8 = 4;
+pub const PMSI_TUNNEL_TYPE_BIDIR_PIM: u8 = 5;
+pub const PMSI_TUNNEL_TYPE_INGRESS_REPLICATION: u8 = 6;
+pub const PMSI_TUNNEL_TYPE_MLDP_MP2MP: u8 = 7;
+
+/// flag requesting leaf information
+pub const PMSI_FLAG_LEAF_INFORMATION_REQUIRED: u8 = 0x01;
+
+const MAX_MPLS_LABEL: u32 = 0xF_FFFF;
+const MAX_VNI: u32 = 0xFF_FFFF;
+
+/// PMSI Tunnel attribute.
+/// https://datatracker.ietf.org/doc/html/rfc6514#section-5
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PmsiTunnel {
+    pub flags: u8,
+    pub tunnel_type: u8,
+    pub label: PmsiLabel,
+    pub tunnel_id: PmsiTunnelId,
+}
+
+/// Content of the 3-byte MPLS label field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PmsiLabel {
+    /// A 20-bit MPLS label, in the high-order bits of the field.
+    Mpls(u32),
+    /// A 24-bit VXLAN or NVGRE virtual network identifier filling the field, as used by EVPN.
+    /// https://datatracker.ietf.org/doc/html/rfc8365#section-5.1.3
+    Vni(u32),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PmsiTunnelId {
+    /// No tunnel identifier, with [PMSI_TUNNEL_TYPE_NO_TUNNEL_INFO].
+    None,
+    /// Sender and group address of PIM trees.
+    PimTree { sender: IpAddr, group: IpAddr },
+    /// Unicast tunnel endpoint of ingress replication.
+    IngressReplication(IpAddr),
+    /// Any other tunnel identifier, written as is.
+    Raw(Vec<u8>),
+}
+
+impl AttrValueDump for PmsiTunnel {
+    fn type_code(&self) -> u8 {
+        ATTR_TYPE_PMSI_TUNNEL
+    }
+
+    fn flag(&self) -> u8 {
+        FLAG_OPTIONAL_TRANSITIVE
+    }
+
+    fn value_bytes(&self) -> Result<Vec<u8>, DumpError> {
+        let (field, value, max) = match self.label {
+            PmsiLabel::Mpls(label) => ("MPLS label", label, MAX_MPLS_LABEL),
+            PmsiLabel::Vni(vni) => ("VNI", vni, MAX_VNI),
+        };
+        if value > max {
+            return Err(DumpError::ValueOutOfRange {
+                record: "PMSI_TUNNEL",
+                field,
+                value: value as u64,
+                max: max as u64,
+            })
+        }
+        let label_field = match self.label {
+            PmsiLabel::Mpls(label) => label << 4,
+            PmsiLabel::Vni(vni) => vni,
+        };
+
+        let mut buffer: Vec<u8> = vec![self.flags, self.tunnel_type];
+        buffer.extend_from_slice(&label_field.to_be_bytes()[1..]);
+        match &self.tunnel_id {
+            PmsiTunnelId::None => {}
+            PmsiTunnelId::PimTree { sender, group } => {
+                buffer.write_ip(sender)?;
+                buffer.write_ip(group)?;
+            }
+            PmsiTunnelId::IngressReplication(endpoint) => {
+                buffer.write_ip(endpoint)?;
+            }
+            PmsiTunnelId::Raw(v) => {
+                buffer.extend_from_slice(v);
+            }
+        }
+        Ok(buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use crate::mrt_dump::attr_values::assert_round_trip;
+
+    use super::*;
+
+    #[test]
+    fn test_pmsi_tunnel() {
+        let endpoint = IpAddr::from(Ipv4Addr::from([192, 0, 2, 1]));
+        let pmsi = PmsiTunnel {
+            flags: 0,
+            tunnel_type: PMSI_TUNNEL_TYPE_INGRESS_REPLICATION,
+            label: PmsiLabel::Vni(10100),
+            tunnel_id: PmsiTunnelId::IngressReplication(endpoint),
+        };
+        let raw = pmsi.to_raw_attribute().unwrap();
+        assert_eq!((raw.attr_type, raw.flag), (22, 0xC0));
+        assert_eq!(raw.value, [0, 6, 0, 0x27, 0x74, 192, 0, 2, 1]);
+        assert_round_trip(&pmsi);
+
+        let pmsi = PmsiTunnel {
+            flags: PMSI_FLAG_LEAF_INFORMATION_REQUIRED,
+            tunnel_type: PMSI_TUNNEL_TYPE_PIM_SSM,
+            label: PmsiLabel::Mpls(16),
+            tunnel_id: PmsiTunnelId::PimTree { sender: endpoint, group: IpAddr::from(Ipv4Addr::from([232, 1, 1, 1])) },
+        };
+        assert_eq!(pmsi.value_bytes().unwrap(), [1, 3, 0, 1, 0, 192, 0, 2, 1, 232, 1, 1, 1]);
+
+        let invalid = PmsiTunnel {
+            flags: 0,
+            tunnel_type: PMSI_TUNNEL_TYPE_NO_TUNNEL_INFO,
+            label: PmsiLabel::Mpls(1 << 20),
+            tunnel_id: PmsiTunnelId::None,
+        };
+        assert!(matches!(invalid.value_bytes(), Err(DumpError::ValueOutOfRange { .. })));
+    }
+}
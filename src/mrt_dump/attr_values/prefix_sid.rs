@@ -0,0 +1,239 @@
+use std::net::Ipv6Addr;
+
+use crate::DumpError;
+use crate::mrt_dump::attr_values::{AttrValueDump, FLAG_OPTIONAL_TRANSITIVE, write_tlv};
+use crate::mrt_dump::utils::WriteUtils;
+
+/// attribute type code of BGP Prefix-SID
+const ATTR_TYPE_PREFIX_SID: u8 = 40;
+
+const TLV_TYPE_LABEL_INDEX: u8 = 1;
+const TLV_TYPE_ORIGINATOR_SRGB: u8 = 3;
+const TLV_TYPE_SRV6_L3_SERVICE: u8 = 5;
+const TLV_TYPE_SRV6_L2_SERVICE: u8 = 6;
+const SUB_TLV_TYPE_SRV6_SID_INFORMATION: u8 = 1;
+const SUB_SUB_TLV_TYPE_SRV6_SID_STRUCTURE: u8 = 1;
+
+/// maximum of the 3-byte SRGB base and range fields
+const MAX_SRGB_VALUE: u32 = 0xFF_FFFF;
+
+/// BGP Prefix-SID attribute.
+/// https://datatracker.ietf.org/doc/html/rfc8669#section-3
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrefixSid {
+    pub tlvs: Vec<PrefixSidTlv>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PrefixSidTlv {
+    /// https://datatracker.ietf.org/doc/html/rfc8669#section-3.1
+    LabelIndex { flags: u16, label_index: u32 },
+    /// https://datatracker.ietf.org/doc/html/rfc8669#section-3.2
+    OriginatorSrgb { flags: u16, ranges: Vec<SrgbRange> },
+    /// https://datatracker.ietf.org/doc/html/rfc9252#section-2
+    Srv6L3Service(Vec<Srv6ServiceSubTlv>),
+    Srv6L2Service(Vec<Srv6ServiceSubTlv>),
+    /// Any other TLV, written as is.
+    Unknown { tlv_type: u8, value: Vec<u8> },
+}
+
+/// A range of labels in the SR global block, both values are 3 bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SrgbRange {
+    pub base: u32,
+    pub range: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Srv6ServiceSubTlv {
+    /// https://datatracker.ietf.org/doc/html/rfc9252#section-3.1
+    SidInformation(Srv6SidInformation),
+    Unknown { sub_tlv_type: u8, value: Vec<u8> },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Srv6SidInformation {
+    pub sid: Ipv6Addr,
+    pub flags: u8,
+    pub endpoint_behavior: u16,
+    pub data: Vec<Srv6ServiceDataSubSubTlv>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Srv6ServiceDataSubSubTlv {
+    /// https://datatracker.ietf.org/doc/html/rfc9252#section-3.2.1
+    SidStructure(Srv6SidStructure),
+    Unknown { sub_sub_tlv_type: u8, value: Vec<u8> },
+}
+
+/// Lengths in bits of the parts of an SRv6 SID, and where the transposed part of the SID is
+/// carried in the label field of the NLRI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Srv6SidStructure {
+    pub locator_block_length: u8,
+    pub locator_node_length: u8,
+    pub function_length: u8,
+    pub argument_length: u8,
+    pub transposition_length: u8,
+    pub transposition_offset: u8,
+}
+
+fn write_srgb_value(buffer: &mut Vec<u8>, value: u32, field: &'static str) -> Result<(), DumpError> {
+    if value > MAX_SRGB_VALUE {
+        return Err(DumpError::ValueOutOfRange {
+            record: "BGP Prefix-SID",
+            field,
+            value: value as u64,
+            max: MAX_SRGB_VALUE as u64,
+        })
+    }
+    buffer.extend_from_slice(&value.to_be_bytes()[1..]);
+    Ok(())
+}
+
+fn srv6_service_bytes(sub_tlvs: &[Srv6ServiceSubTlv]) -> Result<Vec<u8>, DumpError> {
+    // reserved
+    let mut buffer: Vec<u8> = vec![0];
+    for sub_tlv in sub_tlvs {
+        match sub_tlv {
+            Srv6ServiceSubTlv::SidInformation(info) => {
+                let mut value: Vec<u8> = vec![0];
+                value.extend_from_slice(&info.sid.octets());
+                value.push(info.flags);
+                value.write_16b(info.endpoint_behavior)?;
+                value.push(0);
+                for sub_sub_tlv in &info.data {
+                    match sub_sub_tlv {
+                        Srv6ServiceDataSubSubTlv::SidStructure(s) => {
+                            write_tlv(&mut value, SUB_SUB_TLV_TYPE_SRV6_SID_STRUCTURE, &[
+                                s.locator_block_length,
+                                s.locator_node_length,
+                                s.function_length,
+                                s.argument_length,
+                                s.transposition_length,
+                                s.transposition_offset,
+                            ], "BGP Prefix-SID")?;
+                        }
+                        Srv6ServiceDataSubSubTlv::Unknown { sub_sub_tlv_type, value: v } => {
+                            write_tlv(&mut value, *sub_sub_tlv_type, v, "BGP Prefix-SID")?;
+                        }
+                    }
+                }
+                write_tlv(&mut buffer, SUB_TLV_TYPE_SRV6_SID_INFORMATION, &value, "BGP Prefix-SID")?;
+            }
+            Srv6ServiceSubTlv::Unknown { sub_tlv_type, value } => {
+                write_tlv(&mut buffer, *sub_tlv_type, value, "BGP Prefix-SID")?;
+            }
+        }
+    }
+    Ok(buffer)
+}
+
+impl AttrValueDump for PrefixSid {
+    fn type_code(&self) -> u8 {
+        ATTR_TYPE_PREFIX_SID
+    }
+
+    fn flag(&self) -> u8 {
+        FLAG_OPTIONAL_TRANSITIVE
+    }
+
+    fn value_bytes(&self) -> Result<Vec<u8>, DumpError> {
+        let mut buffer: Vec<u8> = vec![];
+        for tlv in &self.tlvs {
+            match tlv {
+                PrefixSidTlv::LabelIndex { flags, label_index } => {
+                    let mut value: Vec<u8> = vec![0];
+                    value.write_16b(*flags)?;
+                    value.write_32b(*label_index)?;
+                    write_tlv(&mut buffer, TLV_TYPE_LABEL_INDEX, &value, "BGP Prefix-SID")?;
+                }
+                PrefixSidTlv::OriginatorSrgb { flags, ranges } => {
+                    let mut value: Vec<u8> = vec![];
+                    value.write_16b(*flags)?;
+                    for range in ranges {
+                        write_srgb_value(&mut value, range.base, "SRGB base")?;
+                        write_srgb_value(&mut value, range.range, "SRGB range")?;
+                    }
+                    write_tlv(&mut buffer, TLV_TYPE_ORIGINATOR_SRGB, &value, "BGP Prefix-SID")?;
+                }
+                PrefixSidTlv::Srv6L3Service(sub_tlvs) => {
+                    write_tlv(&mut buffer, TLV_TYPE_SRV6_L3_SERVICE, &srv6_service_bytes(sub_tlvs)?, "BGP Prefix-SID")?;
+                }
+                PrefixSidTlv::Srv6L2Service(sub_tlvs) => {
+                    write_tlv(&mut buffer, TLV_TYPE_SRV6_L2_SERVICE, &srv6_service_bytes(sub_tlvs)?, "BGP Prefix-SID")?;
+                }
+                PrefixSidTlv::Unknown { tlv_type, value } => {
+                    write_tlv(&mut buffer, *tlv_type, value, "BGP Prefix-SID")?;
+                }
+            }
+        }
+        Ok(buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::mrt_dump::attr_values::assert_round_trip;
+
+    use super::*;
+
+    #[test]
+    fn test_prefix_sid_mpls() {
+        let prefix_sid = PrefixSid {
+            tlvs: vec![
+                PrefixSidTlv::LabelIndex { flags: 0, label_index: 100 },
+                PrefixSidTlv::OriginatorSrgb {
+                    flags: 0,
+                    ranges: vec![SrgbRange { base: 16000, range: 8000 }],
+                },
+            ],
+        };
+        let raw = prefix_sid.to_raw_attribute().unwrap();
+        assert_eq!((raw.attr_type, raw.flag), (40, 0xC0));
+        assert_eq!(raw.value, [
+            1, 0, 7, 0, 0, 0, 0, 0, 0, 100,
+            3, 0, 8, 0, 0, 0, 0x3E, 0x80, 0, 0x1F, 0x40,
+        ]);
+        assert_round_trip(&prefix_sid);
+
+        let invalid = PrefixSid {
+            tlvs: vec![PrefixSidTlv::OriginatorSrgb { flags: 0, ranges: vec![SrgbRange { base: 1 << 24, range: 1 }] }],
+        };
+        assert!(matches!(invalid.value_bytes(), Err(DumpError::ValueOutOfRange { .. })));
+    }
+
+    #[test]
+    fn test_prefix_sid_srv6() {
+        let sid = Ipv6Addr::from_str("2001:db8:1:1::").unwrap();
+        let prefix_sid = PrefixSid {
+            tlvs: vec![PrefixSidTlv::Srv6L3Service(vec![Srv6ServiceSubTlv::SidInformation(Srv6SidInformation {
+                sid,
+                flags: 0,
+                // End.DT4
+                endpoint_behavior: 0x13,
+                data: vec![Srv6ServiceDataSubSubTlv::SidStructure(Srv6SidStructure {
+                    locator_block_length: 40,
+                    locator_node_length: 24,
+                    function_length: 16,
+                    argument_length: 0,
+                    transposition_length: 16,
+                    transposition_offset: 64,
+                })],
+            })])],
+        };
+        let mut expected: Vec<u8> = vec![
+            // L3 service TLV, reserved
+            5, 0, 34, 0,
+            // SID information sub-TLV, reserved
+            1, 0, 30, 0,
+        ];
+        expected.extend(sid.octets());
+        expected.extend([0, 0, 0x13, 0]);
+        // SID structure sub-sub-TLV
+        expected.extend([1, 0, 6, 40, 24, 16, 0, 16, 64]);
+        assert_eq!(prefix_sid.value_bytes().unwrap(), expected);
+    }
+}
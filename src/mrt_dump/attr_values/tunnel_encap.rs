@@ -0,0 +1,168 @@
+use std::net::IpAddr;
+
+use crate::DumpError;
+use crate::mrt_dump::attr_values::{AttrValueDump, FLAG_OPTIONAL_TRANSITIVE};
+use crate::mrt_dump::utils::{length_u16, length_u8, WriteUtils};
+
+/// attribute type code of Tunnel Encapsulation
+const ATTR_TYPE_TUNNEL_ENCAPSULATION: u8 = 23;
+
+/// tunnel types, https://www.iana.org/assignments/bgp-parameters/bgp-parameters.xhtml#tunnel-types
+pub const TUNNEL_TYPE_VXLAN: u16 = 8;
+pub const TUNNEL_TYPE_NVGRE: u16 = 9;
+pub const TUNNEL_TYPE_MPLS_IN_GRE: u16 = 11;
+pub const TUNNEL_TYPE_MPLS_IN_UDP: u16 = 13;
+pub const TUNNEL_TYPE_SR_POLICY: u16 = 15;
+pub const TUNNEL_TYPE_GENEVE: u16 = 19;
+
+const SUB_TLV_TYPE_ENCAPSULATION: u8 = 1;
+const SUB_TLV_TYPE_PROTOCOL_TYPE: u8 = 2;
+const SUB_TLV_TYPE_COLOR: u8 = 4;
+const SUB_TLV_TYPE_TUNNEL_EGRESS_ENDPOINT: u8 = 6;
+const SUB_TLV_TYPE_UDP_DESTINATION_PORT: u8 = 8;
+/// sub-TLV types from this value on have a 2-byte length field
+const SUB_TLV_TYPE_EXTENDED_LENGTH: u8 = 128;
+
+/// Tunnel Encapsulation attribute.
+/// https://datatracker.ietf.org/doc/html/rfc9012#section-2
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TunnelEncapsulation {
+    pub tunnels: Vec<TunnelTlv>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TunnelTlv {
+    pub tunnel_type: u16,
+    pub sub_tlvs: Vec<TunnelSubTlv>,
+}
+
+/// https://datatracker.ietf.org/doc/html/rfc9012#section-3
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TunnelSubTlv {
+    /// The encapsulation specific value, whose format depends on the tunnel type.
+    Encapsulation(Vec<u8>),
+    /// Ethertype of the payload.
+    ProtocolType(u16),
+    /// Color value, written in the format of a color extended community.
+    Color(u32),
+    /// Tunnel egress endpoint address, none for an unspecified endpoint.
+    TunnelEgressEndpoint(Option<IpAddr>),
+    UdpDestinationPort(u16),
+    /// Any other sub-TLV, written as is.
+    Unknown { sub_tlv_type: u8, value: Vec<u8> },
+}
+
+impl TunnelSubTlv {
+    fn type_and_value(&self) -> Result<(u8, Vec<u8>), DumpError> {
+        let mut value: Vec<u8> = vec![];
+        let sub_tlv_type = match self {
+            TunnelSubTlv::Encapsulation(v) => {
+                value.extend_from_slice(v);
+                SUB_TLV_TYPE_ENCAPSULATION
+            }
+            TunnelSubTlv::ProtocolType(ethertype) => {
+                value.write_16b(*ethertype)?;
+                SUB_TLV_TYPE_PROTOCOL_TYPE
+            }
+            TunnelSubTlv::Color(color) => {
+                // transitive opaque extended community of subtype color
+                value.extend([0x03, 0x0b, 0, 0]);
+                value.write_32b(*color)?;
+                SUB_TLV_TYPE_COLOR
+            }
+            TunnelSubTlv::TunnelEgressEndpoint(address) => {
+                // reserved, then the AFI with the address; AFI 0 has no address
+                value.write_32b(0)?;
+                match address {
+                    None => value.write_16b(0)?,
+                    Some(ip) => {
+                        value.write_16b(if ip.is_ipv4() { 1 } else { 2 })?;
+                        value.write_ip(ip)?;
+                    }
+                }
+                SUB_TLV_TYPE_TUNNEL_EGRESS_ENDPOINT
+            }
+            TunnelSubTlv::UdpDestinationPort(port) => {
+                value.write_16b(*port)?;
+                SUB_TLV_TYPE_UDP_DESTINATION_PORT
+            }
+            TunnelSubTlv::Unknown { sub_tlv_type, value: v } => {
+                value.extend_from_slice(v);
+                *sub_tlv_type
+            }
+        };
+        Ok((sub_tlv_type, value))
+    }
+}
+
+impl AttrValueDump for TunnelEncapsulation {
+    fn type_code(&self) -> u8 {
+        ATTR_TYPE_TUNNEL_ENCAPSULATION
+    }
+
+    fn flag(&self) -> u8 {
+        FLAG_OPTIONAL_TRANSITIVE
+    }
+
+    fn value_bytes(&self) -> Result<Vec<u8>, DumpError> {
+        let mut buffer: Vec<u8> = vec![];
+        for tunnel in &self.tunnels {
+            let mut tunnel_buf: Vec<u8> = vec![];
+            for sub_tlv in &tunnel.sub_tlvs {
+                let (sub_tlv_type, value) = sub_tlv.type_and_value()?;
+                tunnel_buf.push(sub_tlv_type);
+                match sub_tlv_type < SUB_TLV_TYPE_EXTENDED_LENGTH {
+                    true => tunnel_buf.push(length_u8(value.len(), "Tunnel Encapsulation", "sub-TLV length")?),
+                    false => tunnel_buf.write_16b(length_u16(value.len(), "Tunnel Encapsulation", "sub-TLV length")?)?,
+                }
+                tunnel_buf.extend(value);
+            }
+            buffer.write_16b(tunnel.tunnel_type)?;
+            buffer.write_16b(length_u16(tunnel_buf.len(), "Tunnel Encapsulation", "tunnel TLV length")?)?;
+            buffer.extend(tunnel_buf);
+        }
+        Ok(buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use crate::mrt_dump::attr_values::assert_round_trip;
+
+    use super::*;
+
+    #[test]
+    fn test_tunnel_encapsulation() {
+        let encap = TunnelEncapsulation {
+            tunnels: vec![TunnelTlv {
+                tunnel_type: TUNNEL_TYPE_VXLAN,
+                sub_tlvs: vec![
+                    TunnelSubTlv::TunnelEgressEndpoint(Some(IpAddr::from(Ipv4Addr::from([192, 0, 2, 1])))),
+                    TunnelSubTlv::Color(100),
+                    TunnelSubTlv::UdpDestinationPort(4789),
+                    TunnelSubTlv::Unknown { sub_tlv_type: 200, value: vec![1] },
+                ],
+            }],
+        };
+        let raw = encap.to_raw_attribute().unwrap();
+        assert_eq!((raw.attr_type, raw.flag), (23, 0xC0));
+        assert_eq!(raw.value, [
+            0, 8, 0, 30,
+            6, 10, 0, 0, 0, 0, 0, 1, 192, 0, 2, 1,
+            4, 8, 0x03, 0x0b, 0, 0, 0, 0, 0, 100,
+            8, 2, 0x12, 0xB5,
+            200, 0, 1, 1,
+        ]);
+        assert_round_trip(&encap);
+
+        let too_long = TunnelEncapsulation {
+            tunnels: vec![TunnelTlv {
+                tunnel_type: TUNNEL_TYPE_MPLS_IN_GRE,
+                sub_tlvs: vec![TunnelSubTlv::Encapsulation(vec![0; 256])],
+            }],
+        };
+        assert!(matches!(too_long.value_bytes(), Err(DumpError::LengthOverflow { .. })));
+    }
+}
@@ -1,29 +1,131 @@
 use std::io::Write;
 use std::net::IpAddr;
 
-use bgp_models::bgp::{AsPathSegment, Attribute, AttributeFlagsBit, Community, ExtendedCommunity};
-use bgp_models::network::{AsnLength, NextHopAddress};
+use bgp_models::bgp::{AsPath, AsPathSegment, Attribute, AttributeFlagsBit, AttrType, Community, ExtendedCommunity};
+use bgp_models::network::{Asn, AsnLength, NextHopAddress};
 use bgp_models::prelude::AttributeValue;
 use byteorder::WriteBytesExt;
-use num_traits::ToPrimitive;
 
 use crate::DumpError;
-use crate::mrt_dump::utils::WriteUtils;
+use crate::mrt_dump::PathAttribute;
+use crate::mrt_dump::utils::{length_u16, type_code_u16, type_code_u8, WriteUtils};
+
+/// maximum number of ASNs in one AS_PATH segment
+const MAX_AS_PATH_SEGMENT_LENGTH: usize = u8::MAX as usize;
+
+/// AS_TRANS, the 2-octet ASN used in place of 4-octet ASNs.
+/// https://datatracker.ietf.org/doc/html/rfc6793#section-9
+pub(crate) const AS_TRANS: u32 = 23456;
+
+/// Convert attributes into the form a speaker without 4-octet ASN support sends them.
+///
+/// AS_PATH and AGGREGATOR are written with 2-octet ASNs, 4-octet ASNs are replaced by AS_TRANS and
+/// the actual ASNs are carried in AS4_PATH and AS4_AGGREGATOR attributes, unless these attributes
+/// are already present.
+/// https://datatracker.ietf.org/doc/html/rfc6793#section-4.2.2
+pub(crate) fn to_two_octet_asn_attributes(attributes: &[PathAttribute]) -> Vec<PathAttribute> {
+    let has_as4_path = attributes.iter().any(|a| a.type_code() == AttrType::AS4_PATH as u8);
+    let has_as4_aggregator = attributes.iter().any(|a| a.type_code() == AttrType::AS4_AGGREGATOR as u8);
+
+    let two_octet_asn = |asn: &Asn| Asn {
+        asn: match asn.asn > u16::MAX as u32 {
+            true => AS_TRANS,
+            false => asn.asn,
+        },
+        len: AsnLength::Bits16,
+    };
+    let four_octet_asn = |asn: &Asn| Asn { asn: asn.asn, len: AsnLength::Bits32 };
+
+    let mut converted = vec![];
+    let mut as4_attributes = vec![];
+    for path_attr in attributes {
+        let attr = match path_attr {
+            PathAttribute::Attribute(attr) => attr,
+            _ => {
+                converted.push(path_attr.clone());
+                continue
+            }
+        };
+        match (&attr.attr_type, &attr.value) {
+            (AttrType::AS_PATH, AttributeValue::AsPath(path)) | (AttrType::AS_PATH, AttributeValue::As4Path(path)) => {
+                let needs_as4_path = path.segments.iter().any(|seg| match seg {
+                    AsPathSegment::AsSequence(v) | AsPathSegment::AsSet(v) |
+                    AsPathSegment::ConfedSequence(v) | AsPathSegment::ConfedSet(v) => {
+                        v.iter().any(|asn| asn.asn > u16::MAX as u32)
+                    }
+                });
+                let segments = path.segments.iter().map(|seg| match seg {
+                    AsPathSegment::AsSequence(v) => AsPathSegment::AsSequence(v.iter().map(two_octet_asn).collect()),
+                    AsPathSegment::AsSet(v) => AsPathSegment::AsSet(v.iter().map(two_octet_asn).collect()),
+                    AsPathSegment::ConfedSequence(v) => AsPathSegment::ConfedSequence(v.iter().map(two_octet_asn).collect()),
+                    AsPathSegment::ConfedSet(v) => AsPathSegment::ConfedSet(v.iter().map(two_octet_asn).collect()),
+                }).collect();
+                converted.push(PathAttribute::Attribute(Attribute {
+                    attr_type: AttrType::AS_PATH,
+                    value: AttributeValue::AsPath(AsPath::from_segments(segments)),
+                    flag: attr.flag,
+                }));
+
+                if needs_as4_path && !has_as4_path {
+                    // confederation segments must not be sent in AS4_PATH
+                    let segments = path.segments.iter().filter_map(|seg| match seg {
+                        AsPathSegment::AsSequence(v) => Some(AsPathSegment::AsSequence(v.iter().map(four_octet_asn).collect())),
+                        AsPathSegment::AsSet(v) => Some(AsPathSegment::AsSet(v.iter().map(four_octet_asn).collect())),
+                        AsPathSegment::ConfedSequence(_) | AsPathSegment::ConfedSet(_) => None,
+                    }).collect();
+                    as4_attributes.push(Attribute {
+                        attr_type: AttrType::AS4_PATH,
+                        value: AttributeValue::As4Path(AsPath::from_segments(segments)),
+                        flag: AttributeFlagsBit::OptionalBit as u8 | AttributeFlagsBit::TransitiveBit as u8,
+                    });
+                }
+            }
+            (AttrType::AGGREGATOR, AttributeValue::Aggregator(asn, ip)) => {
+                converted.push(PathAttribute::Attribute(Attribute {
+                    attr_type: AttrType::AGGREGATOR,
+                    value: AttributeValue::Aggregator(two_octet_asn(asn), *ip),
+                    flag: attr.flag,
+                }));
+                if asn.asn > u16::MAX as u32 && !has_as4_aggregator {
+                    as4_attributes.push(Attribute {
+                        attr_type: AttrType::AS4_AGGREGATOR,
+                        value: AttributeValue::Aggregator(four_octet_asn(asn), *ip),
+                        flag: AttributeFlagsBit::OptionalBit as u8 | AttributeFlagsBit::TransitiveBit as u8,
+                    });
+                }
+            }
+            _ => converted.push(path_attr.clone()),
+        }
+    }
+
+    // keep attributes ordered by type code, the way BGP speakers usually send them
+    for as4_attr in as4_attributes {
+        let type_code = as4_attr.attr_type as u8;
+        let pos = converted.iter()
+            .position(|a| a.type_code() > type_code)
+            .unwrap_or(converted.len());
+        converted.insert(pos, PathAttribute::Attribute(as4_attr));
+    }
+    converted
+}
 
 pub trait MrtAttrDump {
-    fn to_bytes(&self, add_path: bool, write_afi: bool, write_safi: bool, write_prefixes: bool)-> Result<Vec<u8>, DumpError>;
+    /// Encode the attribute. `asn_len` is the ASN width of the encoding context, used for AS_PATH
+    /// and AGGREGATOR; AS4_PATH and AS4_AGGREGATOR are always encoded with 4-byte ASNs.
+    fn to_bytes(&self, add_path: bool, asn_len: AsnLength, write_afi: bool, write_safi: bool, write_prefixes: bool)-> Result<Vec<u8>, DumpError>;
 }
 
 impl MrtAttrDump for Attribute {
-    fn to_bytes(&self, add_path: bool, write_afi: bool, write_safi: bool, write_prefixes: bool) -> Result<Vec<u8>, DumpError> {
-        let mut buffer: Vec<u8> = vec![];
-        buffer.write_u8(self.flag)?;
-        buffer.write_u8(self.attr_type.to_u8().unwrap())?;
+    fn to_bytes(&self, add_path: bool, asn_len: AsnLength, write_afi: bool, write_safi: bool, write_prefixes: bool) -> Result<Vec<u8>, DumpError> {
+        let asn_len = match self.attr_type {
+            AttrType::AS4_PATH | AttrType::AS4_AGGREGATOR => AsnLength::Bits32,
+            _ => asn_len,
+        };
 
         let mut attr_buf: Vec<u8> = vec![];
         match &self.value {
             AttributeValue::Origin(v) => {
-                attr_buf.write_u8(v.to_u8().unwrap())?;
+                attr_buf.write_u8(type_code_u8(v, "ORIGIN", "origin")?)?;
             }
             AttributeValue::AsPath(v) | AttributeValue::As4Path(v) => {
                 for seg in &v.segments {
@@ -42,16 +144,19 @@ impl MrtAttrDump for Attribute {
                             (4, v)
                         }
                     };
-                    attr_buf.write_u8(seg_type as u8)?;
-                    attr_buf.write_u8(asns.len() as u8)?;
-                    for asn in asns {
-                        match asn.len {
-                            AsnLength::Bits16 => {
-                                attr_buf.write_16b(asn.asn as u16)?
-                            }
-                            AsnLength::Bits32 => {
-                                attr_buf.write_32b(asn.asn as u32)?
-                            }
+                    if asns.is_empty() {
+                        attr_buf.write_u8(seg_type as u8)?;
+                        attr_buf.write_u8(0)?;
+                        continue
+                    }
+                    // the segment length is one octet, longer segments are split into multiple
+                    // consecutive segments of the same type.
+                    // https://datatracker.ietf.org/doc/html/rfc4271#section-9.2.2.1
+                    for chunk in asns.chunks(MAX_AS_PATH_SEGMENT_LENGTH) {
+                        attr_buf.write_u8(seg_type as u8)?;
+                        attr_buf.write_u8(chunk.len() as u8)?;
+                        for asn in chunk {
+                            attr_buf.write_asn(asn, asn_len)?;
                         }
                     }
                 }
@@ -69,7 +174,7 @@ impl MrtAttrDump for Attribute {
                 // do nothing here. the value type is enough and it's already written above.
             }
             AttributeValue::Aggregator(asn, ip) => {
-                attr_buf.write_asn(asn)?;
+                attr_buf.write_asn(asn, asn_len)?;
                 attr_buf.write_ip(ip)?;
             }
             AttributeValue::Communities(v) => {
@@ -89,6 +194,14 @@ impl MrtAttrDump for Attribute {
                             attr_buf.write_32b(COMMUNITY_NO_EXPORT_SUBCONFED)?;
                         }
                         Community::Custom(asn, value) => {
+                            if asn.asn > u16::MAX as u32 {
+                                return Err(DumpError::ValueOutOfRange {
+                                    record: "COMMUNITIES",
+                                    field: "ASN",
+                                    value: asn.asn as u64,
+                                    max: u16::MAX as u64,
+                                })
+                            }
                             attr_buf.write_16b(asn.asn as u16)?;
                             attr_buf.write_16b(*value)?;
                         }
@@ -97,10 +210,31 @@ impl MrtAttrDump for Attribute {
                 }
             }
             AttributeValue::ExtendedCommunities(v) => {
+                // 20-byte IPv6 address specific communities have their own attribute
+                // https://datatracker.ietf.org/doc/html/rfc5701
+                let ipv6_attribute = self.attr_type == AttrType::IPV6_ADDRESS_SPECIFIC_EXTENDED_COMMUNITIES;
                 for comm in v {
+                    if matches!(comm, ExtendedCommunity::Ipv6AddressSpecific(_)) != ipv6_attribute {
+                        return Err(DumpError::UnsupportedType {
+                            record: match ipv6_attribute {
+                                true => "IPV6_ADDRESS_SPECIFIC_EXTENDED_COMMUNITIES",
+                                false => "EXTENDED_COMMUNITIES",
+                            },
+                            field: "community",
+                            value: format!("{:?}", comm),
+                        })
+                    }
                     match comm {
                         ExtendedCommunity::TransitiveTwoOctetAsSpecific(c) |
                             ExtendedCommunity::NonTransitiveTwoOctetAsSpecific(c) => {
+                            if c.global_administrator.asn > u16::MAX as u32 {
+                                return Err(DumpError::ValueOutOfRange {
+                                    record: "EXTENDED_COMMUNITIES",
+                                    field: "two-octet AS specific global administrator",
+                                    value: c.global_administrator.asn as u64,
+                                    max: u16::MAX as u64,
+                                })
+                            }
                             attr_buf.write_u8(c.ec_type)?;
                             attr_buf.write_u8(c.ec_subtype)?;
                             attr_buf.write_16b(c.global_administrator.asn as u16)?;
@@ -110,7 +244,7 @@ impl MrtAttrDump for Attribute {
                         ExtendedCommunity::NonTransitiveFourOctetAsSpecific(c) => {
                             attr_buf.write_u8(c.ec_type)?;
                             attr_buf.write_u8(c.ec_subtype)?;
-                            attr_buf.write_32b(c.global_administrator.asn as u32)?;
+                            attr_buf.write_32b(c.global_administrator.asn)?;
                             attr_buf.write_all(&c.local_administrator)?;
 
                         }
@@ -142,7 +276,6 @@ impl MrtAttrDump for Attribute {
             AttributeValue::LargeCommunities(v) => {
                 for comm in v {
                     attr_buf.write_32b(comm.global_administrator)?;
-                    assert_eq!(comm.local_data.len(), 2);
                     attr_buf.write_32b(comm.local_data[0])?;
                     attr_buf.write_32b(comm.local_data[1])?;
                 }
@@ -172,10 +305,10 @@ impl MrtAttrDump for Attribute {
                     +---------------------------------------------------------+
                  */
                 if write_afi {
-                    attr_buf.write_16b(v.afi.to_u16().unwrap())?;
+                    attr_buf.write_16b(type_code_u16(&v.afi, "MP_REACH_NLRI", "AFI")?)?;
                 }
                 if write_safi {
-                    attr_buf.write_u8(v.safi.to_u8().unwrap())?;
+                    attr_buf.write_u8(type_code_u8(&v.safi, "MP_REACH_NLRI", "SAFI")?)?;
                 }
                 match v.next_hop {
                     None => {
@@ -220,10 +353,10 @@ impl MrtAttrDump for Attribute {
                     +---------------------------------------------------------+
                  */
                 if write_afi {
-                    attr_buf.write_16b(v.afi.to_u16().unwrap())?;
+                    attr_buf.write_16b(type_code_u16(&v.afi, "MP_UNREACH_NLRI", "AFI")?)?;
                 }
                 if write_safi {
-                    attr_buf.write_u8(v.safi.to_u8().unwrap())?;
+                    attr_buf.write_u8(type_code_u8(&v.safi, "MP_UNREACH_NLRI", "SAFI")?)?;
                 }
                 if write_prefixes {
                     for prefix in &v.prefixes {
@@ -232,18 +365,173 @@ impl MrtAttrDump for Attribute {
                 }
             }
             AttributeValue::Development(v) => {
-                attr_buf.write_all(&v)?;
+                attr_buf.write_all(v)?;
             }
         }
 
-        // write attribute length
-        match self.flag & AttributeFlagsBit::ExtendedLengthBit as u8 {
-            0 => buffer.write_u8(attr_buf.len() as u8)?,
-            _ => buffer.write_16b(attr_buf.len() as u16)?,
+        attribute_bytes(self.flag, type_code_u8(&self.attr_type, "path attribute", "type code")?, &attr_buf)
+    }
+}
+
+/// Encode a path attribute with its header.
+pub(crate) fn attribute_bytes(flag: u8, type_code: u8, value: &[u8]) -> Result<Vec<u8>, DumpError> {
+    // keep the original flags (including an extended length bit on short attributes) and
+    // only set the extended length bit if the value does not fit in one octet
+    let flag = match value.len() > u8::MAX as usize {
+        true => flag | AttributeFlagsBit::ExtendedLengthBit as u8,
+        false => flag,
+    };
+
+    let mut buffer: Vec<u8> = vec![];
+    buffer.write_u8(flag)?;
+    buffer.write_u8(type_code)?;
+
+    // write attribute length
+    match flag & AttributeFlagsBit::ExtendedLengthBit as u8 {
+        0 => buffer.write_u8(value.len() as u8)?,
+        _ => buffer.write_16b(length_u16(value.len(), "path attribute", "attribute length")?)?,
+    };
+    // write attribute value
+    buffer.write_all(value)?;
+
+    Ok(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn as_path_attribute(segments: Vec<AsPathSegment>) -> Attribute {
+        Attribute {
+            attr_type: AttrType::AS_PATH,
+            value: AttributeValue::AsPath(AsPath::from_segments(segments)),
+            flag: AttributeFlagsBit::TransitiveBit as u8,
+        }
+    }
+
+    /// returns (segment type, number of ASNs) of each segment in an encoded AS_PATH value
+    fn segment_headers(value: &[u8]) -> Vec<(u8, u8)> {
+        let mut headers = vec![];
+        let mut pos = 0;
+        while pos < value.len() {
+            headers.push((value[pos], value[pos + 1]));
+            pos += 2 + value[pos + 1] as usize * 4;
+        }
+        assert_eq!(pos, value.len());
+        headers
+    }
+
+    #[test]
+    fn test_long_as_path() {
+        // prepend-heavy path with 600 hops
+        let mut asns: Vec<Asn> = vec![Asn::from(65000); 599];
+        asns.push(Asn::from(400000));
+        let attr = as_path_attribute(vec![
+            AsPathSegment::AsSequence(asns.clone()),
+            AsPathSegment::AsSet(vec![Asn::from(1); 256]),
+        ]);
+        let bytes = attr.to_bytes(false, AsnLength::Bits32, true, true, true).unwrap();
+
+        // value is longer than 255 bytes, extended length is used
+        assert_eq!(bytes[0], 0x50);
+        let len = u16::from_be_bytes([bytes[2], bytes[3]]) as usize;
+        assert_eq!(len, bytes.len() - 4);
+        assert_eq!(
+            segment_headers(&bytes[4..]),
+            vec![(2, 255), (2, 255), (2, 90), (1, 255), (1, 1)]
+        );
+        // last ASN of the sequence is at the end of the third segment
+        let end = 4 + (2 + 255 * 4) * 2 + 2 + 90 * 4;
+        assert_eq!(bytes[end - 4..end], 400000u32.to_be_bytes());
+    }
+
+    #[test]
+    fn test_two_octet_asn_attributes() {
+        let attributes = vec![
+            Attribute {
+                attr_type: AttrType::ORIGIN,
+                value: AttributeValue::Origin(bgp_models::bgp::Origin::IGP),
+                flag: 0x40,
+            },
+            as_path_attribute(vec![
+                AsPathSegment::ConfedSequence(vec![Asn::from(65100)]),
+                AsPathSegment::AsSequence(vec![Asn::from(100), Asn::from(400000)]),
+            ]),
+            Attribute {
+                attr_type: AttrType::AGGREGATOR,
+                value: AttributeValue::Aggregator(Asn::from(400001), IpAddr::from([10, 0, 0, 1])),
+                flag: 0xc0,
+            },
+            Attribute {
+                attr_type: AttrType::COMMUNITIES,
+                value: AttributeValue::Communities(vec![Community::NoExport]),
+                flag: 0xc0,
+            },
+        ];
+        let attributes: Vec<PathAttribute> = attributes.into_iter().map(PathAttribute::from).collect();
+        let converted = to_two_octet_asn_attributes(&attributes);
+        let types: Vec<u8> = converted.iter().map(|a| a.type_code()).collect();
+        assert_eq!(types, [
+            AttrType::ORIGIN, AttrType::AS_PATH, AttrType::AGGREGATOR, AttrType::COMMUNITIES,
+            AttrType::AS4_PATH, AttrType::AS4_AGGREGATOR,
+        ].map(|t| t as u8));
+        let converted: Vec<Attribute> = converted.iter().filter_map(PathAttribute::as_attribute).cloned().collect();
+
+        let as_trans = Asn { asn: AS_TRANS, len: AsnLength::Bits16 };
+        assert_eq!(converted[1].value, AttributeValue::AsPath(AsPath::from_segments(vec![
+            AsPathSegment::ConfedSequence(vec![Asn { asn: 65100, len: AsnLength::Bits16 }]),
+            AsPathSegment::AsSequence(vec![Asn { asn: 100, len: AsnLength::Bits16 }, as_trans]),
+        ])));
+        assert_eq!(converted[2].value, AttributeValue::Aggregator(as_trans, IpAddr::from([10, 0, 0, 1])));
+        // no confederation segments in AS4_PATH
+        assert_eq!(converted[4].value, AttributeValue::As4Path(AsPath::from_segments(vec![
+            AsPathSegment::AsSequence(vec![Asn::from(100), Asn::from(400000)]),
+        ])));
+        assert_eq!(converted[4].flag, 0xc0);
+        assert_eq!(converted[5].value, AttributeValue::Aggregator(Asn::from(400001), IpAddr::from([10, 0, 0, 1])));
+
+        // existing AS4 attributes are kept as they are, nothing is added for 2-octet ASNs only
+        let converted: Vec<PathAttribute> = converted.into_iter().map(PathAttribute::from).collect();
+        let converted = to_two_octet_asn_attributes(&converted);
+        assert_eq!(converted.len(), 6);
+        let converted = to_two_octet_asn_attributes(&[as_path_attribute(vec![
+            AsPathSegment::AsSequence(vec![Asn::from(100)]),
+        ]).into()]);
+        assert_eq!(converted.len(), 1);
+    }
+
+    #[test]
+    fn test_mixed_asn_lengths() {
+        let attr = as_path_attribute(vec![AsPathSegment::AsSequence(vec![
+            Asn { asn: 100, len: AsnLength::Bits16 },
+            Asn::from(400000),
+        ])]);
+        let bytes = attr.to_bytes(false, AsnLength::Bits32, true, true, true).unwrap();
+        assert_eq!(bytes[3..], [2, 2, 0, 0, 0, 100, 0, 6, 0x1a, 0x80]);
+
+        // 4-byte ASNs cannot be encoded in a 2-byte context
+        assert!(attr.to_bytes(false, AsnLength::Bits16, true, true, true).is_err());
+
+        // AS4_PATH always uses 4-byte ASNs
+        let attr = Attribute {
+            attr_type: AttrType::AS4_PATH,
+            value: AttributeValue::As4Path(AsPath::from_segments(vec![
+                AsPathSegment::AsSequence(vec![Asn { asn: 100, len: AsnLength::Bits16 }])
+            ])),
+            flag: 0xc0,
         };
-        // write attribute value
-        buffer.extend(attr_buf);
+        let bytes = attr.to_bytes(false, AsnLength::Bits16, true, true, true).unwrap();
+        assert_eq!(bytes[3..], [2, 1, 0, 0, 0, 100]);
+    }
+
+    #[test]
+    fn test_as_path_segment_boundary() {
+        let attr = as_path_attribute(vec![AsPathSegment::AsSequence(vec![Asn::from(65000); 255])]);
+        let bytes = attr.to_bytes(false, AsnLength::Bits32, true, true, true).unwrap();
+        assert_eq!(segment_headers(&bytes[4..]), vec![(2, 255)]);
 
-        Ok(buffer)
+        let attr = as_path_attribute(vec![AsPathSegment::AsSequence(vec![Asn::from(65000); 256])]);
+        let bytes = attr.to_bytes(false, AsnLength::Bits32, true, true, true).unwrap();
+        assert_eq!(segment_headers(&bytes[4..]), vec![(2, 255), (2, 1)]);
     }
 }
@@ -1,16 +1,24 @@
 use std::io::Write;
 
-use bgp_models::bgp::{BgpKeepAliveMessage, BgpMessage, BgpMessageType, BgpNotificationMessage, BgpOpenMessage, BgpUpdateMessage};
+use bgp_models::bgp::{BgpKeepAliveMessage, BgpMessage, BgpMessageType, BgpNotificationMessage, BgpOpenMessage, BgpUpdateMessage, ParamValue};
 use bgp_models::mrt::{Bgp4Mp, Bgp4MpMessage, Bgp4MpStateChange, Bgp4MpType};
+use bgp_models::network::AsnLength;
 use byteorder::WriteBytesExt;
-use num_traits::ToPrimitive;
+use num_traits::FromPrimitive;
 
-use crate::{DumpError, MrtDump};
-use crate::mrt_dump::attributes::MrtAttrDump;
-use crate::mrt_dump::utils::WriteUtils;
+use crate::{DumpError, ExtraAttributes, MrtDump, MrtDumpWithAttributes};
+use crate::mrt_dump::AS_TRANS;
+use crate::mrt_dump::attributes::{MrtAttrDump, to_two_octet_asn_attributes};
+use crate::mrt_dump::utils::{length_u16, length_u8, type_code_u16, WriteUtils};
 
 impl MrtDump for Bgp4Mp {
     fn to_bytes(&self, subtype: u16) -> Result<Vec<u8>, DumpError> {
+        self.to_bytes_with_attributes(subtype, &ExtraAttributes::new())
+    }
+}
+
+impl MrtDumpWithAttributes for Bgp4Mp {
+    fn to_bytes_with_attributes(&self, subtype: u16, extra: &ExtraAttributes) -> Result<Vec<u8>, DumpError> {
         match self {
             Bgp4Mp::Bgp4MpStateChange(v) |
             Bgp4Mp::Bgp4MpStateChangeAs4(v) => {
@@ -20,7 +28,7 @@ impl MrtDump for Bgp4Mp {
             Bgp4Mp::Bgp4MpMessageLocal(v) |
             Bgp4Mp::Bgp4MpMessageAs4(v) |
             Bgp4Mp::Bgp4MpMessageAs4Local(v) => {
-                v.to_bytes(subtype)
+                v.to_bytes_with_attributes(subtype, extra)
             }
         }
     }
@@ -28,77 +36,101 @@ impl MrtDump for Bgp4Mp {
 
 impl MrtDump for Bgp4MpStateChange {
     fn to_bytes(&self, _subtype: u16) -> Result<Vec<u8>, DumpError> {
+        let asn_len = match self.msg_type {
+            Bgp4MpType::Bgp4MpStateChangeAs4 => AsnLength::Bits32,
+            _ => AsnLength::Bits16,
+        };
         let mut buffer: Vec<u8> = vec![];
-        buffer.write_asn(&self.peer_asn)?;
-        buffer.write_asn(&self.local_asn)?;
+        buffer.write_asn(&self.peer_asn, asn_len)?;
+        buffer.write_asn(&self.local_asn, asn_len)?;
         buffer.write_16b(self.interface_index)?;
-        buffer.write_16b(self.address_family.to_u16().unwrap())?;
+        buffer.write_16b(type_code_u16(&self.address_family, "BGP4MP_STATE_CHANGE", "address family")?)?;
         buffer.write_ip(&self.peer_addr)?;
         buffer.write_ip(&self.local_addr)?;
-        buffer.write_16b(self.old_state.to_u16().unwrap())?;
-        buffer.write_16b(self.new_state.to_u16().unwrap())?;
+        buffer.write_16b(type_code_u16(&self.old_state, "BGP4MP_STATE_CHANGE", "old state")?)?;
+        buffer.write_16b(type_code_u16(&self.new_state, "BGP4MP_STATE_CHANGE", "new state")?)?;
         Ok(buffer)
     }
 }
 
 impl MrtDump for Bgp4MpMessage {
-    fn to_bytes(&self, _subtype: u16) -> Result<Vec<u8>, DumpError> {
+    fn to_bytes(&self, subtype: u16) -> Result<Vec<u8>, DumpError> {
+        self.to_bytes_with_attributes(subtype, &ExtraAttributes::new())
+    }
+}
+
+impl MrtDumpWithAttributes for Bgp4MpMessage {
+    fn to_bytes_with_attributes(&self, _subtype: u16, extra: &ExtraAttributes) -> Result<Vec<u8>, DumpError> {
+        let subtype = type_code_u16(&self.msg_type, "BGP4MP_MESSAGE", "subtype")?;
+        let asn_len = match bgp4mp_two_octet_asn(subtype) {
+            true => AsnLength::Bits16,
+            false => AsnLength::Bits32,
+        };
         let mut buffer: Vec<u8> = vec![];
-        buffer.write_asn(&self.peer_asn)?;
-        buffer.write_asn(&self.local_asn)?;
+        buffer.write_asn(&self.peer_asn, asn_len)?;
+        buffer.write_asn(&self.local_asn, asn_len)?;
         buffer.write_16b(self.interface_index)?;
-        buffer.write_16b(self.afi.to_u16().unwrap())?;
+        buffer.write_16b(type_code_u16(&self.afi, "BGP4MP_MESSAGE", "address family")?)?;
         buffer.write_ip(&self.peer_ip)?;
         buffer.write_ip(&self.local_ip)?;
 
-        let add_path = match self.msg_type {
-            Bgp4MpType::Bgp4MpMessageAddpath |
-            Bgp4MpType::Bgp4MpMessageAs4Addpath |
-            Bgp4MpType::Bgp4MpMessageLocalAddpath |
-            Bgp4MpType::Bgp4MpMessageLocalAs4Addpath => {1}
-            _ => {0}
-        };
-
-        let msg_bytes = self.bgp_message.to_bytes(add_path)?;
+        // the BGP4MP subtype determines add-path and ASN encoding of the BGP message
+        let msg_bytes = self.bgp_message.to_bytes_with_attributes(subtype, extra)?;
         buffer.extend(msg_bytes);
         Ok(buffer)
     }
 }
 
+/// whether the BGP4MP subtype is one of a session between 2-byte ASN speakers
+fn bgp4mp_two_octet_asn(subtype: u16) -> bool {
+    matches!(Bgp4MpType::from_u16(subtype),
+        Some(Bgp4MpType::Bgp4MpMessage) |
+        Some(Bgp4MpType::Bgp4MpMessageLocal) |
+        Some(Bgp4MpType::Bgp4MpMessageAddpath) |
+        Some(Bgp4MpType::Bgp4MpMessageLocalAddpath)
+    )
+}
+
 /////////
 // BGP //
 /////////
 
 impl MrtDump for BgpMessage {
     fn to_bytes(&self, subtype: u16) -> Result<Vec<u8>, DumpError> {
+        self.to_bytes_with_attributes(subtype, &ExtraAttributes::new())
+    }
+}
+
+impl MrtDumpWithAttributes for BgpMessage {
+    fn to_bytes_with_attributes(&self, subtype: u16, extra: &ExtraAttributes) -> Result<Vec<u8>, DumpError> {
         let mut buffer: Vec<u8> = vec![];
 
         // https://tools.ietf.org/html/rfc4271#section-4
-        // 16 (4 x 4 bytes) octets marker
-        buffer.write_all(&[0;16])?;
+        // 16 (4 x 4 bytes) octets marker, all set to one
+        buffer.write_all(&[0xff;16])?;
 
         let mut msg_bytes: Vec<u8> = vec![];
         match self {
             BgpMessage::Open(m) => {
-                msg_bytes.write_u8(BgpMessageType::OPEN.to_u8().unwrap())?;
+                msg_bytes.write_u8(BgpMessageType::OPEN as u8)?;
                 msg_bytes.extend(m.to_bytes(subtype)?);
             }
             BgpMessage::Update(m) => {
-                msg_bytes.write_u8(BgpMessageType::UPDATE.to_u8().unwrap())?;
-                msg_bytes.extend(m.to_bytes(subtype)?);
+                msg_bytes.write_u8(BgpMessageType::UPDATE as u8)?;
+                msg_bytes.extend(m.to_bytes_with_attributes(subtype, extra)?);
             }
             BgpMessage::Notification(m) => {
-                msg_bytes.write_u8(BgpMessageType::NOTIFICATION.to_u8().unwrap())?;
+                msg_bytes.write_u8(BgpMessageType::NOTIFICATION as u8)?;
                 msg_bytes.extend(m.to_bytes(subtype)?);
             }
             BgpMessage::KeepAlive(m) => {
-                msg_bytes.write_u8(BgpMessageType::KEEPALIVE.to_u8().unwrap())?;
+                msg_bytes.write_u8(BgpMessageType::KEEPALIVE as u8)?;
                 msg_bytes.extend(m.to_bytes(subtype)?);
             }
         };
 
         // length, minimum 19
-        buffer.write_16b(2+16+msg_bytes.len() as u16)?;
+        buffer.write_16b(length_u16(2+16+msg_bytes.len(), "BGP message", "message length")?)?;
         buffer.extend(msg_bytes);
         Ok(buffer)
     }
@@ -108,40 +140,87 @@ impl MrtDump for BgpOpenMessage {
     fn to_bytes(&self, _subtype: u16) -> Result<Vec<u8>, DumpError> {
         let mut buffer: Vec<u8> = vec![];
         buffer.write_u8(self.version)?;
-        buffer.write_16b(self.asn.asn as u16)?;
+        // 4-byte ASNs are announced in the capability, the "My Autonomous System" field has AS_TRANS
+        // https://datatracker.ietf.org/doc/html/rfc6793#section-4.1
+        match self.asn.asn > u16::MAX as u32 {
+            true => buffer.write_16b(AS_TRANS as u16)?,
+            false => buffer.write_16b(self.asn.asn as u16)?,
+        }
         buffer.write_16b(self.hold_time)?;
         buffer.write_ip(&self.sender_ip.into())?;
-        // TODO: opt_parm is not current supported. https://github.com/bgpkit/bgpkit-parser/issues/48
-        buffer.write_u8(0)?;
+
+        // https://datatracker.ietf.org/doc/html/rfc5492#section-4
+        // NOTE: the parser does not keep the parameters of extended-length optional parameters
+        // (RFC 9072), so they are always written in the regular one-octet length format.
+        let mut params_buf: Vec<u8> = vec![];
+        for param in &self.opt_params {
+            params_buf.write_u8(param.param_type)?;
+            match &param.param_value {
+                ParamValue::Raw(v) => {
+                    params_buf.write_u8(length_u8(v.len(), "BGP OPEN", "parameter length")?)?;
+                    params_buf.write_all(v)?;
+                }
+                ParamValue::Capability(c) => {
+                    params_buf.write_u8(length_u8(2 + c.value.len(), "BGP OPEN", "parameter length")?)?;
+                    params_buf.write_u8(c.code)?;
+                    params_buf.write_u8(c.value.len() as u8)?;
+                    params_buf.write_all(&c.value)?;
+                }
+            }
+        }
+        buffer.write_u8(length_u8(params_buf.len(), "BGP OPEN", "optional parameters length")?)?;
+        buffer.write_all(&params_buf)?;
         Ok(buffer)
     }
 }
 
 impl MrtDump for BgpUpdateMessage {
     fn to_bytes(&self, subtype: u16) -> Result<Vec<u8>, DumpError> {
-        // subtype is passed from Bgp4MpMessage
-        let add_path = subtype>0;
+        self.to_bytes_with_attributes(subtype, &ExtraAttributes::new())
+    }
+}
+
+impl MrtDumpWithAttributes for BgpUpdateMessage {
+    /// The extra attributes of the message are in list 0 of `extra`.
+    fn to_bytes_with_attributes(&self, subtype: u16, extra: &ExtraAttributes) -> Result<Vec<u8>, DumpError> {
+        // subtype is the BGP4MP subtype passed from Bgp4MpMessage
+        let add_path = matches!(Bgp4MpType::from_u16(subtype),
+            Some(Bgp4MpType::Bgp4MpMessageAddpath) |
+            Some(Bgp4MpType::Bgp4MpMessageAs4Addpath) |
+            Some(Bgp4MpType::Bgp4MpMessageLocalAddpath) |
+            Some(Bgp4MpType::Bgp4MpMessageLocalAs4Addpath)
+        );
+        let two_octet_asn = bgp4mp_two_octet_asn(subtype);
+        let asn_len = match two_octet_asn {
+            true => AsnLength::Bits16,
+            false => AsnLength::Bits32,
+        };
         let mut buffer: Vec<u8> = vec![];
 
         let mut tmp_buf: Vec<u8> = vec![];
         for prefix in &self.withdrawn_prefixes {
             tmp_buf.write_nlri(prefix, add_path)?;
         }
-        buffer.write_16b(tmp_buf.len() as u16)?;
-        buffer.write(tmp_buf.as_slice())?;
+        buffer.write_16b(length_u16(tmp_buf.len(), "BGP UPDATE", "withdrawn routes length")?)?;
+        buffer.write_all(tmp_buf.as_slice())?;
 
+        let attributes = extra.merged(0, &self.attributes);
+        let attributes = match two_octet_asn {
+            true => to_two_octet_asn_attributes(&attributes),
+            false => attributes,
+        };
         let mut tmp_buf: Vec<u8> = vec![];
-        for attr in &self.attributes {
-            tmp_buf.extend(attr.to_bytes(add_path, true, true, true)?);
+        for attr in &attributes {
+            tmp_buf.extend(attr.to_bytes(add_path, asn_len, true, true, true)?);
         }
-        buffer.write_16b(tmp_buf.len() as u16)?;
-        buffer.write(tmp_buf.as_slice())?;
+        buffer.write_16b(length_u16(tmp_buf.len(), "BGP UPDATE", "total path attribute length")?)?;
+        buffer.write_all(tmp_buf.as_slice())?;
 
         let mut tmp_buf: Vec<u8> = vec![];
         for prefix in &self.announced_prefixes {
             tmp_buf.write_nlri(prefix, add_path)?;
         }
-        buffer.write(tmp_buf.as_slice())?;
+        buffer.write_all(tmp_buf.as_slice())?;
         Ok(buffer)
     }
 }
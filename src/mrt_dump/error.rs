@@ -1,11 +1,118 @@
+use std::fmt::{Display, Formatter};
+
+use bgp_models::mrt::EntryType;
+use bgp_models::network::NetworkPrefix;
 use thiserror::Error;
 
+use crate::ValidationFinding;
+
 #[derive(Error, Debug)]
 pub enum DumpError {
     /// Represents all other cases of `std::io::Error`.
     #[error(transparent)]
     IOError(#[from] std::io::Error),
 
-    #[error("{0}")]
-    MsgTypeError(String),
-}
\ No newline at end of file
+    /// The message type is not supported by the encoder.
+    #[error("encoding {0} messages is not supported")]
+    UnsupportedMessage(&'static str),
+
+    /// A type code in the data model cannot be encoded in the given field.
+    #[error("{record}: unsupported {field} {value}")]
+    UnsupportedType {
+        record: &'static str,
+        field: &'static str,
+        value: String,
+    },
+
+    /// A value does not fit in its field, e.g. a 4-byte ASN in a 2-byte ASN field.
+    #[error("{record}: {field} value {value} exceeds the maximum of {max}")]
+    ValueOutOfRange {
+        record: &'static str,
+        field: &'static str,
+        value: u64,
+        max: u64,
+    },
+
+    /// A prefix has bits set beyond its prefix length, in strict mode.
+    #[error("prefix {0} has host bits set")]
+    HostBitsSet(NetworkPrefix),
+
+    /// Encoded data is too long for its length field.
+    #[error("{record}: {field} length {length} exceeds the maximum of {max}")]
+    LengthOverflow {
+        record: &'static str,
+        field: &'static str,
+        length: usize,
+        max: usize,
+    },
+
+    /// The peers of a peer index table do not match its peer count.
+    #[error("PEER_INDEX_TABLE with peer count {peer_count} is inconsistent at peer index {peer_index}")]
+    InconsistentPeerTable {
+        peer_count: u16,
+        peer_index: u32,
+    },
+
+    /// An argument to a writer is invalid.
+    #[error("invalid {name}: {value}")]
+    InvalidArgument {
+        name: &'static str,
+        value: String,
+    },
+
+    /// The record was rejected by a [crate::RecordValidator].
+    #[error("validation failed: {}", .0.iter().map(|f| f.to_string()).collect::<Vec<String>>().join("; "))]
+    ValidationFailed(Vec<ValidationFinding>),
+
+    /// An error while encoding a record, with the context of the record.
+    #[error("{context}: {source}")]
+    Record {
+        context: RecordContext,
+        source: Box<DumpError>,
+    },
+}
+
+impl DumpError {
+    /// Attach the context of the record being encoded, unless the error already has one.
+    pub(crate) fn in_record(self, context: RecordContext) -> DumpError {
+        match self {
+            DumpError::Record { .. } => self,
+            e => DumpError::Record { context, source: Box::new(e) },
+        }
+    }
+
+    /// Set the index of the record in its output, if the error has a record context.
+    pub(crate) fn with_record_index(self, index: usize) -> DumpError {
+        match self {
+            DumpError::Record { mut context, source } => {
+                context.index = Some(index);
+                DumpError::Record { context, source }
+            }
+            e => e,
+        }
+    }
+}
+
+/// The MRT record an error occurred in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordContext {
+    /// index of the record in its output, if known
+    pub index: Option<usize>,
+    pub entry_type: EntryType,
+    pub subtype: u16,
+    /// prefix of RIB records and BGP updates
+    pub prefix: Option<NetworkPrefix>,
+}
+
+impl Display for RecordContext {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.index {
+            Some(index) => write!(f, "record {} ({:?}/{}", index, self.entry_type, self.subtype)?,
+            None => write!(f, "record ({:?}/{}", self.entry_type, self.subtype)?,
+        }
+        if let Some(prefix) = &self.prefix {
+            write!(f, ", prefix {}", prefix)?;
+        }
+        write!(f, ")")
+    }
+}
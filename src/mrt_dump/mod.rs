@@ -1,6 +1,17 @@
+pub use path_attribute::{ExtraAttributes, PathAttribute};
+pub use raw_attribute::RawAttribute;
+pub(crate) use attributes::AS_TRANS;
+pub(crate) use raw_attribute::{dropped_attributes, split_attributes};
+pub(crate) use mrt::record_context;
+pub(crate) use utils::normalize_prefix;
+
 mod mrt;
 mod table_dump;
 mod bgp;
 mod attributes;
 mod utils;
+mod raw_attribute;
+mod path_attribute;
+pub mod attr_values;
+pub mod nlri;
 pub mod error;
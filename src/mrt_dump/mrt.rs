@@ -1,48 +1,93 @@
-use bgp_models::mrt::{CommonHeader, MrtMessage, MrtRecord};
-use num_traits::ToPrimitive;
+use bgp_models::bgp::BgpMessage;
+use bgp_models::mrt::{Bgp4Mp, CommonHeader, MrtMessage, MrtRecord, TableDumpV2Message};
 
-use crate::{DumpError, MrtDump};
-use crate::mrt_dump::utils::WriteUtils;
+use crate::{DumpError, ExtraAttributes, MrtDump, MrtDumpWithAttributes, RecordContext};
+use crate::mrt_dump::utils::{length_u32, type_code_u16, WriteUtils};
 
 impl MrtDump for MrtRecord {
-    fn to_bytes(&self, _subtype: u16) -> Result<Vec<u8>, DumpError> {
+    fn to_bytes(&self, subtype: u16) -> Result<Vec<u8>, DumpError> {
+        self.to_bytes_with_attributes(subtype, &ExtraAttributes::new())
+    }
+}
+
+impl MrtDumpWithAttributes for MrtRecord {
+    fn to_bytes_with_attributes(&self, _subtype: u16, extra: &ExtraAttributes) -> Result<Vec<u8>, DumpError> {
         let mut buffer: Vec<u8> = vec![];
 
-        let bytes = self.message.to_bytes(self.common_header.entry_subtype)?;
-        buffer.extend(self.common_header.to_bytes(bytes.len() as u16)?);
+        let bytes = self.message.to_bytes_with_attributes(self.common_header.entry_subtype, extra)
+            .and_then(|bytes| {
+                buffer.extend(common_header_bytes(&self.common_header, bytes.len())?);
+                Ok(bytes)
+            })
+            .map_err(|e| e.in_record(record_context(self)))?;
         buffer.extend(bytes.as_slice());
 
         Ok(buffer)
     }
 }
 
+/// context of the record for errors, using the first prefix of RIB records and BGP updates
+pub(crate) fn record_context(record: &MrtRecord) -> RecordContext {
+    let prefix = match &record.message {
+        MrtMessage::TableDumpMessage(m) => Some(m.prefix),
+        MrtMessage::TableDumpV2Message(TableDumpV2Message::RibAfiEntries(m)) => Some(m.prefix),
+        MrtMessage::TableDumpV2Message(TableDumpV2Message::RibGenericEntries(m)) => Some(m.nlri),
+        MrtMessage::TableDumpV2Message(TableDumpV2Message::PeerIndexTable(_)) => None,
+        MrtMessage::Bgp4Mp(Bgp4Mp::Bgp4MpMessage(m)) |
+        MrtMessage::Bgp4Mp(Bgp4Mp::Bgp4MpMessageLocal(m)) |
+        MrtMessage::Bgp4Mp(Bgp4Mp::Bgp4MpMessageAs4(m)) |
+        MrtMessage::Bgp4Mp(Bgp4Mp::Bgp4MpMessageAs4Local(m)) => match &m.bgp_message {
+            BgpMessage::Update(u) => u.announced_prefixes.first().or_else(|| u.withdrawn_prefixes.first()).copied(),
+            _ => None,
+        },
+        MrtMessage::Bgp4Mp(_) => None,
+    };
+    RecordContext {
+        index: None,
+        entry_type: record.common_header.entry_type,
+        subtype: record.common_header.entry_subtype,
+        prefix,
+    }
+}
+
 impl MrtDump for CommonHeader {
     fn to_bytes(&self, size: u16) -> Result<Vec<u8>, DumpError> {
-        let mut buffer: Vec<u8> = vec![];
+        common_header_bytes(self, size as usize)
+    }
+}
 
-        buffer.write_32b(self.timestamp)?;
+/// Encode the common header of a record whose message is `size` bytes long.
+fn common_header_bytes(header: &CommonHeader, size: usize) -> Result<Vec<u8>, DumpError> {
+    let mut buffer: Vec<u8> = vec![];
 
-        buffer.write_16b(self.entry_type.to_u16().unwrap())?;
-        buffer.write_16b(self.entry_subtype)?;
+    buffer.write_32b(header.timestamp)?;
 
+    buffer.write_16b(type_code_u16(&header.entry_type, "MRT header", "entry type")?)?;
+    buffer.write_16b(header.entry_subtype)?;
 
-        if let Some(mt) = self.microsecond_timestamp {
-            buffer.write_32b((size + 4) as u32)?;
-            buffer.write_32b(mt)?;
-        } else {
-            buffer.write_32b(size as u32)?;
-        }
-        Ok(buffer)
+    // the length of extended timestamp records includes the microsecond timestamp
+    if let Some(mt) = header.microsecond_timestamp {
+        buffer.write_32b(length_u32(size + 4, "MRT header", "length")?)?;
+        buffer.write_32b(mt)?;
+    } else {
+        buffer.write_32b(length_u32(size, "MRT header", "length")?)?;
     }
+    Ok(buffer)
 }
 
 impl MrtDump for MrtMessage {
     fn to_bytes(&self, subtype: u16) -> Result<Vec<u8>, DumpError> {
+        self.to_bytes_with_attributes(subtype, &ExtraAttributes::new())
+    }
+}
+
+impl MrtDumpWithAttributes for MrtMessage {
+    fn to_bytes_with_attributes(&self, subtype: u16, extra: &ExtraAttributes) -> Result<Vec<u8>, DumpError> {
         Ok(
             match self {
                 MrtMessage::TableDumpMessage(m) => m.to_bytes(subtype)?,
-                MrtMessage::TableDumpV2Message(m) => m.to_bytes(subtype)?,
-                MrtMessage::Bgp4Mp(m) => m.to_bytes(subtype)?,
+                MrtMessage::TableDumpV2Message(m) => m.to_bytes_with_attributes(subtype, extra)?,
+                MrtMessage::Bgp4Mp(m) => m.to_bytes_with_attributes(subtype, extra)?,
             }
         )
     }
@@ -75,4 +120,54 @@ mod tests {
         // test equality!
         assert_eq!(header1, header2);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_unsupported_messages() {
+        let record = MrtRecord {
+            common_header: CommonHeader {
+                timestamp: 1,
+                microsecond_timestamp: None,
+                entry_type: EntryType::TABLE_DUMP_V2,
+                entry_subtype: 1,
+                length: 0,
+            },
+            message: MrtMessage::TableDumpV2Message(TableDumpV2Message::RibGenericEntries(RibGenericEntries {
+                sequence_number: 0,
+                afi: Afi::Ipv4,
+                safi: Safi::Unicast,
+                nlri: NetworkPrefix::new("10.0.0.0/8".parse().unwrap(), 0),
+                rib_entries: vec![],
+            })),
+        };
+        match record.to_bytes(0) {
+            Err(DumpError::Record { context, source }) => {
+                assert_eq!(context.prefix, Some(NetworkPrefix::new("10.0.0.0/8".parse().unwrap(), 0)));
+                assert!(matches!(*source, DumpError::UnsupportedMessage("RIB_GENERIC")));
+            }
+            _ => panic!("expecting error with record context"),
+        }
+
+        // the subtype of a RIB record must be a known RIB subtype
+        let record = MrtRecord {
+            common_header: CommonHeader {
+                timestamp: 1,
+                microsecond_timestamp: None,
+                entry_type: EntryType::TABLE_DUMP_V2,
+                entry_subtype: 100,
+                length: 0,
+            },
+            message: MrtMessage::TableDumpV2Message(TableDumpV2Message::RibAfiEntries(RibAfiEntries {
+                rib_type: TableDumpV2Type::RibIpv4Unicast,
+                sequence_number: 0,
+                prefix: NetworkPrefix::new("10.0.0.0/8".parse().unwrap(), 0),
+                rib_entries: vec![],
+            })),
+        };
+        let err = record.to_bytes(0).unwrap_err();
+        assert!(matches!(&err, DumpError::Record { source, .. } if matches!(**source, DumpError::UnsupportedType { record: "RIB_AFI", .. })));
+        assert_eq!(
+            err.with_record_index(3).to_string(),
+            "record 3 (TABLE_DUMP_V2/100, prefix 10.0.0.0/8): RIB_AFI: unsupported subtype 100"
+        );
+    }
+}
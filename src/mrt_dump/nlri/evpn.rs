@@ -0,0 +1,287 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use ipnetwork::IpNetwork;
+
+use crate::DumpError;
+use crate::mrt_dump::nlri::{NlriDump, RouteDistinguisher};
+use crate::mrt_dump::nlri::labeled::MAX_MPLS_LABEL;
+use crate::mrt_dump::utils::{length_u8, WriteUtils};
+
+const MAX_VNI: u32 = 0xFF_FFFF;
+const MAC_ADDRESS_BITS: u8 = 48;
+
+/// Ethernet segment identifier
+pub type Esi = [u8; 10];
+
+/// EVPN route, for AFI 25 and SAFI 70.
+/// https://datatracker.ietf.org/doc/html/rfc7432#section-7
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvpnNlri {
+    pub route_distinguisher: RouteDistinguisher,
+    pub route: EvpnRoute,
+    pub path_id: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EvpnRoute {
+    /// type 1
+    EthernetAutoDiscovery { esi: Esi, ethernet_tag: u32, label: EvpnLabel },
+    /// type 2
+    MacIpAdvertisement {
+        esi: Esi,
+        ethernet_tag: u32,
+        mac: [u8; 6],
+        ip: Option<IpAddr>,
+        label: EvpnLabel,
+        /// Second label, e.g. for symmetric IRB
+        label2: Option<EvpnLabel>,
+    },
+    /// type 3
+    InclusiveMulticast { ethernet_tag: u32, originating_router: IpAddr },
+    /// type 4
+    EthernetSegment { esi: Esi, originating_router: IpAddr },
+    /// type 5, https://datatracker.ietf.org/doc/html/rfc9136#section-3.1
+    IpPrefix { esi: Esi, ethernet_tag: u32, prefix: IpNetwork, gateway: IpAddr, label: EvpnLabel },
+}
+
+/// Label field of EVPN routes, an MPLS label or with VXLAN encapsulation a VNI.
+/// https://datatracker.ietf.org/doc/html/rfc8365#section-5.1.3
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvpnLabel {
+    Mpls(u32),
+    Vni(u32),
+}
+
+impl EvpnLabel {
+    fn to_bytes(self) -> Result<[u8; 3], DumpError> {
+        let (field, value, max) = match self {
+            EvpnLabel::Mpls(label) => ("MPLS label", label, MAX_MPLS_LABEL),
+            EvpnLabel::Vni(vni) => ("VNI", vni, MAX_VNI),
+        };
+        if value > max {
+            return Err(DumpError::ValueOutOfRange {
+                record: "EVPN NLRI",
+                field,
+                value: value as u64,
+                max: max as u64,
+            })
+        }
+        let label_field = match self {
+            // label with the bottom-of-stack bit
+            EvpnLabel::Mpls(label) => label << 4 | 0x01,
+            EvpnLabel::Vni(vni) => vni,
+        };
+        let bytes = label_field.to_be_bytes();
+        Ok([bytes[1], bytes[2], bytes[3]])
+    }
+}
+
+impl EvpnNlri {
+    fn new(route_distinguisher: RouteDistinguisher, route: EvpnRoute) -> Self {
+        EvpnNlri { route_distinguisher, route, path_id: 0 }
+    }
+
+    pub fn ethernet_auto_discovery(route_distinguisher: RouteDistinguisher, esi: Esi, ethernet_tag: u32, label: EvpnLabel) -> Self {
+        Self::new(route_distinguisher, EvpnRoute::EthernetAutoDiscovery { esi, ethernet_tag, label })
+    }
+
+    /// MAC/IP advertisement route on a single-homed segment with Ethernet tag 0.
+    pub fn mac_ip_advertisement(route_distinguisher: RouteDistinguisher, mac: [u8; 6], ip: Option<IpAddr>, label: EvpnLabel) -> Self {
+        Self::new(route_distinguisher, EvpnRoute::MacIpAdvertisement {
+            esi: [0; 10],
+            ethernet_tag: 0,
+            mac,
+            ip,
+            label,
+            label2: None,
+        })
+    }
+
+    pub fn inclusive_multicast(route_distinguisher: RouteDistinguisher, ethernet_tag: u32, originating_router: IpAddr) -> Self {
+        Self::new(route_distinguisher, EvpnRoute::InclusiveMulticast { ethernet_tag, originating_router })
+    }
+
+    pub fn ethernet_segment(route_distinguisher: RouteDistinguisher, esi: Esi, originating_router: IpAddr) -> Self {
+        Self::new(route_distinguisher, EvpnRoute::EthernetSegment { esi, originating_router })
+    }
+
+    /// IP prefix route on a single-homed segment with Ethernet tag 0 and no gateway address.
+    pub fn ip_prefix(route_distinguisher: RouteDistinguisher, prefix: IpNetwork, label: EvpnLabel) -> Self {
+        let gateway = match prefix {
+            IpNetwork::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            IpNetwork::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        };
+        Self::new(route_distinguisher, EvpnRoute::IpPrefix { esi: [0; 10], ethernet_tag: 0, prefix, gateway, label })
+    }
+
+    pub fn with_path_id(mut self, path_id: u32) -> Self {
+        self.path_id = path_id;
+        self
+    }
+}
+
+impl EvpnRoute {
+    pub fn route_type(&self) -> u8 {
+        match self {
+            EvpnRoute::EthernetAutoDiscovery { .. } => 1,
+            EvpnRoute::MacIpAdvertisement { .. } => 2,
+            EvpnRoute::InclusiveMulticast { .. } => 3,
+            EvpnRoute::EthernetSegment { .. } => 4,
+            EvpnRoute::IpPrefix { .. } => 5,
+        }
+    }
+}
+
+/// Write an IP address preceded by its length in bits.
+fn write_ip_with_length(buffer: &mut Vec<u8>, ip: &IpAddr) -> Result<(), DumpError> {
+    buffer.push(match ip {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    });
+    buffer.write_ip(ip)
+}
+
+impl NlriDump for EvpnNlri {
+    fn nlri_bytes(&self) -> Result<Vec<u8>, DumpError> {
+        let mut buffer: Vec<u8> = vec![];
+        buffer.extend_from_slice(&self.route_distinguisher.to_bytes());
+        match &self.route {
+            EvpnRoute::EthernetAutoDiscovery { esi, ethernet_tag, label } => {
+                buffer.extend_from_slice(esi);
+                buffer.write_32b(*ethernet_tag)?;
+                buffer.extend_from_slice(&label.to_bytes()?);
+            }
+            EvpnRoute::MacIpAdvertisement { esi, ethernet_tag, mac, ip, label, label2 } => {
+                buffer.extend_from_slice(esi);
+                buffer.write_32b(*ethernet_tag)?;
+                buffer.push(MAC_ADDRESS_BITS);
+                buffer.extend_from_slice(mac);
+                match ip {
+                    Some(ip) => write_ip_with_length(&mut buffer, ip)?,
+                    None => buffer.push(0),
+                }
+                buffer.extend_from_slice(&label.to_bytes()?);
+                if let Some(label2) = label2 {
+                    buffer.extend_from_slice(&label2.to_bytes()?);
+                }
+            }
+            EvpnRoute::InclusiveMulticast { ethernet_tag, originating_router } => {
+                buffer.write_32b(*ethernet_tag)?;
+                write_ip_with_length(&mut buffer, originating_router)?;
+            }
+            EvpnRoute::EthernetSegment { esi, originating_router } => {
+                buffer.extend_from_slice(esi);
+                write_ip_with_length(&mut buffer, originating_router)?;
+            }
+            EvpnRoute::IpPrefix { esi, ethernet_tag, prefix, gateway, label } => {
+                if prefix.is_ipv4() != gateway.is_ipv4() {
+                    return Err(DumpError::InvalidArgument {
+                        name: "EVPN IP prefix gateway",
+                        value: format!("{} for {}", gateway, prefix),
+                    })
+                }
+                buffer.extend_from_slice(esi);
+                buffer.write_32b(*ethernet_tag)?;
+                // the prefix is written with the full address length
+                buffer.push(prefix.prefix());
+                buffer.write_ip(&prefix.network())?;
+                buffer.write_ip(gateway)?;
+                buffer.extend_from_slice(&label.to_bytes()?);
+            }
+        }
+
+        let mut nlri = vec![self.route.route_type(), length_u8(buffer.len(), "EVPN NLRI", "route length")?];
+        nlri.extend(buffer);
+        Ok(nlri)
+    }
+
+    fn path_id(&self) -> u32 {
+        self.path_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bgp_models::network::AsnLength;
+
+    use crate::{AFI_L2VPN, MpNextHop, MpReachAttribute, SAFI_EVPN};
+    use crate::mrt_dump::attributes::MrtAttrDump;
+
+    use super::*;
+
+    const RD: RouteDistinguisher = RouteDistinguisher::Ipv4 { address: Ipv4Addr::new(192, 0, 2, 1), number: 100 };
+    const RD_BYTES: [u8; 8] = [0, 1, 192, 0, 2, 1, 0, 100];
+    const ESI: Esi = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9];
+
+    #[test]
+    fn test_evpn_route_types() {
+        let router = IpAddr::from_str("192.0.2.1").unwrap();
+
+        let bytes = EvpnNlri::ethernet_auto_discovery(RD, ESI, 0xFFFFFFFF, EvpnLabel::Mpls(0)).nlri_bytes().unwrap();
+        assert_eq!(bytes[..2], [1, 25]);
+        assert_eq!(bytes[2..10], RD_BYTES);
+        assert_eq!(bytes[10..20], ESI);
+        assert_eq!(bytes[20..], [0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 1]);
+
+        let mac = [0x00, 0x11, 0x22, 0x33, 0x44, 0x55];
+        let bytes = EvpnNlri::mac_ip_advertisement(RD, mac, None, EvpnLabel::Vni(10100)).nlri_bytes().unwrap();
+        assert_eq!(bytes[..2], [2, 33]);
+        assert_eq!(bytes[10..], [
+            0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+            0, 0, 0, 0,
+            48, 0x00, 0x11, 0x22, 0x33, 0x44, 0x55,
+            0,
+            0x00, 0x27, 0x74,
+        ]);
+        let mut with_ip = EvpnNlri::mac_ip_advertisement(RD, mac, Some(IpAddr::from_str("2001:db8::5").unwrap()), EvpnLabel::Vni(10100));
+        if let EvpnRoute::MacIpAdvertisement { label2, .. } = &mut with_ip.route {
+            *label2 = Some(EvpnLabel::Vni(50000));
+        }
+        let bytes = with_ip.nlri_bytes().unwrap();
+        assert_eq!(bytes[..2], [2, 52]);
+        assert_eq!(bytes[31], 128);
+        assert_eq!(bytes[48..], [0x00, 0x27, 0x74, 0x00, 0xC3, 0x50]);
+
+        let bytes = EvpnNlri::inclusive_multicast(RD, 100, router).nlri_bytes().unwrap();
+        assert_eq!(bytes, [3, 17, 0, 1, 192, 0, 2, 1, 0, 100, 0, 0, 0, 100, 32, 192, 0, 2, 1]);
+
+        let bytes = EvpnNlri::ethernet_segment(RD, ESI, router).nlri_bytes().unwrap();
+        assert_eq!(bytes[..2], [4, 23]);
+        assert_eq!(bytes[20..], [32, 192, 0, 2, 1]);
+
+        let bytes = EvpnNlri::ip_prefix(RD, IpNetwork::from_str("10.1.0.0/16").unwrap(), EvpnLabel::Mpls(16))
+            .nlri_bytes()
+            .unwrap();
+        assert_eq!(bytes[..2], [5, 34]);
+        assert_eq!(bytes[24..], [16, 10, 1, 0, 0, 0, 0, 0, 0, 0x00, 0x01, 0x01]);
+        let bytes = EvpnNlri::ip_prefix(RD, IpNetwork::from_str("2001:db8::/32").unwrap(), EvpnLabel::Vni(1))
+            .nlri_bytes()
+            .unwrap();
+        assert_eq!(bytes[..2], [5, 58]);
+
+        let mut invalid = EvpnNlri::ip_prefix(RD, IpNetwork::from_str("10.1.0.0/16").unwrap(), EvpnLabel::Mpls(16));
+        if let EvpnRoute::IpPrefix { gateway, .. } = &mut invalid.route {
+            *gateway = IpAddr::from_str("2001:db8::1").unwrap();
+        }
+        assert!(matches!(invalid.nlri_bytes(), Err(DumpError::InvalidArgument { .. })));
+        let invalid = EvpnNlri::ethernet_auto_discovery(RD, ESI, 0, EvpnLabel::Mpls(MAX_MPLS_LABEL + 1));
+        assert!(matches!(invalid.nlri_bytes(), Err(DumpError::ValueOutOfRange { .. })));
+    }
+
+    #[test]
+    fn test_evpn_mp_reach() {
+        let router = IpAddr::from_str("192.0.2.1").unwrap();
+        let reach = MpReachAttribute {
+            afi: AFI_L2VPN,
+            safi: SAFI_EVPN,
+            next_hop: MpNextHop::Address(router),
+            nlri: vec![EvpnNlri::inclusive_multicast(RD, 0, router).with_path_id(2)],
+        };
+        let value = reach.to_bytes(true, AsnLength::Bits32, true, true, true).unwrap()[3..].to_vec();
+        assert_eq!(value[..9], [0, 25, 70, 4, 192, 0, 2, 1, 0]);
+        assert_eq!(value[9..15], [0, 0, 0, 2, 3, 17]);
+        assert_eq!(value.len(), 15 + 17);
+    }
+}
@@ -0,0 +1,399 @@
+use bgp_models::network::Afi;
+use ipnetwork::IpNetwork;
+
+use crate::DumpError;
+use crate::mrt_dump::nlri::{NlriDump, RouteDistinguisher};
+
+/// NLRI lengths from this value on are written in two bytes
+const EXTENDED_LENGTH_THRESHOLD: usize = 240;
+const MAX_NLRI_LENGTH: usize = 0xFFF;
+
+// operator bits
+const OP_END_OF_LIST: u8 = 0x80;
+const OP_AND: u8 = 0x40;
+const OP_LT: u8 = 0x04;
+const OP_GT: u8 = 0x02;
+const OP_EQ: u8 = 0x01;
+const OP_NOT: u8 = 0x02;
+const OP_MATCH: u8 = 0x01;
+
+/// Flow specification NLRI, for SAFI 133 and, with a route distinguisher, SAFI 134.
+/// https://datatracker.ietf.org/doc/html/rfc8955#section-4
+/// https://datatracker.ietf.org/doc/html/rfc8956#section-3
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlowspecNlri {
+    pub afi: Afi,
+    pub route_distinguisher: Option<RouteDistinguisher>,
+    /// Components, written ordered by component type. Each type may appear once.
+    pub components: Vec<FlowspecComponent>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FlowspecComponent {
+    DestinationPrefix(FlowspecPrefix),
+    SourcePrefix(FlowspecPrefix),
+    /// IP protocol, or the next header with IPv6
+    IpProtocol(Vec<NumericOp>),
+    Port(Vec<NumericOp>),
+    DestinationPort(Vec<NumericOp>),
+    SourcePort(Vec<NumericOp>),
+    IcmpType(Vec<NumericOp>),
+    IcmpCode(Vec<NumericOp>),
+    TcpFlags(Vec<BitmaskOp>),
+    PacketLength(Vec<NumericOp>),
+    Dscp(Vec<NumericOp>),
+    Fragment(Vec<BitmaskOp>),
+    /// IPv6 only
+    FlowLabel(Vec<NumericOp>),
+}
+
+/// A prefix to match. The offset in bits is only supported with IPv6, where it skips the
+/// beginning of the address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlowspecPrefix {
+    pub prefix: IpNetwork,
+    pub offset: u8,
+}
+
+/// Numeric comparison with a value. Consecutive operators are ORed unless `and` is set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NumericOp {
+    pub and: bool,
+    pub lt: bool,
+    pub gt: bool,
+    pub eq: bool,
+    pub value: u64,
+}
+
+/// Bitmask match with a value. Without `match_all` any of the bits matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BitmaskOp {
+    pub and: bool,
+    pub not: bool,
+    pub match_all: bool,
+    pub value: u64,
+}
+
+impl FlowspecPrefix {
+    pub fn new(prefix: IpNetwork) -> Self {
+        FlowspecPrefix { prefix, offset: 0 }
+    }
+}
+
+impl NumericOp {
+    fn compare(value: u64, lt: bool, gt: bool, eq: bool) -> Self {
+        NumericOp { and: false, lt, gt, eq, value }
+    }
+
+    pub fn eq(value: u64) -> Self {
+        Self::compare(value, false, false, true)
+    }
+
+    pub fn ne(value: u64) -> Self {
+        Self::compare(value, true, true, false)
+    }
+
+    pub fn lt(value: u64) -> Self {
+        Self::compare(value, true, false, false)
+    }
+
+    pub fn le(value: u64) -> Self {
+        Self::compare(value, true, false, true)
+    }
+
+    pub fn gt(value: u64) -> Self {
+        Self::compare(value, false, true, false)
+    }
+
+    pub fn ge(value: u64) -> Self {
+        Self::compare(value, false, true, true)
+    }
+
+    /// AND this operator with the previous one.
+    pub fn and(mut self) -> Self {
+        self.and = true;
+        self
+    }
+}
+
+impl BitmaskOp {
+    /// Match if any of the bits is set.
+    pub fn any(value: u64) -> Self {
+        BitmaskOp { and: false, not: false, match_all: false, value }
+    }
+
+    /// Match if all bits are set.
+    pub fn all(value: u64) -> Self {
+        BitmaskOp { and: false, not: false, match_all: true, value }
+    }
+
+    /// Negate the match.
+    pub fn negate(mut self) -> Self {
+        self.not = true;
+        self
+    }
+
+    /// AND this operator with the previous one.
+    pub fn and(mut self) -> Self {
+        self.and = true;
+        self
+    }
+}
+
+impl FlowspecComponent {
+    pub fn component_type(&self) -> u8 {
+        match self {
+            FlowspecComponent::DestinationPrefix(_) => 1,
+            FlowspecComponent::SourcePrefix(_) => 2,
+            FlowspecComponent::IpProtocol(_) => 3,
+            FlowspecComponent::Port(_) => 4,
+            FlowspecComponent::DestinationPort(_) => 5,
+            FlowspecComponent::SourcePort(_) => 6,
+            FlowspecComponent::IcmpType(_) => 7,
+            FlowspecComponent::IcmpCode(_) => 8,
+            FlowspecComponent::TcpFlags(_) => 9,
+            FlowspecComponent::PacketLength(_) => 10,
+            FlowspecComponent::Dscp(_) => 11,
+            FlowspecComponent::Fragment(_) => 12,
+            FlowspecComponent::FlowLabel(_) => 13,
+        }
+    }
+}
+
+/// The length field code and the value bytes of an operator value, in the shortest of 1, 2, 4 or
+/// 8 bytes.
+fn operator_value(value: u64) -> (u8, Vec<u8>) {
+    let bytes = value.to_be_bytes();
+    match value {
+        v if v <= u8::MAX as u64 => (0, bytes[7..].to_vec()),
+        v if v <= u16::MAX as u64 => (1, bytes[6..].to_vec()),
+        v if v <= u32::MAX as u64 => (2, bytes[4..].to_vec()),
+        _ => (3, bytes.to_vec()),
+    }
+}
+
+fn write_operators<T>(buffer: &mut Vec<u8>, ops: &[T], encode: impl Fn(&T) -> (u8, u64)) -> Result<(), DumpError> {
+    if ops.is_empty() {
+        return Err(DumpError::InvalidArgument {
+            name: "flowspec component",
+            value: "no operators".to_string(),
+        })
+    }
+    for (i, op) in ops.iter().enumerate() {
+        let (mut op_byte, value) = encode(op);
+        let (len_code, value_bytes) = operator_value(value);
+        op_byte |= len_code << 4;
+        if i == ops.len() - 1 {
+            op_byte |= OP_END_OF_LIST;
+        }
+        buffer.push(op_byte);
+        buffer.extend(value_bytes);
+    }
+    Ok(())
+}
+
+fn write_numeric(buffer: &mut Vec<u8>, ops: &[NumericOp]) -> Result<(), DumpError> {
+    write_operators(buffer, ops, |op| {
+        let mut op_byte = 0;
+        if op.and { op_byte |= OP_AND }
+        if op.lt { op_byte |= OP_LT }
+        if op.gt { op_byte |= OP_GT }
+        if op.eq { op_byte |= OP_EQ }
+        (op_byte, op.value)
+    })
+}
+
+fn write_bitmask(buffer: &mut Vec<u8>, ops: &[BitmaskOp]) -> Result<(), DumpError> {
+    write_operators(buffer, ops, |op| {
+        let mut op_byte = 0;
+        if op.and { op_byte |= OP_AND }
+        if op.not { op_byte |= OP_NOT }
+        if op.match_all { op_byte |= OP_MATCH }
+        (op_byte, op.value)
+    })
+}
+
+fn write_prefix(buffer: &mut Vec<u8>, prefix: &FlowspecPrefix, afi: Afi) -> Result<(), DumpError> {
+    let length = prefix.prefix.prefix();
+    match (afi, prefix.prefix.network()) {
+        (Afi::Ipv4, std::net::IpAddr::V4(network)) if prefix.offset == 0 => {
+            buffer.push(length);
+            let n_bytes = (length as usize).div_ceil(8);
+            buffer.extend_from_slice(&network.octets()[..n_bytes]);
+        }
+        (Afi::Ipv6, std::net::IpAddr::V6(network)) if prefix.offset <= length => {
+            // the pattern holds the bits from the offset to the prefix length
+            buffer.push(length);
+            buffer.push(prefix.offset);
+            let pattern = u128::from(network).checked_shl(prefix.offset as u32).unwrap_or(0);
+            let n_bytes = ((length - prefix.offset) as usize).div_ceil(8);
+            buffer.extend_from_slice(&pattern.to_be_bytes()[..n_bytes]);
+        }
+        _ => {
+            return Err(DumpError::InvalidArgument {
+                name: "flowspec prefix",
+                value: format!("{} with offset {} for {:?}", prefix.prefix, prefix.offset, afi),
+            })
+        }
+    }
+    Ok(())
+}
+
+impl NlriDump for FlowspecNlri {
+    fn nlri_bytes(&self) -> Result<Vec<u8>, DumpError> {
+        let mut components: Vec<&FlowspecComponent> = self.components.iter().collect();
+        components.sort_by_key(|c| c.component_type());
+
+        let mut buffer: Vec<u8> = vec![];
+        if let Some(rd) = &self.route_distinguisher {
+            buffer.extend_from_slice(&rd.to_bytes());
+        }
+        let mut last_type = 0;
+        for component in components {
+            let component_type = component.component_type();
+            if component_type == last_type {
+                return Err(DumpError::InvalidArgument {
+                    name: "flowspec components",
+                    value: format!("duplicate component type {}", component_type),
+                })
+            }
+            last_type = component_type;
+
+            buffer.push(component_type);
+            match component {
+                FlowspecComponent::DestinationPrefix(p) | FlowspecComponent::SourcePrefix(p) => {
+                    write_prefix(&mut buffer, p, self.afi)?;
+                }
+                FlowspecComponent::TcpFlags(ops) | FlowspecComponent::Fragment(ops) => {
+                    write_bitmask(&mut buffer, ops)?;
+                }
+                FlowspecComponent::FlowLabel(_) if self.afi != Afi::Ipv6 => {
+                    return Err(DumpError::InvalidArgument {
+                        name: "flowspec components",
+                        value: "flow label for IPv4".to_string(),
+                    })
+                }
+                FlowspecComponent::IpProtocol(ops) | FlowspecComponent::Port(ops) |
+                FlowspecComponent::DestinationPort(ops) | FlowspecComponent::SourcePort(ops) |
+                FlowspecComponent::IcmpType(ops) | FlowspecComponent::IcmpCode(ops) |
+                FlowspecComponent::PacketLength(ops) | FlowspecComponent::Dscp(ops) |
+                FlowspecComponent::FlowLabel(ops) => {
+                    write_numeric(&mut buffer, ops)?;
+                }
+            }
+        }
+
+        let mut nlri: Vec<u8> = vec![];
+        match buffer.len() {
+            len if len < EXTENDED_LENGTH_THRESHOLD => nlri.push(len as u8),
+            len if len <= MAX_NLRI_LENGTH => nlri.extend_from_slice(&(0xF000 | len as u16).to_be_bytes()),
+            len => {
+                return Err(DumpError::LengthOverflow {
+                    record: "flowspec NLRI",
+                    field: "NLRI length",
+                    length: len,
+                    max: MAX_NLRI_LENGTH,
+                })
+            }
+        }
+        nlri.extend(buffer);
+        Ok(nlri)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::{AttrValueDump, MpNextHop, MpReachAttribute, MpUnreachAttribute, SAFI_FLOWSPEC, SAFI_FLOWSPEC_VPN};
+
+    use super::*;
+
+    #[test]
+    fn test_flowspec_ipv4() {
+        // example from https://datatracker.ietf.org/doc/html/rfc8955#appendix-A
+        let nlri = FlowspecNlri {
+            afi: Afi::Ipv4,
+            route_distinguisher: None,
+            components: vec![
+                FlowspecComponent::Port(vec![NumericOp::eq(137), NumericOp::ge(139).and(), NumericOp::le(141).and()]),
+                FlowspecComponent::DestinationPrefix(FlowspecPrefix::new(IpNetwork::from_str("10.0.1.0/24").unwrap())),
+                FlowspecComponent::SourcePrefix(FlowspecPrefix::new(IpNetwork::from_str("10.0.0.0/8").unwrap())),
+                FlowspecComponent::IpProtocol(vec![NumericOp::eq(6), NumericOp::eq(17)]),
+            ],
+        };
+        assert_eq!(nlri.nlri_bytes().unwrap(), [
+            0x14,
+            0x01, 0x18, 0x0a, 0x00, 0x01,
+            0x02, 0x08, 0x0a,
+            0x03, 0x01, 0x06, 0x81, 0x11,
+            0x04, 0x01, 0x89, 0x43, 0x8b, 0xc5, 0x8d,
+        ]);
+
+        let raw = MpReachAttribute { afi: 1, safi: SAFI_FLOWSPEC, next_hop: MpNextHop::None, nlri: vec![nlri.clone()] }
+            .to_raw_attribute()
+            .unwrap();
+        assert_eq!((raw.attr_type, raw.flag), (14, 0x80));
+        assert_eq!(raw.value[..5], [0, 1, 133, 0, 0]);
+        assert_eq!(raw.value[5..], nlri.nlri_bytes().unwrap()[..]);
+
+        let raw = MpUnreachAttribute { afi: 1, safi: SAFI_FLOWSPEC, nlri: vec![nlri.clone()] }
+            .to_raw_attribute()
+            .unwrap();
+        assert_eq!(raw.value[..3], [0, 1, 133]);
+
+        let mut duplicate = nlri.clone();
+        duplicate.components.push(FlowspecComponent::Port(vec![NumericOp::eq(1)]));
+        assert!(duplicate.nlri_bytes().is_err());
+    }
+
+    #[test]
+    fn test_flowspec_ipv6_and_vpn() {
+        // 2001:db8::/32 matching the bits from 8 on
+        let nlri = FlowspecNlri {
+            afi: Afi::Ipv6,
+            route_distinguisher: Some(RouteDistinguisher::As2 { asn: 65000, number: 1 }),
+            components: vec![
+                FlowspecComponent::DestinationPrefix(FlowspecPrefix {
+                    prefix: IpNetwork::from_str("2001:db8::/32").unwrap(),
+                    offset: 8,
+                }),
+                FlowspecComponent::FlowLabel(vec![NumericOp::eq(70000)]),
+                FlowspecComponent::TcpFlags(vec![BitmaskOp::all(0x02), BitmaskOp::any(0x10).negate().and()]),
+            ],
+        };
+        assert_eq!(nlri.nlri_bytes().unwrap(), [
+            25,
+            0, 0, 0xFD, 0xE8, 0, 0, 0, 1,
+            0x01, 32, 8, 0x01, 0x0d, 0xb8,
+            0x09, 0x01, 0x02, 0xc2, 0x10,
+            0x0d, 0xa1, 0, 1, 0x11, 0x70,
+        ]);
+        let raw = MpReachAttribute { afi: 2, safi: SAFI_FLOWSPEC_VPN, next_hop: MpNextHop::None, nlri: vec![nlri] }
+            .to_raw_attribute()
+            .unwrap();
+        assert_eq!(raw.value[..3], [0, 2, 134]);
+
+        let ipv4_with_offset = FlowspecNlri {
+            afi: Afi::Ipv4,
+            route_distinguisher: None,
+            components: vec![FlowspecComponent::DestinationPrefix(FlowspecPrefix {
+                prefix: IpNetwork::from_str("10.0.0.0/8").unwrap(),
+                offset: 1,
+            })],
+        };
+        assert!(ipv4_with_offset.nlri_bytes().is_err());
+    }
+
+    #[test]
+    fn test_flowspec_long_nlri() {
+        let nlri = FlowspecNlri {
+            afi: Afi::Ipv4,
+            route_distinguisher: None,
+            components: vec![FlowspecComponent::Port((0..150).map(NumericOp::eq).collect())],
+        };
+        let bytes = nlri.nlri_bytes().unwrap();
+        assert_eq!(bytes[..2], [0xF1, 0x2D]);
+        assert_eq!(bytes.len(), 303);
+    }
+}
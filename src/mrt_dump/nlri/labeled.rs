@@ -0,0 +1,66 @@
+use ipnetwork::IpNetwork;
+
+use crate::DumpError;
+use crate::mrt_dump::nlri::RouteDistinguisher;
+use crate::mrt_dump::utils::length_u8;
+
+pub(crate) const MAX_MPLS_LABEL: u32 = 0xFFFFF;
+const BOTTOM_OF_STACK: u32 = 0x01;
+
+/// Write a prefix preceded by its label stack and optional route distinguisher, with the length
+/// in bits covering all three.
+/// https://datatracker.ietf.org/doc/html/rfc8277#section-2
+pub(crate) fn write_labeled_prefix(
+    buffer: &mut Vec<u8>,
+    record: &'static str,
+    labels: &[u32],
+    route_distinguisher: Option<&RouteDistinguisher>,
+    prefix: &IpNetwork,
+) -> Result<(), DumpError> {
+    if labels.is_empty() {
+        return Err(DumpError::InvalidArgument {
+            name: "label stack",
+            value: format!("no labels for {}", prefix),
+        })
+    }
+    let mut label_bytes: Vec<u8> = Vec::with_capacity(labels.len() * 3);
+    for (i, label) in labels.iter().enumerate() {
+        if *label > MAX_MPLS_LABEL {
+            return Err(DumpError::ValueOutOfRange {
+                record,
+                field: "MPLS label",
+                value: *label as u64,
+                max: MAX_MPLS_LABEL as u64,
+            })
+        }
+        let mut field = label << 4;
+        if i == labels.len() - 1 {
+            field |= BOTTOM_OF_STACK;
+        }
+        label_bytes.extend_from_slice(&field.to_be_bytes()[1..]);
+    }
+    write_prefix_with_header(buffer, record, &label_bytes, route_distinguisher, prefix)
+}
+
+/// Write a prefix preceded by the given label field bytes and optional route distinguisher.
+pub(crate) fn write_prefix_with_header(
+    buffer: &mut Vec<u8>,
+    record: &'static str,
+    label_bytes: &[u8],
+    route_distinguisher: Option<&RouteDistinguisher>,
+    prefix: &IpNetwork,
+) -> Result<(), DumpError> {
+    let rd_len = route_distinguisher.map_or(0, |_| 8);
+    let bit_len = (label_bytes.len() + rd_len) * 8 + prefix.prefix() as usize;
+    buffer.push(length_u8(bit_len, record, "prefix length")?);
+    buffer.extend_from_slice(label_bytes);
+    if let Some(rd) = route_distinguisher {
+        buffer.extend_from_slice(&rd.to_bytes());
+    }
+    let n_bytes = (prefix.prefix() as usize).div_ceil(8);
+    match prefix.network() {
+        std::net::IpAddr::V4(ip) => buffer.extend_from_slice(&ip.octets()[..n_bytes]),
+        std::net::IpAddr::V6(ip) => buffer.extend_from_slice(&ip.octets()[..n_bytes]),
+    }
+    Ok(())
+}
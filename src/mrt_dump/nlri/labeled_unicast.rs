@@ -0,0 +1,97 @@
+use ipnetwork::IpNetwork;
+
+use crate::DumpError;
+use crate::mrt_dump::nlri::NlriDump;
+use crate::mrt_dump::nlri::labeled::{write_labeled_prefix, write_prefix_with_header};
+
+/// Label field of withdrawn routes, which receivers ignore.
+/// https://datatracker.ietf.org/doc/html/rfc8277#section-2.4
+pub const WITHDRAWAL_LABEL: [u8; 3] = [0x80, 0x00, 0x00];
+
+/// Labeled unicast route, for SAFI 4.
+/// https://datatracker.ietf.org/doc/html/rfc8277#section-2
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LabeledUnicastNlri {
+    /// MPLS labels, outermost first. The last label gets the bottom-of-stack bit.
+    pub labels: Vec<u32>,
+    /// Host bits of the prefix are not written.
+    pub prefix: IpNetwork,
+    pub path_id: u32,
+}
+
+impl LabeledUnicastNlri {
+    pub fn new(label: u32, prefix: IpNetwork) -> Self {
+        LabeledUnicastNlri { labels: vec![label], prefix, path_id: 0 }
+    }
+
+    pub fn with_path_id(mut self, path_id: u32) -> Self {
+        self.path_id = path_id;
+        self
+    }
+}
+
+impl NlriDump for LabeledUnicastNlri {
+    fn nlri_bytes(&self) -> Result<Vec<u8>, DumpError> {
+        let mut buffer: Vec<u8> = vec![];
+        write_labeled_prefix(&mut buffer, "labeled unicast NLRI", &self.labels, None, &self.prefix)?;
+        Ok(buffer)
+    }
+
+    /// Withdrawn routes carry the compatibility label 0x800000 instead of their label stack.
+    fn withdrawn_nlri_bytes(&self) -> Result<Vec<u8>, DumpError> {
+        let mut buffer: Vec<u8> = vec![];
+        write_prefix_with_header(&mut buffer, "labeled unicast NLRI", &WITHDRAWAL_LABEL, None, &self.prefix)?;
+        Ok(buffer)
+    }
+
+    fn path_id(&self) -> u32 {
+        self.path_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+    use std::str::FromStr;
+
+    use bgp_models::network::AsnLength;
+
+    use crate::{MpNextHop, MpReachAttribute, MpUnreachAttribute, SAFI_MPLS_LABEL};
+    use crate::mrt_dump::attributes::MrtAttrDump;
+
+    use super::*;
+
+    #[test]
+    fn test_labeled_unicast_nlri() {
+        let nlri = LabeledUnicastNlri {
+            labels: vec![16, 3],
+            prefix: IpNetwork::from_str("192.0.2.0/24").unwrap(),
+            path_id: 0,
+        };
+        assert_eq!(nlri.nlri_bytes().unwrap(), [
+            72,
+            0x00, 0x01, 0x00,
+            0x00, 0x00, 0x31,
+            192, 0, 2,
+        ]);
+        assert_eq!(nlri.withdrawn_nlri_bytes().unwrap(), [48, 0x80, 0, 0, 192, 0, 2]);
+
+        let reach = MpReachAttribute {
+            afi: 1,
+            safi: SAFI_MPLS_LABEL,
+            next_hop: MpNextHop::Address(IpAddr::from_str("192.0.2.254").unwrap()),
+            nlri: vec![LabeledUnicastNlri::new(1000, IpNetwork::from_str("10.0.0.0/8").unwrap()).with_path_id(7)],
+        };
+        assert_eq!(reach.to_bytes(true, AsnLength::Bits32, true, true, true).unwrap(), [
+            0x80, 14, 18,
+            0, 1, 4, 4, 192, 0, 2, 254, 0,
+            0, 0, 0, 7, 32, 0x00, 0x3e, 0x81, 10,
+        ]);
+
+        let unreach = MpUnreachAttribute { afi: 1, safi: SAFI_MPLS_LABEL, nlri: reach.nlri };
+        assert_eq!(unreach.to_bytes(true, AsnLength::Bits32, true, true, true).unwrap(), [
+            0x80, 15, 12,
+            0, 1, 4, 0, 0, 0, 7, 32, 0x80, 0, 0, 10,
+        ]);
+    }
+}
@@ -0,0 +1,112 @@
+//! NLRI of address families that [Nlri](bgp_models::prelude::Nlri) cannot express.
+//!
+//! NLRI implementing [NlriDump] are written with [MpReachAttribute] and [MpUnreachAttribute],
+//! which take the AFI and SAFI as raw values. They are added to messages and RIB entries as
+//! [PathAttribute](crate::PathAttribute)s with their NLRI as [MpNlri].
+
+use crate::DumpError;
+
+pub use evpn::{Esi, EvpnLabel, EvpnNlri, EvpnRoute};
+pub use flowspec::{BitmaskOp, FlowspecComponent, FlowspecNlri, FlowspecPrefix, NumericOp};
+pub use labeled_unicast::{LabeledUnicastNlri, WITHDRAWAL_LABEL};
+pub use mp_attributes::{MpNextHop, MpReachAttribute, MpUnreachAttribute};
+pub use route_distinguisher::RouteDistinguisher;
+pub use vpn::VpnNlri;
+
+mod evpn;
+mod flowspec;
+mod labeled;
+mod labeled_unicast;
+mod mp_attributes;
+mod route_distinguisher;
+mod vpn;
+
+// address family identifiers without [Afi](bgp_models::network::Afi) variant
+// https://www.iana.org/assignments/address-family-numbers/address-family-numbers.xhtml
+pub const AFI_L2VPN: u16 = 25;
+
+// subsequent address family identifiers
+// https://www.iana.org/assignments/safi-namespace/safi-namespace.xhtml
+pub const SAFI_MPLS_LABEL: u8 = 4;
+pub const SAFI_EVPN: u8 = 70;
+pub const SAFI_MPLS_VPN: u8 = 128;
+pub const SAFI_FLOWSPEC: u8 = 133;
+pub const SAFI_FLOWSPEC_VPN: u8 = 134;
+
+pub trait NlriDump {
+    /// Encode the NLRI as announced, without ADD-PATH path identifier.
+    fn nlri_bytes(&self) -> Result<Vec<u8>, DumpError>;
+
+    /// Encode the NLRI as withdrawn, which for most NLRI types is the same as announced.
+    fn withdrawn_nlri_bytes(&self) -> Result<Vec<u8>, DumpError> {
+        self.nlri_bytes()
+    }
+
+    /// The ADD-PATH path identifier, written on sessions with ADD-PATH.
+    fn path_id(&self) -> u32 {
+        0
+    }
+}
+
+/// NLRI of any of the address families in this module, to hold them in a
+/// [PathAttribute](crate::PathAttribute).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MpNlri {
+    Evpn(EvpnNlri),
+    Flowspec(FlowspecNlri),
+    LabeledUnicast(LabeledUnicastNlri),
+    Vpn(VpnNlri),
+}
+
+impl NlriDump for MpNlri {
+    fn nlri_bytes(&self) -> Result<Vec<u8>, DumpError> {
+        match self {
+            MpNlri::Evpn(n) => n.nlri_bytes(),
+            MpNlri::Flowspec(n) => n.nlri_bytes(),
+            MpNlri::LabeledUnicast(n) => n.nlri_bytes(),
+            MpNlri::Vpn(n) => n.nlri_bytes(),
+        }
+    }
+
+    fn withdrawn_nlri_bytes(&self) -> Result<Vec<u8>, DumpError> {
+        match self {
+            MpNlri::Evpn(n) => n.withdrawn_nlri_bytes(),
+            MpNlri::Flowspec(n) => n.withdrawn_nlri_bytes(),
+            MpNlri::LabeledUnicast(n) => n.withdrawn_nlri_bytes(),
+            MpNlri::Vpn(n) => n.withdrawn_nlri_bytes(),
+        }
+    }
+
+    fn path_id(&self) -> u32 {
+        match self {
+            MpNlri::Evpn(n) => n.path_id(),
+            MpNlri::Flowspec(n) => n.path_id(),
+            MpNlri::LabeledUnicast(n) => n.path_id(),
+            MpNlri::Vpn(n) => n.path_id(),
+        }
+    }
+}
+
+impl From<EvpnNlri> for MpNlri {
+    fn from(nlri: EvpnNlri) -> Self {
+        MpNlri::Evpn(nlri)
+    }
+}
+
+impl From<FlowspecNlri> for MpNlri {
+    fn from(nlri: FlowspecNlri) -> Self {
+        MpNlri::Flowspec(nlri)
+    }
+}
+
+impl From<LabeledUnicastNlri> for MpNlri {
+    fn from(nlri: LabeledUnicastNlri) -> Self {
+        MpNlri::LabeledUnicast(nlri)
+    }
+}
+
+impl From<VpnNlri> for MpNlri {
+    fn from(nlri: VpnNlri) -> Self {
+        MpNlri::Vpn(nlri)
+    }
+}
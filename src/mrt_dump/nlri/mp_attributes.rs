@@ -0,0 +1,214 @@
+use std::net::{IpAddr, Ipv6Addr};
+
+use bgp_models::network::AsnLength;
+
+use crate::DumpError;
+use crate::mrt_dump::attr_values::{AttrValueDump, FLAG_OPTIONAL};
+use crate::mrt_dump::attributes::{attribute_bytes, MrtAttrDump};
+use crate::mrt_dump::nlri::{NlriDump, RouteDistinguisher};
+use crate::mrt_dump::utils::{length_u8, WriteUtils};
+
+const ATTR_TYPE_MP_REACH_NLRI: u8 = 14;
+const ATTR_TYPE_MP_UNREACH_NLRI: u8 = 15;
+
+/// Next hop of an [MpReachAttribute].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MpNextHop {
+    /// No next hop, e.g. for flowspec.
+    None,
+    Address(IpAddr),
+    /// IPv6 global and link-local address.
+    Ipv6LinkLocal(Ipv6Addr, Ipv6Addr),
+    /// VPN next hop, an address preceded by a route distinguisher that is usually zero.
+    Vpn { route_distinguisher: RouteDistinguisher, address: IpAddr },
+    /// Any other next hop, written as is.
+    Raw(Vec<u8>),
+}
+
+impl MpNextHop {
+    fn to_bytes(&self) -> Result<Vec<u8>, DumpError> {
+        let mut buffer: Vec<u8> = vec![];
+        match self {
+            MpNextHop::None => {}
+            MpNextHop::Address(ip) => buffer.write_ip(ip)?,
+            MpNextHop::Ipv6LinkLocal(global, link_local) => {
+                buffer.extend_from_slice(&global.octets());
+                buffer.extend_from_slice(&link_local.octets());
+            }
+            MpNextHop::Vpn { route_distinguisher, address } => {
+                buffer.extend_from_slice(&route_distinguisher.to_bytes());
+                buffer.write_ip(address)?;
+            }
+            MpNextHop::Raw(v) => buffer.extend_from_slice(v),
+        }
+        Ok(buffer)
+    }
+}
+
+/// MP_REACH_NLRI with NLRI of any address family.
+/// https://datatracker.ietf.org/doc/html/rfc4760#section-3
+///
+/// ADD-PATH path identifiers are written in messages and RIB records with ADD-PATH, but not by
+/// [AttrValueDump::value_bytes].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MpReachAttribute<N> {
+    pub afi: u16,
+    pub safi: u8,
+    pub next_hop: MpNextHop,
+    pub nlri: Vec<N>,
+}
+
+/// MP_UNREACH_NLRI with NLRI of any address family.
+/// https://datatracker.ietf.org/doc/html/rfc4760#section-4
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MpUnreachAttribute<N> {
+    pub afi: u16,
+    pub safi: u8,
+    pub nlri: Vec<N>,
+}
+
+fn write_nlri_list<N: NlriDump>(buffer: &mut Vec<u8>, nlri: &[N], add_path: bool, withdrawn: bool) -> Result<(), DumpError> {
+    for n in nlri {
+        if add_path {
+            buffer.write_32b(n.path_id())?;
+        }
+        match withdrawn {
+            true => buffer.extend(n.withdrawn_nlri_bytes()?),
+            false => buffer.extend(n.nlri_bytes()?),
+        }
+    }
+    Ok(())
+}
+
+impl<N: NlriDump> MpReachAttribute<N> {
+    /// Encode the attribute value. RIB entries leave out the AFI, SAFI, reserved byte and NLRI.
+    /// https://datatracker.ietf.org/doc/html/rfc6396#section-4.3.4
+    fn write_value(&self, add_path: bool, write_afi: bool, write_safi: bool, write_prefixes: bool) -> Result<Vec<u8>, DumpError> {
+        let mut buffer: Vec<u8> = vec![];
+        if write_afi {
+            buffer.write_16b(self.afi)?;
+        }
+        if write_safi {
+            buffer.push(self.safi);
+        }
+        let next_hop = self.next_hop.to_bytes()?;
+        buffer.push(length_u8(next_hop.len(), "MP_REACH_NLRI", "next hop length")?);
+        buffer.extend(next_hop);
+        if write_prefixes {
+            // reserved
+            buffer.push(0);
+            write_nlri_list(&mut buffer, &self.nlri, add_path, false)?;
+        }
+        Ok(buffer)
+    }
+}
+
+impl<N: NlriDump> MpUnreachAttribute<N> {
+    fn write_value(&self, add_path: bool, write_afi: bool, write_safi: bool, write_prefixes: bool) -> Result<Vec<u8>, DumpError> {
+        let mut buffer: Vec<u8> = vec![];
+        if write_afi {
+            buffer.write_16b(self.afi)?;
+        }
+        if write_safi {
+            buffer.push(self.safi);
+        }
+        if write_prefixes {
+            write_nlri_list(&mut buffer, &self.nlri, add_path, true)?;
+        }
+        Ok(buffer)
+    }
+}
+
+impl<N: NlriDump> AttrValueDump for MpReachAttribute<N> {
+    fn type_code(&self) -> u8 {
+        ATTR_TYPE_MP_REACH_NLRI
+    }
+
+    fn flag(&self) -> u8 {
+        FLAG_OPTIONAL
+    }
+
+    fn value_bytes(&self) -> Result<Vec<u8>, DumpError> {
+        self.write_value(false, true, true, true)
+    }
+}
+
+impl<N: NlriDump> AttrValueDump for MpUnreachAttribute<N> {
+    fn type_code(&self) -> u8 {
+        ATTR_TYPE_MP_UNREACH_NLRI
+    }
+
+    fn flag(&self) -> u8 {
+        FLAG_OPTIONAL
+    }
+
+    fn value_bytes(&self) -> Result<Vec<u8>, DumpError> {
+        self.write_value(false, true, true, true)
+    }
+}
+
+impl<N: NlriDump> MrtAttrDump for MpReachAttribute<N> {
+    fn to_bytes(&self, add_path: bool, _asn_len: AsnLength, write_afi: bool, write_safi: bool, write_prefixes: bool) -> Result<Vec<u8>, DumpError> {
+        attribute_bytes(FLAG_OPTIONAL, ATTR_TYPE_MP_REACH_NLRI, &self.write_value(add_path, write_afi, write_safi, write_prefixes)?)
+    }
+}
+
+impl<N: NlriDump> MrtAttrDump for MpUnreachAttribute<N> {
+    fn to_bytes(&self, add_path: bool, _asn_len: AsnLength, write_afi: bool, write_safi: bool, write_prefixes: bool) -> Result<Vec<u8>, DumpError> {
+        attribute_bytes(FLAG_OPTIONAL, ATTR_TYPE_MP_UNREACH_NLRI, &self.write_value(add_path, write_afi, write_safi, write_prefixes)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bgp_models::prelude::*;
+    use ipnetwork::IpNetwork;
+
+    use crate::{ExtraAttributes, LabeledUnicastNlri, MrtDump, RawMrtRecord, SAFI_MPLS_LABEL};
+
+    use super::*;
+
+    #[test]
+    fn test_mp_attributes_in_rib_entries() {
+        let next_hop = Ipv6Addr::from_str("2001:db8::1").unwrap();
+        let prefix = IpNetwork::from_str("2001:db8:1::/48").unwrap();
+        let reach = MpReachAttribute {
+            afi: 2,
+            safi: SAFI_MPLS_LABEL,
+            next_hop: MpNextHop::Address(IpAddr::from(next_hop)),
+            nlri: vec![LabeledUnicastNlri::new(16, prefix)],
+        };
+        let unreach = MpUnreachAttribute { afi: 2, safi: SAFI_MPLS_LABEL, nlri: reach.nlri.clone() };
+
+        // RIB entries only hold the next hop of MP_REACH_NLRI
+        let mut expected = vec![0x80, 14, 17, 16];
+        expected.extend(next_hop.octets());
+        let reach = reach.to_attribute().unwrap();
+        assert_eq!(reach.to_bytes(false, AsnLength::Bits32, false, false, false).unwrap(), expected);
+        let unreach = unreach.to_attribute().unwrap();
+        assert_eq!(unreach.to_bytes(false, AsnLength::Bits32, false, false, false).unwrap(), [0x80, 15, 0]);
+        assert_eq!(unreach.to_bytes(false, AsnLength::Bits32, true, true, true).unwrap()[..6], [0x80, 15, 13, 0, 2, 4]);
+
+        let record = MrtRecord {
+            common_header: CommonHeader {
+                timestamp: 1,
+                microsecond_timestamp: None,
+                entry_type: EntryType::TABLE_DUMP_V2,
+                entry_subtype: TableDumpV2Type::RibIpv6Unicast as u16,
+                length: 0,
+            },
+            message: MrtMessage::TableDumpV2Message(TableDumpV2Message::RibAfiEntries(RibAfiEntries {
+                rib_type: TableDumpV2Type::RibIpv6Unicast,
+                sequence_number: 0,
+                prefix: NetworkPrefix::new(prefix, 0),
+                rib_entries: vec![RibEntry { peer_index: 0, originated_time: 1, attributes: vec![] }],
+            })),
+        };
+        let mut extra = ExtraAttributes::new();
+        extra.insert(0, 0, reach);
+        let bytes = RawMrtRecord::new(record, extra).to_bytes(0).unwrap();
+        assert!(bytes.ends_with(&expected));
+    }
+}
@@ -0,0 +1,51 @@
+use std::net::Ipv4Addr;
+
+/// Route distinguisher of VPN NLRI.
+/// https://datatracker.ietf.org/doc/html/rfc4364#section-4.2
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteDistinguisher {
+    /// type 0, 2-byte ASN and 4-byte assigned number
+    As2 { asn: u16, number: u32 },
+    /// type 1, IPv4 address and 2-byte assigned number
+    Ipv4 { address: Ipv4Addr, number: u16 },
+    /// type 2, 4-byte ASN and 2-byte assigned number
+    As4 { asn: u32, number: u16 },
+}
+
+impl RouteDistinguisher {
+    pub fn to_bytes(&self) -> [u8; 8] {
+        let mut bytes = [0u8; 8];
+        match self {
+            RouteDistinguisher::As2 { asn, number } => {
+                bytes[2..4].copy_from_slice(&asn.to_be_bytes());
+                bytes[4..].copy_from_slice(&number.to_be_bytes());
+            }
+            RouteDistinguisher::Ipv4 { address, number } => {
+                bytes[1] = 1;
+                bytes[2..6].copy_from_slice(&address.octets());
+                bytes[6..].copy_from_slice(&number.to_be_bytes());
+            }
+            RouteDistinguisher::As4 { asn, number } => {
+                bytes[1] = 2;
+                bytes[2..6].copy_from_slice(&asn.to_be_bytes());
+                bytes[6..].copy_from_slice(&number.to_be_bytes());
+            }
+        }
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route_distinguisher() {
+        assert_eq!(RouteDistinguisher::As2 { asn: 65000, number: 100 }.to_bytes(), [0, 0, 0xFD, 0xE8, 0, 0, 0, 100]);
+        assert_eq!(
+            RouteDistinguisher::Ipv4 { address: Ipv4Addr::from([192, 0, 2, 1]), number: 7 }.to_bytes(),
+            [0, 1, 192, 0, 2, 1, 0, 7]
+        );
+        assert_eq!(RouteDistinguisher::As4 { asn: 400000, number: 7 }.to_bytes(), [0, 2, 0, 6, 0x1A, 0x80, 0, 7]);
+    }
+}
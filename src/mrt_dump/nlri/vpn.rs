@@ -0,0 +1,102 @@
+use ipnetwork::IpNetwork;
+
+use crate::DumpError;
+use crate::mrt_dump::nlri::{NlriDump, RouteDistinguisher};
+use crate::mrt_dump::nlri::labeled::write_labeled_prefix;
+
+/// Labeled VPN-IPv4 or VPN-IPv6 route, for SAFI 128.
+/// https://datatracker.ietf.org/doc/html/rfc4364#section-4.3.4
+/// https://datatracker.ietf.org/doc/html/rfc4659#section-3.2
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VpnNlri {
+    /// MPLS labels, outermost first. The last label gets the bottom-of-stack bit.
+    pub labels: Vec<u32>,
+    pub route_distinguisher: RouteDistinguisher,
+    /// Host bits of the prefix are not written.
+    pub prefix: IpNetwork,
+    pub path_id: u32,
+}
+
+impl VpnNlri {
+    pub fn new(label: u32, route_distinguisher: RouteDistinguisher, prefix: IpNetwork) -> Self {
+        VpnNlri { labels: vec![label], route_distinguisher, prefix, path_id: 0 }
+    }
+}
+
+impl NlriDump for VpnNlri {
+    fn nlri_bytes(&self) -> Result<Vec<u8>, DumpError> {
+        let mut buffer: Vec<u8> = vec![];
+        write_labeled_prefix(&mut buffer, "VPN NLRI", &self.labels, Some(&self.route_distinguisher), &self.prefix)?;
+        Ok(buffer)
+    }
+
+    fn path_id(&self) -> u32 {
+        self.path_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::str::FromStr;
+
+    use bgp_models::network::AsnLength;
+
+    use crate::{AttrValueDump, MpNextHop, MpReachAttribute, MpUnreachAttribute, SAFI_MPLS_VPN};
+    use crate::mrt_dump::attributes::MrtAttrDump;
+
+    use super::*;
+
+    #[test]
+    fn test_vpn_nlri() {
+        let rd = RouteDistinguisher::Ipv4 { address: Ipv4Addr::new(192, 0, 2, 1), number: 7 };
+        let nlri = VpnNlri::new(16, rd, IpNetwork::from_str("10.1.0.0/16").unwrap());
+        // 24 label bits, 64 RD bits and 16 prefix bits
+        assert_eq!(nlri.nlri_bytes().unwrap(), [
+            104,
+            0x00, 0x01, 0x01,
+            0, 1, 192, 0, 2, 1, 0, 7,
+            10, 1,
+        ]);
+
+        let stacked = VpnNlri {
+            labels: vec![100, 200],
+            route_distinguisher: RouteDistinguisher::As2 { asn: 65000, number: 1 },
+            prefix: IpNetwork::from_str("2001:db8::/32").unwrap(),
+            path_id: 3,
+        };
+        let reach = MpReachAttribute {
+            afi: 2,
+            safi: SAFI_MPLS_VPN,
+            next_hop: MpNextHop::Vpn {
+                route_distinguisher: RouteDistinguisher::As2 { asn: 0, number: 0 },
+                address: IpAddr::from_str("2001:db8::1").unwrap(),
+            },
+            nlri: vec![stacked.clone()],
+        };
+        let value = reach.to_bytes(true, AsnLength::Bits32, true, true, true).unwrap()[3..].to_vec();
+        assert_eq!(value[..4], [0, 2, 128, 24]);
+        assert_eq!(value[4..12], [0; 8]);
+        assert_eq!(value[28], 0);
+        assert_eq!(value[29..], [
+            0, 0, 0, 3,
+            48 + 64 + 32,
+            0x00, 0x06, 0x40, 0x00, 0x0c, 0x81,
+            0, 0, 0xFD, 0xE8, 0, 0, 0, 1,
+            0x20, 0x01, 0x0d, 0xb8,
+        ]);
+
+        let unreach = MpUnreachAttribute { afi: 2, safi: SAFI_MPLS_VPN, nlri: vec![stacked] };
+        assert_eq!(unreach.value_bytes().unwrap()[3], 144);
+
+        let invalid = VpnNlri::new(0x100000, rd, IpNetwork::from_str("10.1.0.0/16").unwrap());
+        assert!(matches!(invalid.nlri_bytes(), Err(DumpError::ValueOutOfRange { .. })));
+        let too_long = VpnNlri {
+            labels: vec![1; 8],
+            route_distinguisher: rd,
+            prefix: IpNetwork::from_str("2001:db8::/64").unwrap(),
+            path_id: 0,
+        };
+        assert!(matches!(too_long.nlri_bytes(), Err(DumpError::LengthOverflow { .. })));
+    }
+}
@@ -0,0 +1,241 @@
+use bgp_models::bgp::Attribute;
+use bgp_models::network::AsnLength;
+
+use crate::DumpError;
+use crate::mrt_dump::attr_values::{
+    Aigp, AttrSet, AttrValueDump, BgpsecPath, OnlyToCustomer, PmsiTunnel, PrefixSid, TunnelEncapsulation,
+};
+use crate::mrt_dump::attributes::{attribute_bytes, MrtAttrDump};
+use crate::mrt_dump::nlri::{MpNlri, MpReachAttribute, MpUnreachAttribute};
+use crate::mrt_dump::RawAttribute;
+
+/// A path attribute as written to a message or RIB entry: one of the [Attribute]s records hold,
+/// an attribute that [AttributeValue](bgp_models::prelude::AttributeValue) does not model, or any
+/// other attribute as [RawAttribute].
+///
+/// Attributes read from records are [PathAttribute::Attribute] or [PathAttribute::Raw].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathAttribute {
+    Attribute(Attribute),
+    Aigp(Aigp),
+    AttrSet(AttrSet),
+    BgpsecPath(BgpsecPath),
+    MpReach(MpReachAttribute<MpNlri>),
+    MpUnreach(MpUnreachAttribute<MpNlri>),
+    OnlyToCustomer(OnlyToCustomer),
+    PmsiTunnel(PmsiTunnel),
+    PrefixSid(PrefixSid),
+    TunnelEncapsulation(TunnelEncapsulation),
+    Raw(RawAttribute),
+}
+
+impl PathAttribute {
+    /// The type code the attribute is written with.
+    pub fn type_code(&self) -> u8 {
+        match self {
+            PathAttribute::Attribute(a) => a.attr_type as u8,
+            PathAttribute::Raw(a) => a.attr_type,
+            PathAttribute::Aigp(v) => v.type_code(),
+            PathAttribute::AttrSet(v) => v.type_code(),
+            PathAttribute::BgpsecPath(v) => v.type_code(),
+            PathAttribute::MpReach(v) => v.type_code(),
+            PathAttribute::MpUnreach(v) => v.type_code(),
+            PathAttribute::OnlyToCustomer(v) => v.type_code(),
+            PathAttribute::PmsiTunnel(v) => v.type_code(),
+            PathAttribute::PrefixSid(v) => v.type_code(),
+            PathAttribute::TunnelEncapsulation(v) => v.type_code(),
+        }
+    }
+
+    pub fn as_attribute(&self) -> Option<&Attribute> {
+        match self {
+            PathAttribute::Attribute(a) => Some(a),
+            _ => None,
+        }
+    }
+}
+
+impl From<Attribute> for PathAttribute {
+    fn from(attr: Attribute) -> Self {
+        PathAttribute::Attribute(attr)
+    }
+}
+
+impl From<RawAttribute> for PathAttribute {
+    fn from(attr: RawAttribute) -> Self {
+        PathAttribute::Raw(attr)
+    }
+}
+
+impl From<Aigp> for PathAttribute {
+    fn from(value: Aigp) -> Self {
+        PathAttribute::Aigp(value)
+    }
+}
+
+impl From<AttrSet> for PathAttribute {
+    fn from(value: AttrSet) -> Self {
+        PathAttribute::AttrSet(value)
+    }
+}
+
+impl From<BgpsecPath> for PathAttribute {
+    fn from(value: BgpsecPath) -> Self {
+        PathAttribute::BgpsecPath(value)
+    }
+}
+
+impl<N: Into<MpNlri>> From<MpReachAttribute<N>> for PathAttribute {
+    fn from(value: MpReachAttribute<N>) -> Self {
+        PathAttribute::MpReach(MpReachAttribute {
+            afi: value.afi,
+            safi: value.safi,
+            next_hop: value.next_hop,
+            nlri: value.nlri.into_iter().map(Into::into).collect(),
+        })
+    }
+}
+
+impl<N: Into<MpNlri>> From<MpUnreachAttribute<N>> for PathAttribute {
+    fn from(value: MpUnreachAttribute<N>) -> Self {
+        PathAttribute::MpUnreach(MpUnreachAttribute {
+            afi: value.afi,
+            safi: value.safi,
+            nlri: value.nlri.into_iter().map(Into::into).collect(),
+        })
+    }
+}
+
+impl From<OnlyToCustomer> for PathAttribute {
+    fn from(value: OnlyToCustomer) -> Self {
+        PathAttribute::OnlyToCustomer(value)
+    }
+}
+
+impl From<PmsiTunnel> for PathAttribute {
+    fn from(value: PmsiTunnel) -> Self {
+        PathAttribute::PmsiTunnel(value)
+    }
+}
+
+impl From<PrefixSid> for PathAttribute {
+    fn from(value: PrefixSid) -> Self {
+        PathAttribute::PrefixSid(value)
+    }
+}
+
+impl From<TunnelEncapsulation> for PathAttribute {
+    fn from(value: TunnelEncapsulation) -> Self {
+        PathAttribute::TunnelEncapsulation(value)
+    }
+}
+
+impl MrtAttrDump for PathAttribute {
+    fn to_bytes(&self, add_path: bool, asn_len: AsnLength, write_afi: bool, write_safi: bool, write_prefixes: bool) -> Result<Vec<u8>, DumpError> {
+        match self {
+            PathAttribute::Attribute(a) => a.to_bytes(add_path, asn_len, write_afi, write_safi, write_prefixes),
+            PathAttribute::Raw(a) => attribute_bytes(a.flag, a.attr_type, &a.value),
+            PathAttribute::Aigp(v) => value_attribute_bytes(v),
+            PathAttribute::AttrSet(v) => value_attribute_bytes(v),
+            PathAttribute::BgpsecPath(v) => value_attribute_bytes(v),
+            PathAttribute::MpReach(v) => v.to_bytes(add_path, asn_len, write_afi, write_safi, write_prefixes),
+            PathAttribute::MpUnreach(v) => v.to_bytes(add_path, asn_len, write_afi, write_safi, write_prefixes),
+            PathAttribute::OnlyToCustomer(v) => value_attribute_bytes(v),
+            PathAttribute::PmsiTunnel(v) => value_attribute_bytes(v),
+            PathAttribute::PrefixSid(v) => value_attribute_bytes(v),
+            PathAttribute::TunnelEncapsulation(v) => value_attribute_bytes(v),
+        }
+    }
+}
+
+fn value_attribute_bytes<T: AttrValueDump>(value: &T) -> Result<Vec<u8>, DumpError> {
+    attribute_bytes(value.flag(), value.type_code(), &value.value_bytes()?)
+}
+
+/// Path attributes of a record that are kept next to the record instead of in it.
+///
+/// Records hold their path attributes as [Attribute]s, which cannot express attributes such as
+/// [RawAttribute]s. These are kept here per attribute list of the record: list 0 is the attribute
+/// list of a BGP UPDATE message, list `i` the one of the `i`-th entry of a RIB record.
+///
+/// Each extra attribute has a position in its list and is written right before the record
+/// attribute at that position, or after all record attributes if the position is beyond them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExtraAttributes {
+    lists: Vec<Vec<(usize, PathAttribute)>>,
+}
+
+impl ExtraAttributes {
+    pub fn new() -> Self {
+        ExtraAttributes { lists: vec![] }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lists.iter().all(|l| l.is_empty())
+    }
+
+    /// The extra attributes of attribute list `list` with their positions, ordered by position.
+    pub fn list(&self, list: usize) -> &[(usize, PathAttribute)] {
+        match self.lists.get(list) {
+            Some(l) => l,
+            None => &[],
+        }
+    }
+
+    /// Add an attribute to attribute list `list`, to be written before the record attribute at
+    /// `position`. Attributes at the same position are written in the order they are added.
+    pub fn insert(&mut self, list: usize, position: usize, attr: PathAttribute) {
+        if self.lists.len() <= list {
+            self.lists.resize(list + 1, vec![]);
+        }
+        let extras = &mut self.lists[list];
+        let index = extras.iter().position(|(p, _)| *p > position).unwrap_or(extras.len());
+        extras.insert(index, (position, attr));
+    }
+
+    /// The complete attribute list `list`, the record's `attributes` with the extra attributes in
+    /// their positions.
+    pub fn merged(&self, list: usize, attributes: &[Attribute]) -> Vec<PathAttribute> {
+        let mut extras = self.list(list).iter().peekable();
+        let mut merged = Vec::with_capacity(attributes.len() + self.list(list).len());
+        for (i, attr) in attributes.iter().enumerate() {
+            while let Some((_, extra)) = extras.next_if(|(p, _)| *p <= i) {
+                merged.push(extra.clone());
+            }
+            merged.push(PathAttribute::Attribute(attr.clone()));
+        }
+        merged.extend(extras.map(|(_, extra)| extra.clone()));
+        merged
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bgp_models::bgp::{AttrType, Origin};
+    use bgp_models::prelude::AttributeValue;
+
+    use super::*;
+
+    #[test]
+    fn test_merged_attributes() {
+        let origin = Attribute { attr_type: AttrType::ORIGIN, value: AttributeValue::Origin(Origin::IGP), flag: 0x40 };
+        let med = Attribute { attr_type: AttrType::MULTI_EXIT_DISCRIMINATOR, value: AttributeValue::MultiExitDiscriminator(1), flag: 0x80 };
+        let raw = |attr_type: u8| PathAttribute::Raw(RawAttribute::new(attr_type, 0xC0, vec![attr_type]));
+
+        let mut extra = ExtraAttributes::new();
+        assert!(extra.is_empty());
+        extra.insert(1, 5, raw(40));
+        extra.insert(1, 1, raw(34));
+        extra.insert(1, 0, raw(35));
+        // duplicates keep the order they are added in
+        extra.insert(1, 1, raw(34));
+        assert!(!extra.is_empty());
+        assert!(extra.list(0).is_empty());
+
+        assert_eq!(extra.merged(0, std::slice::from_ref(&origin)), vec![PathAttribute::from(origin.clone())]);
+        assert_eq!(extra.merged(1, &[origin.clone(), med.clone()]), vec![
+            raw(35), origin.into(), raw(34), raw(34), med.into(), raw(40),
+        ]);
+        assert_eq!(extra.merged(1, &[]), vec![raw(35), raw(34), raw(34), raw(40)]);
+    }
+}
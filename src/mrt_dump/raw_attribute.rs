@@ -0,0 +1,142 @@
+use bgp_models::bgp::{Attribute, AttributeFlagsBit};
+
+/// A path attribute carried as opaque type code, flags and value bytes.
+///
+/// Attributes this crate or the parser do not model are kept in this form and written back
+/// exactly as they were, flags included. Records cannot hold them, they are carried as
+/// [PathAttribute](crate::PathAttribute)s in the [ExtraAttributes](crate::ExtraAttributes) of a
+/// record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawAttribute {
+    pub attr_type: u8,
+    pub flag: u8,
+    pub value: Vec<u8>,
+}
+
+impl RawAttribute {
+    pub fn new(attr_type: u8, flag: u8, value: Vec<u8>) -> Self {
+        RawAttribute { attr_type, flag, value }
+    }
+
+    pub fn is_partial(&self) -> bool {
+        self.flag & AttributeFlagsBit::PartialBit as u8 != 0
+    }
+}
+
+/// Split an encoded attribute list into raw attributes, or `None` if it is malformed.
+pub(crate) fn split_attributes(mut bytes: &[u8]) -> Option<Vec<RawAttribute>> {
+    let mut attributes = vec![];
+    while !bytes.is_empty() {
+        if bytes.len() < 3 {
+            return None
+        }
+        let (flag, attr_type) = (bytes[0], bytes[1]);
+        let (length, header_len) = match flag & AttributeFlagsBit::ExtendedLengthBit as u8 {
+            0 => (bytes[2] as usize, 3),
+            _ => (u16::from_be_bytes([bytes[2], *bytes.get(3)?]) as usize, 4),
+        };
+        let value = bytes.get(header_len..header_len + length)?;
+        attributes.push(RawAttribute::new(attr_type, flag, value.to_vec()));
+        bytes = &bytes[header_len + length..];
+    }
+    Some(attributes)
+}
+
+/// Find the raw attributes the parser dropped from an attribute list.
+///
+/// `raw` is the complete attribute list of the original bytes, and `parsed` what the parser made
+/// of it. The parser keeps the order of the attributes it does not drop, so both lists are walked
+/// in step: a raw attribute with the type code of the next parsed attribute is that attribute,
+/// all others were dropped. Returns the dropped attributes with the position of the parsed
+/// attribute they came before, or `None` if the lists do not line up.
+pub(crate) fn dropped_attributes(parsed: &[Attribute], raw: Vec<RawAttribute>) -> Option<Vec<(usize, RawAttribute)>> {
+    let mut next = 0;
+    let mut dropped = vec![];
+    for raw_attr in raw {
+        match parsed.get(next) {
+            Some(a) if a.attr_type as u8 == raw_attr.attr_type => next += 1,
+            _ => dropped.push((next, raw_attr)),
+        }
+    }
+    match next == parsed.len() {
+        true => Some(dropped),
+        false => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bgp_models::bgp::{AttrType, Origin};
+    use bgp_models::network::AsnLength;
+    use bgp_models::prelude::AttributeValue;
+
+    use crate::mrt_dump::attributes::MrtAttrDump;
+    use crate::PathAttribute;
+
+    use super::*;
+
+    #[test]
+    fn test_raw_attribute_bytes() {
+        // optional transitive partial AIGP attribute, extended length bit set on a short value
+        let raw = RawAttribute::new(26, 0xF0, vec![1, 0, 11, 0, 0, 0, 0, 0, 0, 0, 100]);
+        assert!(raw.is_partial());
+        let attr = PathAttribute::from(raw.clone());
+        assert_eq!(attr.type_code(), 26);
+
+        let bytes = attr.to_bytes(false, AsnLength::Bits32, true, true, true).unwrap();
+        assert_eq!(bytes[..4], [0xF0, 26, 0, 11]);
+        assert_eq!(bytes[4..], raw.value[..]);
+        assert_eq!(split_attributes(&bytes), Some(vec![raw]));
+
+        // extended length is added for long values
+        let raw = RawAttribute::new(40, 0xC0, vec![0; 300]);
+        let bytes = PathAttribute::from(raw).to_bytes(false, AsnLength::Bits32, true, true, true).unwrap();
+        assert_eq!(bytes[..4], [0xD0, 40, 1, 44]);
+        assert_eq!(split_attributes(&bytes), Some(vec![RawAttribute::new(40, 0xD0, vec![0; 300])]));
+
+        assert_eq!(split_attributes(&bytes[..bytes.len() - 1]), None);
+    }
+
+    #[test]
+    fn test_reserved_development_attribute() {
+        // written with type code 0 and the value as is, whatever the value looks like
+        let attr = Attribute {
+            attr_type: AttrType::RESERVED,
+            value: AttributeValue::Development(b"\0raw-attribute\0\x1a".to_vec()),
+            flag: 0xC0,
+        };
+        let bytes = attr.to_bytes(false, AsnLength::Bits32, true, true, true).unwrap();
+        assert_eq!(bytes[..3], [0xC0, 0, 16]);
+        assert_eq!(split_attributes(&bytes), Some(vec![RawAttribute::new(0, 0xC0, b"\0raw-attribute\0\x1a".to_vec())]));
+
+        let attr = Attribute { value: AttributeValue::Development(vec![]), ..attr };
+        assert_eq!(attr.to_bytes(false, AsnLength::Bits32, true, true, true).unwrap(), [0xC0, 0, 0]);
+    }
+
+    #[test]
+    fn test_dropped_attributes() {
+        let origin = Attribute {
+            attr_type: AttrType::ORIGIN,
+            value: AttributeValue::Origin(Origin::IGP),
+            flag: 0x40,
+        };
+        let raw = vec![
+            RawAttribute::new(40, 0xC0, vec![1, 2]),
+            RawAttribute::new(1, 0x40, vec![0]),
+            RawAttribute::new(26, 0x80, vec![3]),
+            RawAttribute::new(26, 0x80, vec![4]),
+            RawAttribute::new(1, 0x40, vec![0]),
+        ];
+        // duplicates stay in place, the second ORIGIN was dropped by the parser
+        assert_eq!(dropped_attributes(std::slice::from_ref(&origin), raw.clone()), Some(vec![
+            (0, raw[0].clone()),
+            (1, raw[2].clone()),
+            (1, raw[3].clone()),
+            (1, raw[4].clone()),
+        ]));
+        assert_eq!(dropped_attributes(&[origin.clone(), origin.clone()], raw.clone()).unwrap().len(), 3);
+
+        // parsed attributes without raw counterpart
+        assert_eq!(dropped_attributes(&[origin.clone(), origin.clone(), origin], raw), None);
+    }
+}
@@ -1,25 +1,35 @@
 use std::io::Write;
 
 use bgp_models::mrt::{PeerIndexTable, RibAfiEntries, RibGenericEntries, TableDumpMessage, TableDumpV2Message, TableDumpV2Type};
+use bgp_models::network::AsnLength;
 use byteorder::WriteBytesExt;
 use num_traits::FromPrimitive;
 
-use crate::{DumpError, MrtDump};
+use crate::{DumpError, ExtraAttributes, MrtDump, MrtDumpWithAttributes};
 use crate::mrt_dump::attributes::MrtAttrDump;
-use crate::mrt_dump::utils::WriteUtils;
+use crate::mrt_dump::utils::{length_u16, WriteUtils};
+
+/// peer type bit for 4-byte peer ASNs in the peer index table
+const PEER_TYPE_AS4: u8 = 0b10;
 
 impl MrtDump for TableDumpMessage{
     fn to_bytes(&self, _subtype: u16) -> Result<Vec<u8>, DumpError> {
-        todo!()
+        Err(DumpError::UnsupportedMessage("TABLE_DUMP"))
     }
 }
 
 impl MrtDump for TableDumpV2Message{
     fn to_bytes(&self, subtype: u16) -> Result<Vec<u8>, DumpError> {
+        self.to_bytes_with_attributes(subtype, &ExtraAttributes::new())
+    }
+}
+
+impl MrtDumpWithAttributes for TableDumpV2Message{
+    fn to_bytes_with_attributes(&self, subtype: u16, extra: &ExtraAttributes) -> Result<Vec<u8>, DumpError> {
         Ok(
             match self {
                 TableDumpV2Message::PeerIndexTable(m) => {m.to_bytes(subtype)?}
-                TableDumpV2Message::RibAfiEntries(m) => {m.to_bytes(subtype)?}
+                TableDumpV2Message::RibAfiEntries(m) => {m.to_bytes_with_attributes(subtype, extra)?}
                 TableDumpV2Message::RibGenericEntries(m) => {m.to_bytes(subtype)?}
             }
         )
@@ -33,20 +43,36 @@ impl MrtDump for PeerIndexTable {
         // collector id as ip
         buffer.write_ip(&self.collector_bgp_id.into())?;
 
-        // view name length
-        buffer.write_16b(self.view_name_length)?;
-
-        buffer.write_all(&vec![0; self.view_name_length as usize])?;
+        // view name: the parser only keeps the length of the view name, write zeros in that case
+        if self.view_name.is_empty() {
+            buffer.write_16b(self.view_name_length)?;
+            buffer.write_all(&vec![0; self.view_name_length as usize])?;
+        } else {
+            buffer.write_16b(length_u16(self.view_name.len(), "PEER_INDEX_TABLE", "view name length")?)?;
+            buffer.write_all(self.view_name.as_bytes())?;
+        }
 
         // peer count
         buffer.write_16b(self.peer_count)?;
 
-        for i in 0..self.peers_map.len() {
-            let peer = self.peers_map.get(&(i as u32)).unwrap();
+        // the peers must be exactly the peer indices from 0 to peer count - 1
+        if self.peers_map.len() > self.peer_count as usize {
+            let peer_index = self.peers_map.keys().copied().filter(|i| *i >= self.peer_count as u32).min().unwrap_or_default();
+            return Err(DumpError::InconsistentPeerTable { peer_count: self.peer_count, peer_index })
+        }
+        for i in 0..self.peer_count as u32 {
+            let peer = self.peers_map.get(&i)
+                .ok_or(DumpError::InconsistentPeerTable { peer_count: self.peer_count, peer_index: i })?;
             buffer.write_u8(peer.peer_type)?;
             buffer.write_ip(&peer.peer_bgp_id.into())?;
             buffer.write_ip(&peer.peer_address)?;
-            buffer.write_asn(&peer.peer_asn)?;
+            // https://datatracker.ietf.org/doc/html/rfc6396#section-4.3.1
+            // bit 1 of the peer type is set for 4-byte peer ASNs
+            let asn_len = match peer.peer_type & PEER_TYPE_AS4 {
+                0 => AsnLength::Bits16,
+                _ => AsnLength::Bits32,
+            };
+            buffer.write_asn(&peer.peer_asn, asn_len)?;
         }
         Ok(buffer)
     }
@@ -54,23 +80,32 @@ impl MrtDump for PeerIndexTable {
 
 impl MrtDump for RibAfiEntries {
     fn to_bytes(&self, subtype: u16) -> Result<Vec<u8>, DumpError> {
-        let rib_type: TableDumpV2Type = TableDumpV2Type::from_u16(subtype).unwrap();
-        let add_path = match rib_type {
+        self.to_bytes_with_attributes(subtype, &ExtraAttributes::new())
+    }
+}
+
+impl MrtDumpWithAttributes for RibAfiEntries {
+    /// The extra attributes of the `i`-th RIB entry are in list `i` of `extra`.
+    fn to_bytes_with_attributes(&self, subtype: u16, extra: &ExtraAttributes) -> Result<Vec<u8>, DumpError> {
+        let rib_type = TableDumpV2Type::from_u16(subtype).ok_or_else(|| DumpError::UnsupportedType {
+            record: "RIB_AFI",
+            field: "subtype",
+            value: subtype.to_string(),
+        })?;
+        let add_path = matches!(
+            rib_type,
             TableDumpV2Type::RibIpv4UnicastAddPath | TableDumpV2Type::RibIpv4MulticastAddPath |
-            TableDumpV2Type::RibIpv6UnicastAddPath | TableDumpV2Type::RibIpv6MulticastAddPath => {
-                true
-            }
-            _ => {false}
-        };
+            TableDumpV2Type::RibIpv6UnicastAddPath | TableDumpV2Type::RibIpv6MulticastAddPath
+        );
 
         let mut buffer: Vec<u8> = vec![];
         buffer.write_32b(self.sequence_number)?;
 
         buffer.write_nlri(&self.prefix, add_path)?;
 
-        buffer.write_16b(self.rib_entries.len() as u16)?;
+        buffer.write_16b(length_u16(self.rib_entries.len(), "RIB_AFI", "entry count")?)?;
 
-        for entry in &self.rib_entries {
+        for (i, entry) in self.rib_entries.iter().enumerate() {
             buffer.write_16b(entry.peer_index)?;
             buffer.write_32b(entry.originated_time)?;
             if add_path {
@@ -79,11 +114,13 @@ impl MrtDump for RibAfiEntries {
             }
 
             let mut attr_buffer = vec![];
-            for attribute in &entry.attributes {
-                attr_buffer.extend(attribute.to_bytes(add_path, false, false, false)?);
+            for attribute in &extra.merged(i, &entry.attributes) {
+                // RIB entries always use 4-byte ASNs
+                // https://datatracker.ietf.org/doc/html/rfc6396#section-4.3.4
+                attr_buffer.extend(attribute.to_bytes(add_path, AsnLength::Bits32, false, false, false)?);
             }
 
-            buffer.write_16b(attr_buffer.len() as u16)?;
+            buffer.write_16b(length_u16(attr_buffer.len(), "RIB_AFI", "attribute length")?)?;
             buffer.write_all(&attr_buffer)?;
 
         }
@@ -93,6 +130,7 @@ impl MrtDump for RibAfiEntries {
 
 impl MrtDump for RibGenericEntries {
     fn to_bytes(&self, _subtype: u16) -> Result<Vec<u8>, DumpError> {
-        todo!("parser has not supported yet, so haven't us.")
+        // the parser does not support RIB_GENERIC yet
+        Err(DumpError::UnsupportedMessage("RIB_GENERIC"))
     }
 }
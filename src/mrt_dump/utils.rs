@@ -1,7 +1,11 @@
+use std::convert::TryFrom;
+use std::fmt::Debug;
 use std::net::{IpAddr, Ipv4Addr};
 
 use bgp_models::network::{Asn, AsnLength, NetworkPrefix};
 use byteorder::{BigEndian, WriteBytesExt};
+use ipnetwork::IpNetwork;
+use num_traits::ToPrimitive;
 
 use crate::DumpError;
 
@@ -11,6 +15,50 @@ pub fn ipv4_to_u32(ip: &Ipv4Addr) -> u32 {
     ((o[0] as u32) <<24)+((o[1] as u32) <<16)+((o[2] as u32) <<8)+ o[3] as u32
 }
 
+/// Get the type code of an enum value for a one-octet field, `record` and `field` name the field
+/// in errors.
+pub(crate) fn type_code_u8<T: ToPrimitive + Debug>(value: &T, record: &'static str, field: &'static str) -> Result<u8, DumpError> {
+    value.to_u8().ok_or_else(|| DumpError::UnsupportedType { record, field, value: format!("{:?}", value) })
+}
+
+/// Get the type code of an enum value for a two-octet field, `record` and `field` name the field
+/// in errors.
+pub(crate) fn type_code_u16<T: ToPrimitive + Debug>(value: &T, record: &'static str, field: &'static str) -> Result<u16, DumpError> {
+    value.to_u16().ok_or_else(|| DumpError::UnsupportedType { record, field, value: format!("{:?}", value) })
+}
+
+/// Check that `length` fits in a one-octet length field.
+pub(crate) fn length_u8(length: usize, record: &'static str, field: &'static str) -> Result<u8, DumpError> {
+    u8::try_from(length).map_err(|_| DumpError::LengthOverflow { record, field, length, max: u8::MAX as usize })
+}
+
+/// Check that `length` fits in a two-octet length field.
+pub(crate) fn length_u16(length: usize, record: &'static str, field: &'static str) -> Result<u16, DumpError> {
+    u16::try_from(length).map_err(|_| DumpError::LengthOverflow { record, field, length, max: u16::MAX as usize })
+}
+
+/// Check that `length` fits in a four-octet length field.
+pub(crate) fn length_u32(length: usize, record: &'static str, field: &'static str) -> Result<u32, DumpError> {
+    u32::try_from(length).map_err(|_| DumpError::LengthOverflow { record, field, length, max: u32::MAX as usize })
+}
+
+/// Clear the bits of a prefix beyond its prefix length, or return [DumpError::HostBitsSet] in
+/// `strict` mode.
+pub(crate) fn normalize_prefix(nlri: &NetworkPrefix, strict: bool) -> Result<NetworkPrefix, DumpError> {
+    let network = nlri.prefix.network();
+    if network == nlri.prefix.ip() {
+        return Ok(*nlri)
+    }
+    if strict {
+        return Err(DumpError::HostBitsSet(*nlri))
+    }
+    // the prefix length is already valid for the address family
+    match IpNetwork::new(network, nlri.prefix.prefix()) {
+        Ok(prefix) => Ok(NetworkPrefix::new(prefix, nlri.path_id)),
+        Err(_) => Err(DumpError::HostBitsSet(*nlri)),
+    }
+}
+
 pub trait WriteUtils: std::io::Write {
 
     fn write_16b(&mut self, v: u16) -> Result<(), DumpError>{
@@ -35,36 +83,47 @@ pub trait WriteUtils: std::io::Write {
         Ok(())
     }
 
-    fn write_asn(&mut self, asn: &Asn) -> Result<(), DumpError> {
-        match asn.len {
+    /// Write an ASN with the width of the encoding context `asn_len`, regardless of [Asn::len].
+    fn write_asn(&mut self, asn: &Asn, asn_len: AsnLength) -> Result<(), DumpError> {
+        match asn_len {
             AsnLength::Bits16 => {
+                if asn.asn > u16::MAX as u32 {
+                    return Err(DumpError::ValueOutOfRange {
+                        record: "ASN",
+                        field: "2-byte ASN",
+                        value: asn.asn as u64,
+                        max: u16::MAX as u64,
+                    })
+                }
                 self.write_16b(asn.asn as u16)?;
             }
             AsnLength::Bits32 => {
-                self.write_32b(asn.asn as u32)?;
+                self.write_32b(asn.asn)?;
             }
         }
         Ok(())
     }
 
+    /// Write a prefix with the bits beyond the prefix length cleared.
+    ///
+    /// Prefixes with host bits set are rejected by the composers in strict mode, see
+    /// `set_strict_prefixes`, and reported by the record validator.
     fn write_nlri(&mut self, nlri: &NetworkPrefix, add_path: bool) -> Result<(), DumpError>{
+        let nlri = normalize_prefix(nlri, false)?;
         if add_path{
             self.write_32b(nlri.path_id)?;
         }
-        let ip = nlri.prefix.ip();
-        let ip_bytes = match ip {
+        let ip_bytes = match nlri.prefix.ip() {
             IpAddr::V4(ip) => {ip.octets().to_vec()}
             IpAddr::V6(ip) => {ip.octets().to_vec()}
         };
 
+        // the prefix length is already valid for the address family
         let bit_len = nlri.prefix.prefix();
-        let byte_len: usize = (bit_len as usize + 7) / 8;
+        let byte_len: usize = (bit_len as usize).div_ceil(8);
 
-        self.write_u8(bit_len as u8)?;
-
-        for i in 0..byte_len {
-            self.write_u8(ip_bytes[i])?;
-        }
+        self.write_u8(bit_len)?;
+        self.write_all(&ip_bytes[..byte_len])?;
 
         Ok(())
     }
@@ -77,6 +136,9 @@ impl<W: std::io::Write> WriteUtils for W {}
 mod tests {
     use std::str::FromStr;
 
+    use bgp_models::network::Afi;
+    use bgpkit_parser::parser::utils::DataBytes;
+
     use super::*;
 
     #[test]
@@ -85,26 +147,61 @@ mod tests {
         assert_eq!(ip_u32, 16909060);
     }
 
-    // #[test]
-    // fn test_nlri() {
-    //     let prefix1 = NetworkPrefix{ prefix: IpNetwork::from_str("10.2.0.0/24").unwrap(), path_id: 0 };
-    //     let mut buffer: Vec<u8> = vec![];
-    //     buffer.write_nlri(&prefix1, false).unwrap();
-    //     let prefix2 = buffer.as_slice().read_nlri_prefix(&Afi::Ipv4, false).unwrap();
-    //     assert_eq!(prefix1, prefix2);
-
-    //     // with path id but not toggled on
-    //     let prefix1 = NetworkPrefix{ prefix: IpNetwork::from_str("10.2.0.0/24").unwrap(), path_id: 1 };
-    //     let mut buffer: Vec<u8> = vec![];
-    //     buffer.write_nlri(&prefix1, false).unwrap();
-    //     let prefix2 = buffer.as_slice().read_nlri_prefix(&Afi::Ipv4, false).unwrap();
-    //     assert_ne!(prefix1, prefix2);
-
-    //     // with path id and toggled on
-    //     let prefix1 = NetworkPrefix{ prefix: IpNetwork::from_str("10.2.0.0/24").unwrap(), path_id: 1 };
-    //     let mut buffer: Vec<u8> = vec![];
-    //     buffer.write_nlri(&prefix1, true).unwrap();
-    //     let prefix2 = buffer.as_slice().read_nlri_prefix(&Afi::Ipv4, true).unwrap();
-    //     assert_eq!(prefix1, prefix2);
-    // }
+    #[test]
+    fn test_write_asn() {
+        let mut buffer: Vec<u8> = vec![];
+        // the width of the context is used, not the length of the ASN
+        buffer.write_asn(&Asn { asn: 65000, len: AsnLength::Bits16 }, AsnLength::Bits32).unwrap();
+        buffer.write_asn(&Asn::from(65000), AsnLength::Bits16).unwrap();
+        assert_eq!(buffer, vec![0, 0, 0xfd, 0xe8, 0xfd, 0xe8]);
+
+        assert!(matches!(
+            buffer.write_asn(&Asn::from(65536), AsnLength::Bits16),
+            Err(DumpError::ValueOutOfRange { value: 65536, .. })
+        ));
+    }
+
+    #[test]
+    fn test_nlri() {
+        let prefix1 = NetworkPrefix{ prefix: IpNetwork::from_str("10.2.0.0/24").unwrap(), path_id: 0 };
+        let mut buffer: Vec<u8> = vec![];
+        buffer.write_nlri(&prefix1, false).unwrap();
+        let prefix2 = DataBytes::new(&buffer).read_nlri_prefix(&Afi::Ipv4, false).unwrap();
+        assert_eq!(prefix1, prefix2);
+
+        // with path id but not toggled on
+        let prefix1 = NetworkPrefix{ prefix: IpNetwork::from_str("10.2.0.0/24").unwrap(), path_id: 1 };
+        let mut buffer: Vec<u8> = vec![];
+        buffer.write_nlri(&prefix1, false).unwrap();
+        let prefix2 = DataBytes::new(&buffer).read_nlri_prefix(&Afi::Ipv4, false).unwrap();
+        assert_ne!(prefix1, prefix2);
+
+        // with path id and toggled on
+        let prefix1 = NetworkPrefix{ prefix: IpNetwork::from_str("10.2.0.0/24").unwrap(), path_id: 1 };
+        let mut buffer: Vec<u8> = vec![];
+        buffer.write_nlri(&prefix1, true).unwrap();
+        let prefix2 = DataBytes::new(&buffer).read_nlri_prefix(&Afi::Ipv4, true).unwrap();
+        assert_eq!(prefix1, prefix2);
+
+        // IPv6
+        let prefix1 = NetworkPrefix{ prefix: IpNetwork::from_str("2001:db8:1::/48").unwrap(), path_id: 0 };
+        let mut buffer: Vec<u8> = vec![];
+        buffer.write_nlri(&prefix1, false).unwrap();
+        assert_eq!(buffer.len(), 7);
+        let prefix2 = DataBytes::new(&buffer).read_nlri_prefix(&Afi::Ipv6, false).unwrap();
+        assert_eq!(prefix1, prefix2);
+    }
+
+    #[test]
+    fn test_nlri_host_bits() {
+        let prefix = NetworkPrefix{ prefix: IpNetwork::from_str("10.2.3.4/23").unwrap(), path_id: 0 };
+        let mut buffer: Vec<u8> = vec![];
+        buffer.write_nlri(&prefix, false).unwrap();
+        assert_eq!(buffer, vec![23, 10, 2, 2]);
+        let parsed = DataBytes::new(&buffer).read_nlri_prefix(&Afi::Ipv4, false).unwrap();
+        assert_eq!(parsed, NetworkPrefix::from_str("10.2.2.0/23").unwrap());
+
+        assert!(matches!(normalize_prefix(&prefix, true), Err(DumpError::HostBitsSet(_))));
+        assert_eq!(normalize_prefix(&parsed, true).unwrap(), parsed);
+    }
 }
\ No newline at end of file
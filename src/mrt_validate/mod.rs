@@ -0,0 +1,3 @@
+pub use record_validator::{RecordValidator, ValidationFinding};
+
+mod record_validator;
@@ -0,0 +1,459 @@
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv4Addr};
+
+use bgp_models::prelude::*;
+use num_traits::FromPrimitive;
+use thiserror::Error;
+
+use crate::{ExtraAttributes, MpNextHop, PathAttribute};
+
+/// A problem found by [RecordValidator] in a record.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ValidationFinding {
+    /// The prefix does not belong to the address family of the field it is in.
+    #[error("prefix {prefix} does not belong to address family {afi:?}")]
+    PrefixFamilyMismatch {
+        prefix: NetworkPrefix,
+        afi: Afi,
+    },
+
+    /// The prefix has bits set beyond its prefix length.
+    #[error("prefix {0} has host bits set")]
+    HostBitsSet(NetworkPrefix),
+
+    /// The next hop address cannot be used for the address family.
+    #[error("next hop {next_hop} does not match address family {afi:?}")]
+    NextHopFamilyMismatch {
+        next_hop: IpAddr,
+        afi: Afi,
+    },
+
+    /// An announcement or RIB entry is missing a well-known mandatory attribute.
+    #[error("missing mandatory attribute {0:?}")]
+    MissingMandatoryAttribute(AttrType),
+
+    /// An attribute type code appears more than once in one message or RIB entry.
+    #[error("attribute type {0} appears more than once")]
+    DuplicateAttribute(u8),
+
+    /// The RIB record subtype does not match the address family of the prefix.
+    #[error("RIB subtype {subtype} does not match prefix {prefix}")]
+    RibSubtypeMismatch {
+        subtype: u16,
+        prefix: NetworkPrefix,
+    },
+
+    /// A RIB entry references a peer index beyond the peer index table.
+    #[error("peer index {peer_index} is not in the peer index table of {peer_count} peers")]
+    PeerIndexOutOfRange {
+        peer_index: u16,
+        peer_count: usize,
+    },
+
+    /// A RIB record was validated before any peer index table.
+    #[error("RIB record without a preceding peer index table")]
+    MissingPeerIndexTable,
+}
+
+/// Check records for data that would produce invalid or inconsistent MRT files, before they are
+/// encoded.
+///
+/// The validator keeps the size of the last peer index table it has seen to check the peer
+/// indices of the RIB records that follow it, so records should be validated in file order.
+#[derive(Debug, Default)]
+pub struct RecordValidator {
+    peer_count: Option<usize>,
+}
+
+impl RecordValidator {
+    pub fn new() -> Self {
+        RecordValidator { peer_count: None }
+    }
+
+    /// Validate one record and return all findings, an empty list means the record is valid.
+    pub fn validate(&mut self, record: &MrtRecord) -> Vec<ValidationFinding> {
+        self.validate_with_attributes(record, &ExtraAttributes::new())
+    }
+
+    /// Validate one record that is written with `extra` attributes, which are checked together
+    /// with the attributes of the record.
+    pub fn validate_with_attributes(&mut self, record: &MrtRecord, extra: &ExtraAttributes) -> Vec<ValidationFinding> {
+        let mut findings = vec![];
+        match &record.message {
+            MrtMessage::TableDumpMessage(m) => {
+                let attributes = extra.merged(0, &m.attributes);
+                check_prefix(&m.prefix, afi_of(&m.prefix), &mut findings);
+                check_attributes(&attributes, afi_of(&m.prefix), false, &mut findings);
+                check_mandatory(&attributes, &[AttrType::ORIGIN, AttrType::AS_PATH], &mut findings);
+            }
+            MrtMessage::TableDumpV2Message(TableDumpV2Message::PeerIndexTable(t)) => {
+                self.peer_count = Some(t.peers_map.len());
+            }
+            MrtMessage::TableDumpV2Message(TableDumpV2Message::RibAfiEntries(rib)) => {
+                self.validate_rib(record.common_header.entry_subtype, rib, extra, &mut findings);
+            }
+            MrtMessage::TableDumpV2Message(TableDumpV2Message::RibGenericEntries(_)) => {}
+            MrtMessage::Bgp4Mp(Bgp4Mp::Bgp4MpMessage(m)) |
+            MrtMessage::Bgp4Mp(Bgp4Mp::Bgp4MpMessageLocal(m)) |
+            MrtMessage::Bgp4Mp(Bgp4Mp::Bgp4MpMessageAs4(m)) |
+            MrtMessage::Bgp4Mp(Bgp4Mp::Bgp4MpMessageAs4Local(m)) => {
+                if let BgpMessage::Update(update) = &m.bgp_message {
+                    validate_update(update, extra, &mut findings);
+                }
+            }
+            MrtMessage::Bgp4Mp(_) => {}
+        }
+        findings
+    }
+
+    fn validate_rib(&self, subtype: u16, rib: &RibAfiEntries, extra: &ExtraAttributes, findings: &mut Vec<ValidationFinding>) {
+        let afi = afi_of(&rib.prefix);
+        let subtype_afi = match TableDumpV2Type::from_u16(subtype) {
+            Some(TableDumpV2Type::RibIpv4Unicast) | Some(TableDumpV2Type::RibIpv4Multicast) |
+            Some(TableDumpV2Type::RibIpv4UnicastAddPath) | Some(TableDumpV2Type::RibIpv4MulticastAddPath) => Some(Afi::Ipv4),
+            Some(TableDumpV2Type::RibIpv6Unicast) | Some(TableDumpV2Type::RibIpv6Multicast) |
+            Some(TableDumpV2Type::RibIpv6UnicastAddPath) | Some(TableDumpV2Type::RibIpv6MulticastAddPath) => Some(Afi::Ipv6),
+            _ => None,
+        };
+        if subtype_afi != Some(afi) {
+            findings.push(ValidationFinding::RibSubtypeMismatch { subtype, prefix: rib.prefix });
+        }
+        check_prefix(&rib.prefix, afi, findings);
+
+        match self.peer_count {
+            None => findings.push(ValidationFinding::MissingPeerIndexTable),
+            Some(peer_count) => {
+                for entry in &rib.rib_entries {
+                    if entry.peer_index as usize >= peer_count {
+                        findings.push(ValidationFinding::PeerIndexOutOfRange { peer_index: entry.peer_index, peer_count });
+                    }
+                }
+            }
+        }
+
+        for (i, entry) in rib.rib_entries.iter().enumerate() {
+            let attributes = extra.merged(i, &entry.attributes);
+            check_attributes(&attributes, afi, true, findings);
+            check_mandatory(&attributes, &[AttrType::ORIGIN, AttrType::AS_PATH], findings);
+        }
+    }
+}
+
+fn validate_update(update: &BgpUpdateMessage, extra: &ExtraAttributes, findings: &mut Vec<ValidationFinding>) {
+    // the NLRI fields of the update message itself only carry IPv4 unicast prefixes
+    for prefix in update.withdrawn_prefixes.iter().chain(update.announced_prefixes.iter()) {
+        check_prefix(prefix, Afi::Ipv4, findings);
+    }
+    let attributes = extra.merged(0, &update.attributes);
+    check_attributes(&attributes, Afi::Ipv4, false, findings);
+
+    let mp_announced = attributes.iter().any(|a| match a {
+        PathAttribute::Attribute(Attribute { value: AttributeValue::MpReachNlri(n), .. }) => !n.prefixes.is_empty(),
+        PathAttribute::MpReach(reach) => !reach.nlri.is_empty(),
+        // the NLRI of raw attributes are not decoded
+        PathAttribute::Raw(raw) => raw.attr_type == AttrType::MP_REACHABLE_NLRI as u8,
+        _ => false,
+    });
+    if !update.announced_prefixes.is_empty() {
+        check_mandatory(&attributes, &[AttrType::ORIGIN, AttrType::AS_PATH, AttrType::NEXT_HOP], findings);
+    } else if mp_announced {
+        check_mandatory(&attributes, &[AttrType::ORIGIN, AttrType::AS_PATH], findings);
+    }
+}
+
+/// Check the attributes of one message or RIB entry, `afi` is the address family of the prefixes
+/// a NEXT_HOP attribute applies to. RIB entries hold MP_REACH_NLRI in the abbreviated form that
+/// only has the next hop.
+fn check_attributes(attributes: &[PathAttribute], afi: Afi, rib_entry: bool, findings: &mut Vec<ValidationFinding>) {
+    let mut seen = HashSet::new();
+    for attr in attributes {
+        if !seen.insert(attr.type_code()) {
+            findings.push(ValidationFinding::DuplicateAttribute(attr.type_code()));
+        }
+
+        match attr {
+            PathAttribute::Attribute(a) => check_attribute(a, afi, findings),
+            PathAttribute::MpReach(reach) => {
+                let next_hop = match &reach.next_hop {
+                    MpNextHop::Address(ip) | MpNextHop::Vpn { address: ip, .. } => Some(*ip),
+                    _ => None,
+                };
+                if let (Some(next_hop), Some(afi)) = (next_hop, Afi::from_u16(reach.afi)) {
+                    check_mp_next_hop(next_hop, afi, findings);
+                }
+            }
+            PathAttribute::Raw(raw) if raw.attr_type == AttrType::MP_REACHABLE_NLRI as u8 => {
+                // the next hop length and next hop follow the AFI and SAFI, which RIB entries leave out
+                let (next_hop_afi, offset) = match rib_entry {
+                    true => (Some(afi), 0),
+                    false => (raw.value.get(..2).and_then(|v| Afi::from_u16(u16::from_be_bytes([v[0], v[1]]))), 3),
+                };
+                let next_hop = raw.value.get(offset)
+                    .and_then(|len| raw.value.get(offset + 1..offset + 1 + *len as usize))
+                    .and_then(ipv4_next_hop);
+                if let (Some(next_hop), Some(afi)) = (next_hop, next_hop_afi) {
+                    check_mp_next_hop(next_hop, afi, findings);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn check_attribute(attr: &Attribute, afi: Afi, findings: &mut Vec<ValidationFinding>) {
+    match &attr.value {
+        // the RFC 8950 IPv4 next hop for IPv6 prefixes is only allowed in MP_REACH_NLRI
+        AttributeValue::NextHop(next_hop) if next_hop.is_ipv4() != (afi == Afi::Ipv4) => {
+            findings.push(ValidationFinding::NextHopFamilyMismatch { next_hop: *next_hop, afi });
+        }
+        AttributeValue::MpReachNlri(nlri) => {
+            if let Some(next_hop) = &nlri.next_hop {
+                let next_hop = match next_hop {
+                    NextHopAddress::Ipv4(v) => IpAddr::from(*v),
+                    NextHopAddress::Ipv6(v) | NextHopAddress::Ipv6LinkLocal(v, _) => IpAddr::from(*v),
+                };
+                check_mp_next_hop(next_hop, nlri.afi, findings);
+            }
+            for prefix in &nlri.prefixes {
+                check_prefix(prefix, nlri.afi, findings);
+            }
+        }
+        AttributeValue::MpUnreachNlri(nlri) => {
+            for prefix in &nlri.prefixes {
+                check_prefix(prefix, nlri.afi, findings);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Check the next hop of an MP_REACH_NLRI attribute with prefixes of `afi`.
+fn check_mp_next_hop(next_hop: IpAddr, afi: Afi, findings: &mut Vec<ValidationFinding>) {
+    // IPv4 prefixes may have IPv6 next hops, https://datatracker.ietf.org/doc/html/rfc8950
+    if next_hop.is_ipv4() && afi != Afi::Ipv4 {
+        findings.push(ValidationFinding::NextHopFamilyMismatch { next_hop, afi });
+    }
+}
+
+/// The IPv4 address of an encoded MP_REACH_NLRI next hop, which may follow a route distinguisher.
+fn ipv4_next_hop(next_hop: &[u8]) -> Option<IpAddr> {
+    let address = match next_hop.len() {
+        4 => next_hop,
+        12 => &next_hop[8..],
+        _ => return None,
+    };
+    Some(IpAddr::from(Ipv4Addr::from([address[0], address[1], address[2], address[3]])))
+}
+
+fn check_mandatory(attributes: &[PathAttribute], mandatory: &[AttrType], findings: &mut Vec<ValidationFinding>) {
+    for attr_type in mandatory {
+        if !attributes.iter().any(|a| a.type_code() == *attr_type as u8) {
+            findings.push(ValidationFinding::MissingMandatoryAttribute(*attr_type));
+        }
+    }
+}
+
+fn check_prefix(prefix: &NetworkPrefix, afi: Afi, findings: &mut Vec<ValidationFinding>) {
+    if afi_of(prefix) != afi {
+        findings.push(ValidationFinding::PrefixFamilyMismatch { prefix: *prefix, afi });
+    }
+    if prefix.prefix.network() != prefix.prefix.ip() {
+        findings.push(ValidationFinding::HostBitsSet(*prefix));
+    }
+}
+
+fn afi_of(prefix: &NetworkPrefix) -> Afi {
+    match prefix.prefix.is_ipv4() {
+        true => Afi::Ipv4,
+        false => Afi::Ipv6,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::str::FromStr;
+
+    use crate::{AttrValueDump, LabeledUnicastNlri, MpReachAttribute, RawAttribute, SAFI_MPLS_LABEL};
+
+    use super::*;
+
+    fn attribute(attr_type: AttrType, value: AttributeValue) -> Attribute {
+        Attribute { attr_type, value, flag: 0x40 }
+    }
+
+    fn base_attributes() -> Vec<Attribute> {
+        vec![
+            attribute(AttrType::ORIGIN, AttributeValue::Origin(Origin::IGP)),
+            attribute(AttrType::AS_PATH, AttributeValue::AsPath(AsPath::from_segments(vec![
+                AsPathSegment::AsSequence(vec![Asn::from(65000)])
+            ]))),
+        ]
+    }
+
+    fn record(entry_type: EntryType, entry_subtype: u16, message: MrtMessage) -> MrtRecord {
+        MrtRecord {
+            common_header: CommonHeader { timestamp: 0, microsecond_timestamp: None, entry_type, entry_subtype, length: 0 },
+            message,
+        }
+    }
+
+    fn update_record(update: BgpUpdateMessage) -> MrtRecord {
+        record(EntryType::BGP4MP, 4, MrtMessage::Bgp4Mp(Bgp4Mp::Bgp4MpMessageAs4(Bgp4MpMessage {
+            msg_type: Bgp4MpType::Bgp4MpMessageAs4,
+            peer_asn: Asn::from(65000),
+            local_asn: Asn::from(65001),
+            interface_index: 0,
+            afi: Afi::Ipv4,
+            peer_ip: IpAddr::from(Ipv4Addr::from([10, 0, 0, 1])),
+            local_ip: IpAddr::from(Ipv4Addr::from([10, 0, 0, 2])),
+            bgp_message: BgpMessage::Update(update),
+        })))
+    }
+
+    fn rib_record(subtype: u16, prefix: &str, peer_index: u16) -> MrtRecord {
+        record(EntryType::TABLE_DUMP_V2, subtype, MrtMessage::TableDumpV2Message(TableDumpV2Message::RibAfiEntries(RibAfiEntries {
+            rib_type: TableDumpV2Type::RibIpv4Unicast,
+            sequence_number: 0,
+            prefix: NetworkPrefix::from_str(prefix).unwrap(),
+            rib_entries: vec![RibEntry { peer_index, originated_time: 0, attributes: base_attributes() }],
+        })))
+    }
+
+    #[test]
+    fn test_validate_update() {
+        let mut validator = RecordValidator::new();
+
+        let mut attributes = base_attributes();
+        attributes.push(attribute(AttrType::NEXT_HOP, AttributeValue::NextHop(IpAddr::from(Ipv4Addr::from([10, 0, 0, 1])))));
+        let update = BgpUpdateMessage {
+            withdrawn_prefixes: vec![],
+            attributes: attributes.clone(),
+            announced_prefixes: vec![NetworkPrefix::from_str("10.1.0.0/16").unwrap()],
+        };
+        assert!(validator.validate(&update_record(update)).is_empty());
+
+        let mut attributes = base_attributes();
+        attributes.push(attributes[0].clone());
+        let v6_prefix = NetworkPrefix::from_str("2001:db8::/32").unwrap();
+        let update = BgpUpdateMessage {
+            withdrawn_prefixes: vec![],
+            attributes,
+            announced_prefixes: vec![
+                NetworkPrefix::new(ipnetwork::IpNetwork::from_str("10.1.2.0/16").unwrap(), 0),
+                v6_prefix,
+            ],
+        };
+        assert_eq!(validator.validate(&update_record(update)), vec![
+            ValidationFinding::HostBitsSet(NetworkPrefix::new(ipnetwork::IpNetwork::from_str("10.1.2.0/16").unwrap(), 0)),
+            ValidationFinding::PrefixFamilyMismatch { prefix: v6_prefix, afi: Afi::Ipv4 },
+            ValidationFinding::DuplicateAttribute(AttrType::ORIGIN as u8),
+            ValidationFinding::MissingMandatoryAttribute(AttrType::NEXT_HOP),
+        ]);
+
+        // IPv4 next hop for IPv6 prefixes
+        let mut attributes = base_attributes();
+        attributes.push(attribute(AttrType::MP_REACHABLE_NLRI, AttributeValue::MpReachNlri(Nlri {
+            afi: Afi::Ipv6,
+            safi: Safi::Unicast,
+            next_hop: Some(NextHopAddress::Ipv4(Ipv4Addr::from([10, 0, 0, 1]))),
+            prefixes: vec![v6_prefix],
+        })));
+        let update = BgpUpdateMessage { withdrawn_prefixes: vec![], attributes, announced_prefixes: vec![] };
+        assert_eq!(validator.validate(&update_record(update)), vec![
+            ValidationFinding::NextHopFamilyMismatch { next_hop: IpAddr::from(Ipv4Addr::from([10, 0, 0, 1])), afi: Afi::Ipv6 },
+        ]);
+    }
+
+    #[test]
+    fn test_validate_rib() {
+        let mut validator = RecordValidator::new();
+        assert_eq!(validator.validate(&rib_record(2, "10.0.0.0/8", 0)), vec![ValidationFinding::MissingPeerIndexTable]);
+
+        let mut peers_map = HashMap::new();
+        peers_map.insert(0, Peer {
+            peer_type: 2,
+            peer_bgp_id: Ipv4Addr::from([10, 0, 0, 1]),
+            peer_address: IpAddr::from(Ipv4Addr::from([10, 0, 0, 1])),
+            peer_asn: Asn::from(65000),
+        });
+        let table = record(EntryType::TABLE_DUMP_V2, 1, MrtMessage::TableDumpV2Message(TableDumpV2Message::PeerIndexTable(PeerIndexTable {
+            collector_bgp_id: Ipv4Addr::from([10, 0, 0, 100]),
+            view_name_length: 0,
+            view_name: "".to_string(),
+            peer_count: 1,
+            peers_map,
+        })));
+        assert!(validator.validate(&table).is_empty());
+        assert!(validator.validate(&rib_record(2, "10.0.0.0/8", 0)).is_empty());
+        assert_eq!(validator.validate(&rib_record(4, "10.0.0.0/8", 1)), vec![
+            ValidationFinding::RibSubtypeMismatch { subtype: 4, prefix: NetworkPrefix::from_str("10.0.0.0/8").unwrap() },
+            ValidationFinding::PeerIndexOutOfRange { peer_index: 1, peer_count: 1 },
+        ]);
+
+        // IPv4 NEXT_HOP attribute on an IPv6 RIB entry
+        let mut v6_record = rib_record(4, "2001:db8::/32", 0);
+        let next_hop = IpAddr::from(Ipv4Addr::from([10, 0, 0, 1]));
+        if let MrtMessage::TableDumpV2Message(TableDumpV2Message::RibAfiEntries(rib)) = &mut v6_record.message {
+            rib.rib_entries[0].attributes.push(attribute(AttrType::NEXT_HOP, AttributeValue::NextHop(next_hop)));
+        }
+        assert_eq!(validator.validate(&v6_record), vec![
+            ValidationFinding::NextHopFamilyMismatch { next_hop, afi: Afi::Ipv6 },
+        ]);
+    }
+
+    #[test]
+    fn test_validate_extra_attributes() {
+        let mut validator = RecordValidator { peer_count: Some(1) };
+        let next_hop = IpAddr::from(Ipv4Addr::from([10, 0, 0, 1]));
+
+        // duplicates between record attributes and extra attributes, and among raw attributes
+        let mut extra = ExtraAttributes::new();
+        extra.insert(0, 0, RawAttribute::new(1, 0x40, vec![0]).into());
+        extra.insert(0, 2, RawAttribute::new(34, 0xC0, vec![1]).into());
+        extra.insert(0, 2, RawAttribute::new(34, 0xC0, vec![2]).into());
+        let update = BgpUpdateMessage { withdrawn_prefixes: vec![], attributes: base_attributes(), announced_prefixes: vec![] };
+        assert_eq!(validator.validate_with_attributes(&update_record(update), &extra), vec![
+            ValidationFinding::DuplicateAttribute(AttrType::ORIGIN as u8),
+            ValidationFinding::DuplicateAttribute(34),
+        ]);
+
+        // a raw MP_REACH_NLRI announces prefixes, with an IPv4 next hop for IPv6
+        let mut extra = ExtraAttributes::new();
+        extra.insert(0, 0, RawAttribute::new(14, 0x80, vec![0, 2, 1, 4, 10, 0, 0, 1, 0, 32, 0x20, 0x01, 0x0d, 0xb8]).into());
+        let update = BgpUpdateMessage { withdrawn_prefixes: vec![], attributes: vec![], announced_prefixes: vec![] };
+        assert_eq!(validator.validate_with_attributes(&update_record(update), &extra), vec![
+            ValidationFinding::NextHopFamilyMismatch { next_hop, afi: Afi::Ipv6 },
+            ValidationFinding::MissingMandatoryAttribute(AttrType::ORIGIN),
+            ValidationFinding::MissingMandatoryAttribute(AttrType::AS_PATH),
+        ]);
+
+        // the same for a typed MP_REACH_NLRI
+        let mut extra = ExtraAttributes::new();
+        extra.insert(0, 0, MpReachAttribute {
+            afi: 2,
+            safi: SAFI_MPLS_LABEL,
+            next_hop: MpNextHop::Address(next_hop),
+            nlri: vec![LabeledUnicastNlri::new(16, ipnetwork::IpNetwork::from_str("2001:db8::/32").unwrap())],
+        }.to_attribute().unwrap());
+        let update = BgpUpdateMessage { withdrawn_prefixes: vec![], attributes: vec![], announced_prefixes: vec![] };
+        assert_eq!(validator.validate_with_attributes(&update_record(update), &extra), vec![
+            ValidationFinding::NextHopFamilyMismatch { next_hop, afi: Afi::Ipv6 },
+            ValidationFinding::MissingMandatoryAttribute(AttrType::ORIGIN),
+            ValidationFinding::MissingMandatoryAttribute(AttrType::AS_PATH),
+        ]);
+
+        // RIB entries with a raw ORIGIN and an abbreviated raw MP_REACH_NLRI
+        let mut v6_record = rib_record(4, "2001:db8::/32", 0);
+        if let MrtMessage::TableDumpV2Message(TableDumpV2Message::RibAfiEntries(rib)) = &mut v6_record.message {
+            rib.rib_entries[0].attributes.remove(0);
+        }
+        let mut extra = ExtraAttributes::new();
+        extra.insert(0, 0, RawAttribute::new(1, 0x40, vec![0]).into());
+        assert!(validator.validate_with_attributes(&v6_record, &extra).is_empty());
+        extra.insert(0, 1, RawAttribute::new(14, 0x80, vec![4, 10, 0, 0, 1]).into());
+        assert_eq!(validator.validate_with_attributes(&v6_record, &extra), vec![
+            ValidationFinding::NextHopFamilyMismatch { next_hop, afi: Afi::Ipv6 },
+        ]);
+    }
+}
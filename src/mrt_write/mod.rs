@@ -0,0 +1,5 @@
+pub use raw_record::{RawMrtRecord, RawRecordIterator};
+pub use rotating_writer::{RotatingWriter, RotationPolicy};
+
+mod raw_record;
+mod rotating_writer;
@@ -0,0 +1,641 @@
+use std::convert::TryFrom;
+use std::io::{ErrorKind, Read, Write};
+use std::iter::FusedIterator;
+
+use bgp_models::prelude::*;
+use bgpkit_parser::parse_mrt_record;
+use bgpkit_parser::parser::{ParserError, ParserErrorKind};
+use num_traits::FromPrimitive;
+
+use crate::{DumpError, ExtraAttributes, MrtDump, MrtDumpWithAttributes, PathAttribute};
+use crate::mrt_dump::{dropped_attributes, split_attributes};
+
+/// Records claiming to be longer are treated as corrupt instead of being allocated
+const MAX_RECORD_LENGTH: usize = 16 * 1024 * 1024;
+
+/// A parsed [MrtRecord] together with the raw bytes it was parsed from.
+///
+/// As long as the record is not modified, it is written out verbatim from the original bytes.
+/// Mutable access via [RawMrtRecord::record_mut] drops the raw bytes, and the record is fully
+/// re-encoded when written.
+///
+/// Path attributes the parser does not support are restored from the original bytes as
+/// [RawAttribute](crate::RawAttribute)s in the [ExtraAttributes] of the record, so they are kept
+/// in place when a modified record is re-encoded. This includes attribute type codes the parser
+/// does not know at all, which it would otherwise reject the whole record for.
+#[derive(Debug, Clone)]
+pub struct RawMrtRecord {
+    record: MrtRecord,
+    extra: ExtraAttributes,
+    raw_bytes: Option<Vec<u8>>,
+}
+
+impl RawMrtRecord {
+    /// A record without raw bytes, which is encoded together with the `extra` attributes.
+    pub fn new(record: MrtRecord, extra: ExtraAttributes) -> Self {
+        RawMrtRecord { record, extra, raw_bytes: None }
+    }
+
+    /// Parse a record from the complete raw bytes of one MRT record, header included.
+    pub fn from_bytes(raw_bytes: Vec<u8>) -> Result<Self, ParserError> {
+        let (record, extra) = parse_raw_record(&raw_bytes)?;
+        Ok(RawMrtRecord { record, extra, raw_bytes: Some(raw_bytes) })
+    }
+
+    pub fn record(&self) -> &MrtRecord {
+        &self.record
+    }
+
+    /// The attributes of the record that it cannot hold itself.
+    pub fn extra_attributes(&self) -> &ExtraAttributes {
+        &self.extra
+    }
+
+    /// Get mutable access to the extra attributes. The raw bytes are dropped as they may change.
+    pub fn extra_attributes_mut(&mut self) -> &mut ExtraAttributes {
+        self.raw_bytes = None;
+        &mut self.extra
+    }
+
+    /// Get mutable access to the record. The raw bytes are dropped as the record may change.
+    pub fn record_mut(&mut self) -> &mut MrtRecord {
+        self.raw_bytes = None;
+        &mut self.record
+    }
+
+    pub fn into_record(self) -> MrtRecord {
+        self.record
+    }
+
+    pub fn raw_bytes(&self) -> Option<&[u8]> {
+        self.raw_bytes.as_deref()
+    }
+
+    pub fn is_modified(&self) -> bool {
+        self.raw_bytes.is_none()
+    }
+
+    /// Write the record to `writer`, without copying if the original bytes are still available.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), DumpError> {
+        match &self.raw_bytes {
+            Some(bytes) => writer.write_all(bytes)?,
+            None => writer.write_all(self.record.to_bytes_with_attributes(0, &self.extra)?.as_slice())?,
+        }
+        Ok(())
+    }
+}
+
+impl MrtDump for RawMrtRecord {
+    fn to_bytes(&self, subtype: u16) -> Result<Vec<u8>, DumpError> {
+        match &self.raw_bytes {
+            Some(bytes) => Ok(bytes.clone()),
+            None => self.record.to_bytes_with_attributes(subtype, &self.extra),
+        }
+    }
+}
+
+/// Iterator over [RawMrtRecord]s read from an uncompressed MRT byte stream.
+///
+/// Records that fail to parse are returned as errors, with the raw bytes of the record attached,
+/// and iteration continues with the next record. Errors reading the record framing (truncated
+/// input, implausible record lengths, I/O errors) are returned once and end the iteration, as the
+/// position of the next record is unknown.
+pub struct RawRecordIterator<R: Read> {
+    reader: R,
+    done: bool,
+}
+
+impl<R: Read> RawRecordIterator<R> {
+    pub fn new(reader: R) -> Self {
+        RawRecordIterator { reader, done: false }
+    }
+
+    fn read_raw_record(&mut self) -> Result<Option<Vec<u8>>, ParserError> {
+        let mut header = [0u8; 12];
+        let mut read = 0;
+        while read < header.len() {
+            match self.reader.read(&mut header[read..]) {
+                Ok(0) => break,
+                Ok(n) => read += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(ParserErrorKind::IoError(e).into()),
+            }
+        }
+        match read {
+            0 => return Ok(None),
+            12 => {}
+            _ => return Err(ParserErrorKind::TruncatedMsg(format!("truncated MRT header: {} bytes", read)).into()),
+        }
+
+        let length = u32::from_be_bytes([header[8], header[9], header[10], header[11]]) as usize;
+        if length > MAX_RECORD_LENGTH {
+            return Err(ParserErrorKind::ParseError(format!("implausible MRT record length: {} bytes", length)).into())
+        }
+        let mut raw_bytes = Vec::with_capacity(header.len() + length);
+        raw_bytes.extend_from_slice(&header);
+        raw_bytes.resize(header.len() + length, 0);
+        if let Err(e) = self.reader.read_exact(&mut raw_bytes[header.len()..]) {
+            return Err(ParserErrorKind::from(e).into())
+        }
+        Ok(Some(raw_bytes))
+    }
+}
+
+impl<R: Read> Iterator for RawRecordIterator<R> {
+    type Item = Result<RawMrtRecord, ParserError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None
+        }
+        let raw_bytes = match self.read_raw_record() {
+            Ok(Some(bytes)) => bytes,
+            Ok(None) => {
+                self.done = true;
+                return None
+            }
+            Err(e) => {
+                self.done = true;
+                return Some(Err(e))
+            }
+        };
+
+        match parse_raw_record(&raw_bytes) {
+            Ok((record, extra)) => Some(Ok(RawMrtRecord { record, extra, raw_bytes: Some(raw_bytes) })),
+            Err(e) => Some(Err(ParserError { error: e.error, bytes: Some(raw_bytes) })),
+        }
+    }
+}
+
+impl<R: Read> FusedIterator for RawRecordIterator<R> {}
+
+/// Parse one record from its raw bytes, keeping the attributes the parser drops.
+///
+/// The parser rejects the whole record for attribute type codes it does not know, so these
+/// attributes are cut from a copy of the bytes that is parsed instead. They are restored from the
+/// original bytes afterwards as extra attributes, together with the attributes the parser skips.
+fn parse_raw_record(raw_bytes: &[u8]) -> Result<(MrtRecord, ExtraAttributes), ParserError> {
+    let entry_type = raw_bytes.get(4..6).and_then(|b| EntryType::from_u16(u16::from_be_bytes([b[0], b[1]])));
+    if entry_type == Some(EntryType::BGP4MP_ET) && raw_bytes.len() < 16 {
+        return Err(ParserErrorKind::TruncatedMsg("BGP4MP_ET record without microsecond timestamp".to_string()).into())
+    }
+
+    let layout = match attribute_layout(raw_bytes) {
+        Some(layout) => layout,
+        None => return Ok((parse_mrt_record(&mut &raw_bytes[..])?, ExtraAttributes::new())),
+    };
+    let record = match strip_unknown_attributes(raw_bytes, &layout)? {
+        Some((stripped, removed)) => {
+            let mut record = parse_mrt_record(&mut stripped.as_slice())?;
+            record.common_header.length += removed as u32;
+            record
+        }
+        None => parse_mrt_record(&mut &raw_bytes[..])?,
+    };
+    let extra = dropped_attribute_lists(&record, raw_bytes, &layout);
+    Ok((record, extra))
+}
+
+/// The attributes the parser dropped from `record`, taken from its raw bytes.
+///
+/// Attribute lists that do not match the ones in the raw bytes get no extra attributes.
+fn dropped_attribute_lists(record: &MrtRecord, raw_bytes: &[u8], layout: &AttributeLayout) -> ExtraAttributes {
+    let mut extra = ExtraAttributes::new();
+    let attribute_lists: Vec<&Vec<Attribute>> = match &record.message {
+        MrtMessage::Bgp4Mp(Bgp4Mp::Bgp4MpMessage(m)) | MrtMessage::Bgp4Mp(Bgp4Mp::Bgp4MpMessageLocal(m)) |
+        MrtMessage::Bgp4Mp(Bgp4Mp::Bgp4MpMessageAs4(m)) | MrtMessage::Bgp4Mp(Bgp4Mp::Bgp4MpMessageAs4Local(m)) => {
+            match &m.bgp_message {
+                BgpMessage::Update(u) => vec![&u.attributes],
+                _ => vec![],
+            }
+        }
+        MrtMessage::TableDumpV2Message(TableDumpV2Message::RibAfiEntries(rib)) => {
+            rib.rib_entries.iter().map(|e| &e.attributes).collect()
+        }
+        _ => vec![],
+    };
+    if attribute_lists.len() != layout.blocks.len() {
+        return extra
+    }
+    for (list, (attributes, &length_pos)) in attribute_lists.into_iter().zip(&layout.blocks).enumerate() {
+        let dropped = split_attributes(attribute_list(raw_bytes, length_pos))
+            .and_then(|raw| dropped_attributes(attributes, raw));
+        for (position, attr) in dropped.into_iter().flatten() {
+            extra.insert(list, position, PathAttribute::Raw(attr));
+        }
+    }
+    extra
+}
+
+/// Copy `raw_bytes` without the attributes whose type codes the parser does not know, fixing up
+/// the length fields that enclose them. Returns the copy and the number of bytes removed, or
+/// `None` if there is nothing to remove.
+///
+/// Fails if the enclosing length fields are too short for the removed attributes.
+fn strip_unknown_attributes(raw_bytes: &[u8], layout: &AttributeLayout) -> Result<Option<(Vec<u8>, usize)>, ParserError> {
+    let mut stripped = Vec::with_capacity(raw_bytes.len());
+    let mut copied = 0;
+    let mut removed = 0;
+    for &length_pos in &layout.blocks {
+        let list = attribute_list(raw_bytes, length_pos);
+        // malformed attribute lists are left to the parser
+        let attributes = match split_attributes(list) {
+            Some(attributes) => attributes,
+            None => return Ok(None),
+        };
+        if attributes.iter().all(|a| AttrType::from_u8(a.attr_type).is_some()) {
+            continue
+        }
+
+        let mut kept: Vec<u8> = vec![];
+        for attr in attributes.into_iter().filter(|a| AttrType::from_u8(a.attr_type).is_some()) {
+            kept.push(attr.flag);
+            kept.push(attr.attr_type);
+            match attr.flag & AttributeFlagsBit::ExtendedLengthBit as u8 {
+                0 => kept.push(attr.value.len() as u8),
+                _ => kept.extend(&(attr.value.len() as u16).to_be_bytes()),
+            }
+            kept.extend(attr.value);
+        }
+        stripped.extend(&raw_bytes[copied..length_pos]);
+        stripped.extend(&(kept.len() as u16).to_be_bytes());
+        stripped.extend(&kept);
+        removed += list.len() - kept.len();
+        copied = length_pos + 2 + list.len();
+    }
+    if removed == 0 {
+        return Ok(None)
+    }
+    stripped.extend(&raw_bytes[copied..]);
+
+    let length_error = |field: &str| -> ParserError {
+        ParserErrorKind::ParseError(format!("{} shorter than its path attributes", field)).into()
+    };
+    let mrt_length = u32::from_be_bytes([stripped[8], stripped[9], stripped[10], stripped[11]]);
+    let mrt_length = u32::try_from(removed).ok()
+        .and_then(|removed| mrt_length.checked_sub(removed))
+        .ok_or_else(|| length_error("MRT record length"))?;
+    stripped[8..12].copy_from_slice(&mrt_length.to_be_bytes());
+    if let Some(pos) = layout.bgp_length_pos {
+        let bgp_length = u16::from_be_bytes([stripped[pos], stripped[pos + 1]]);
+        let bgp_length = u16::try_from(removed).ok()
+            .and_then(|removed| bgp_length.checked_sub(removed))
+            .ok_or_else(|| length_error("BGP message length"))?;
+        stripped[pos..pos + 2].copy_from_slice(&bgp_length.to_be_bytes());
+    }
+    Ok(Some((stripped, removed)))
+}
+
+fn take<'a>(bytes: &mut &'a [u8], n: usize) -> Option<&'a [u8]> {
+    if bytes.len() < n {
+        return None
+    }
+    let (head, tail) = bytes.split_at(n);
+    *bytes = tail;
+    Some(head)
+}
+
+fn take_u16(bytes: &mut &[u8]) -> Option<u16> {
+    take(bytes, 2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+}
+
+/// Positions of the encoded attribute lists in the raw bytes of a record.
+struct AttributeLayout {
+    /// positions of the 2-octet attribute length fields, each followed by its attribute list
+    blocks: Vec<usize>,
+    /// position of the BGP message length field of BGP4MP records
+    bgp_length_pos: Option<usize>,
+}
+
+/// The attribute list following the attribute length field at `length_pos`.
+fn attribute_list(raw_bytes: &[u8], length_pos: usize) -> &[u8] {
+    let length = u16::from_be_bytes([raw_bytes[length_pos], raw_bytes[length_pos + 1]]) as usize;
+    &raw_bytes[length_pos + 2..length_pos + 2 + length]
+}
+
+/// Locate the encoded attribute lists of BGP4MP update messages and TABLE_DUMP_V2 RIB entries.
+fn attribute_layout(raw_bytes: &[u8]) -> Option<AttributeLayout> {
+    let mut body = raw_bytes.get(12..)?;
+    let pos = |body: &[u8]| raw_bytes.len() - body.len();
+    let entry_type = EntryType::from_u16(u16::from_be_bytes([raw_bytes[4], raw_bytes[5]]));
+    let subtype = u16::from_be_bytes([raw_bytes[6], raw_bytes[7]]);
+
+    let mut layout = AttributeLayout { blocks: vec![], bgp_length_pos: None };
+    match entry_type {
+        Some(EntryType::BGP4MP) | Some(EntryType::BGP4MP_ET) => {
+            if entry_type == Some(EntryType::BGP4MP_ET) {
+                take(&mut body, 4)?;
+            }
+            let asn_len = match Bgp4MpType::from_u16(subtype) {
+                Some(Bgp4MpType::Bgp4MpMessage) | Some(Bgp4MpType::Bgp4MpMessageLocal) |
+                Some(Bgp4MpType::Bgp4MpMessageAddpath) | Some(Bgp4MpType::Bgp4MpMessageLocalAddpath) => 2,
+                Some(Bgp4MpType::Bgp4MpMessageAs4) | Some(Bgp4MpType::Bgp4MpMessageAs4Local) |
+                Some(Bgp4MpType::Bgp4MpMessageAs4Addpath) | Some(Bgp4MpType::Bgp4MpMessageLocalAs4Addpath) => 4,
+                _ => return Some(layout),
+            };
+            // peer and local ASN, interface index
+            take(&mut body, asn_len * 2 + 2)?;
+            let ip_len = match Afi::from_u16(take_u16(&mut body)?) {
+                Some(Afi::Ipv4) => 4,
+                Some(Afi::Ipv6) => 16,
+                _ => return None,
+            };
+            // peer and local IP, BGP marker
+            take(&mut body, ip_len * 2 + 16)?;
+            let bgp_length_pos = pos(body);
+            take(&mut body, 2)?;
+            if take(&mut body, 1)?[0] != 2 {
+                return Some(layout)
+            }
+            let withdrawn_len = take_u16(&mut body)? as usize;
+            take(&mut body, withdrawn_len)?;
+            let length_pos = pos(body);
+            let attributes_len = take_u16(&mut body)? as usize;
+            take(&mut body, attributes_len)?;
+            layout.blocks.push(length_pos);
+            layout.bgp_length_pos = Some(bgp_length_pos);
+        }
+        Some(EntryType::TABLE_DUMP_V2) => {
+            let add_path = match TableDumpV2Type::from_u16(subtype) {
+                Some(TableDumpV2Type::RibIpv4Unicast) | Some(TableDumpV2Type::RibIpv4Multicast) |
+                Some(TableDumpV2Type::RibIpv6Unicast) | Some(TableDumpV2Type::RibIpv6Multicast) => false,
+                Some(TableDumpV2Type::RibIpv4UnicastAddPath) | Some(TableDumpV2Type::RibIpv4MulticastAddPath) |
+                Some(TableDumpV2Type::RibIpv6UnicastAddPath) | Some(TableDumpV2Type::RibIpv6MulticastAddPath) => true,
+                _ => return Some(layout),
+            };
+            // sequence number, then the prefix
+            take(&mut body, 4)?;
+            let prefix_len = take(&mut body, 1)?[0] as usize;
+            take(&mut body, prefix_len.div_ceil(8))?;
+            let entry_count = take_u16(&mut body)?;
+            for _ in 0..entry_count {
+                // peer index and originated time, and the path identifier with ADD-PATH
+                take(&mut body, if add_path { 10 } else { 6 })?;
+                let length_pos = pos(body);
+                let attributes_len = take_u16(&mut body)? as usize;
+                take(&mut body, attributes_len)?;
+                layout.blocks.push(length_pos);
+            }
+        }
+        _ => {}
+    }
+    Some(layout)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::str::FromStr;
+
+    use bgp_models::prelude::*;
+
+    use crate::RawAttribute;
+
+    use super::*;
+
+    fn update_bytes() -> Vec<u8> {
+        let record = MrtRecord {
+            common_header: CommonHeader {
+                timestamp: 1,
+                microsecond_timestamp: Some(5),
+                entry_type: EntryType::BGP4MP_ET,
+                entry_subtype: 4,
+                length: 0,
+            },
+            message: MrtMessage::Bgp4Mp(Bgp4Mp::Bgp4MpMessageAs4(Bgp4MpMessage {
+                msg_type: Bgp4MpType::Bgp4MpMessageAs4,
+                peer_asn: Asn::from(65000),
+                local_asn: Asn::from(65001),
+                interface_index: 0,
+                afi: Afi::Ipv4,
+                peer_ip: IpAddr::from(Ipv4Addr::from([10, 0, 0, 1])),
+                local_ip: IpAddr::from(Ipv4Addr::from([10, 0, 0, 2])),
+                bgp_message: BgpMessage::Update(BgpUpdateMessage {
+                    withdrawn_prefixes: vec![NetworkPrefix::from_str("10.1.0.0/16").unwrap()],
+                    attributes: vec![],
+                    announced_prefixes: vec![],
+                }),
+            })),
+        };
+        record.to_bytes(0).unwrap()
+    }
+
+    #[test]
+    fn test_passthrough() {
+        let bytes = update_bytes();
+        let mut input = vec![];
+        input.extend(&bytes);
+        input.extend(&bytes);
+
+        let records = RawRecordIterator::new(input.as_slice()).collect::<Result<Vec<RawMrtRecord>, ParserError>>().unwrap();
+        assert_eq!(records.len(), 2);
+
+        let mut output = vec![];
+        records[0].write_to(&mut output).unwrap();
+        assert_eq!(output, bytes);
+        assert_eq!(records[1].to_bytes(0).unwrap(), bytes);
+
+        let mut record = records[0].clone();
+        assert!(!record.is_modified());
+        record.record_mut().common_header.timestamp = 2;
+        assert!(record.is_modified());
+        let modified = record.to_bytes(0).unwrap();
+        assert_eq!(modified[..4], [0, 0, 0, 2]);
+        assert_eq!(modified[4..], bytes[4..]);
+    }
+
+    #[test]
+    fn test_truncated_input() {
+        let bytes = update_bytes();
+        let mut iter = RawRecordIterator::new(&bytes[..bytes.len() - 1]);
+        assert!(iter.next().unwrap().is_err());
+        assert!(iter.next().is_none());
+
+        // a corrupt length field fails before allocating, and ends the iteration
+        let mut input = bytes.clone();
+        input[8..12].copy_from_slice(&u32::MAX.to_be_bytes());
+        input.extend(&bytes);
+        let mut iter = RawRecordIterator::new(input.as_slice());
+        assert!(matches!(iter.next(), Some(Err(ParserError { error: ParserErrorKind::ParseError(_), .. }))));
+        assert!(iter.next().is_none());
+
+        // BGP4MP_ET records without the microsecond timestamp are rejected by both entry points
+        let mut input = vec![0, 0, 0, 1];
+        input.extend((EntryType::BGP4MP_ET as u16).to_be_bytes());
+        input.extend(4u16.to_be_bytes());
+        input.extend(0u32.to_be_bytes());
+        assert!(matches!(RawMrtRecord::from_bytes(input.clone()), Err(ParserError { error: ParserErrorKind::TruncatedMsg(_), .. })));
+        let mut iter = RawRecordIterator::new(input.as_slice());
+        assert!(matches!(iter.next(), Some(Err(ParserError { error: ParserErrorKind::TruncatedMsg(_), bytes: Some(_) }))));
+    }
+
+    #[test]
+    fn test_corrupt_length_with_unknown_attribute() {
+        let unknown = RawAttribute::new(34, 0xC0, vec![0; 8]);
+        let record = RawMrtRecord::from_bytes(update_bytes()).unwrap().into_record();
+        let mut extra = ExtraAttributes::new();
+        extra.insert(0, usize::MAX, PathAttribute::Raw(unknown));
+        let mut bytes = RawMrtRecord::new(record, extra).to_bytes(0).unwrap();
+        let pos = attribute_layout(&bytes).unwrap().bgp_length_pos.unwrap();
+
+        // a BGP length smaller than the unknown attribute is a parse error, not an underflow
+        bytes[pos..pos + 2].copy_from_slice(&4u16.to_be_bytes());
+        assert!(matches!(RawMrtRecord::from_bytes(bytes), Err(ParserError { error: ParserErrorKind::ParseError(_), .. })));
+    }
+
+    #[test]
+    fn test_restore_unsupported_attributes() {
+        let aigp = RawAttribute::new(26, 0x80, vec![1, 0, 11, 0, 0, 0, 0, 0, 0, 0, 100]);
+        // optional transitive with the partial bit set
+        let prefix_sid = RawAttribute::new(40, 0xE0, vec![1, 0, 7, 0, 0, 0, 0, 0, 0, 1]);
+        let attributes = vec![
+            Attribute { attr_type: AttrType::ORIGIN, value: AttributeValue::Origin(Origin::IGP), flag: 0x40 },
+            Attribute {
+                attr_type: AttrType::AS_PATH,
+                value: AttributeValue::AsPath(AsPath::from_segments(vec![AsPathSegment::AsSequence(vec![Asn::from(65000)])])),
+                flag: 0x40,
+            },
+            Attribute { attr_type: AttrType::NEXT_HOP, value: AttributeValue::NextHop(IpAddr::from(Ipv4Addr::from([10, 0, 0, 1]))), flag: 0x40 },
+        ];
+        let mut extra = ExtraAttributes::new();
+        extra.insert(0, 3, PathAttribute::Raw(aigp));
+        extra.insert(0, 3, PathAttribute::Raw(prefix_sid));
+        let record = MrtRecord {
+            common_header: CommonHeader {
+                timestamp: 1,
+                microsecond_timestamp: None,
+                entry_type: EntryType::BGP4MP,
+                entry_subtype: 4,
+                length: 0,
+            },
+            message: MrtMessage::Bgp4Mp(Bgp4Mp::Bgp4MpMessageAs4(Bgp4MpMessage {
+                msg_type: Bgp4MpType::Bgp4MpMessageAs4,
+                peer_asn: Asn::from(65000),
+                local_asn: Asn::from(65001),
+                interface_index: 0,
+                afi: Afi::Ipv4,
+                peer_ip: IpAddr::from(Ipv4Addr::from([10, 0, 0, 1])),
+                local_ip: IpAddr::from(Ipv4Addr::from([10, 0, 0, 2])),
+                bgp_message: BgpMessage::Update(BgpUpdateMessage {
+                    withdrawn_prefixes: vec![],
+                    attributes,
+                    announced_prefixes: vec![NetworkPrefix::from_str("10.1.0.0/16").unwrap()],
+                }),
+            })),
+        };
+        let bytes = RawMrtRecord::new(record.clone(), extra.clone()).to_bytes(0).unwrap();
+
+        let mut raw_record = RawMrtRecord::from_bytes(bytes.clone()).unwrap();
+        match (&raw_record.record().message, &record.message) {
+            // the parser reads AS4 messages into the Bgp4MpMessage variant
+            (MrtMessage::Bgp4Mp(Bgp4Mp::Bgp4MpMessage(parsed)), MrtMessage::Bgp4Mp(Bgp4Mp::Bgp4MpMessageAs4(m))) => {
+                assert_eq!(parsed.bgp_message, m.bgp_message);
+            }
+            m => panic!("unexpected message {:?}", m),
+        }
+        assert_eq!(raw_record.extra_attributes(), &extra);
+
+        // the restored attributes are re-encoded with the modified record
+        raw_record.record_mut().common_header.timestamp = 2;
+        let modified = raw_record.to_bytes(0).unwrap();
+        assert_eq!(modified[..4], [0, 0, 0, 2]);
+        assert_eq!(modified[4..], bytes[4..]);
+    }
+
+    #[test]
+    fn test_unknown_attribute_types() {
+        // type codes the parser has no attribute type for
+        let unknown = vec![
+            RawAttribute::new(34, 0xC0, vec![1, 2, 3]),
+            RawAttribute::new(35, 0xC0, vec![0, 0, 0xFD, 0xE8]),
+            RawAttribute::new(36, 0xD0, vec![0; 300]),
+            RawAttribute::new(39, 0xE0, vec![]),
+        ];
+        let attributes = vec![
+            Attribute { attr_type: AttrType::ORIGIN, value: AttributeValue::Origin(Origin::IGP), flag: 0x40 },
+            Attribute {
+                attr_type: AttrType::AS_PATH,
+                value: AttributeValue::AsPath(AsPath::from_segments(vec![AsPathSegment::AsSequence(vec![Asn::from(65000)])])),
+                flag: 0x40,
+            },
+            Attribute { attr_type: AttrType::NEXT_HOP, value: AttributeValue::NextHop(IpAddr::from(Ipv4Addr::from([10, 0, 0, 1]))), flag: 0x40 },
+        ];
+        // in between, and after the known attributes
+        let positions = [1, 2, 3, 3];
+        let mut update_extra = ExtraAttributes::new();
+        let mut rib_extra = ExtraAttributes::new();
+        for (position, attr) in positions.iter().zip(&unknown) {
+            update_extra.insert(0, *position, PathAttribute::Raw(attr.clone()));
+            rib_extra.insert(1, *position, PathAttribute::Raw(attr.clone()));
+        }
+
+        let update = MrtRecord {
+            common_header: CommonHeader {
+                timestamp: 1,
+                microsecond_timestamp: Some(5),
+                entry_type: EntryType::BGP4MP_ET,
+                entry_subtype: 4,
+                length: 0,
+            },
+            message: MrtMessage::Bgp4Mp(Bgp4Mp::Bgp4MpMessageAs4(Bgp4MpMessage {
+                msg_type: Bgp4MpType::Bgp4MpMessageAs4,
+                peer_asn: Asn::from(65000),
+                local_asn: Asn::from(65001),
+                interface_index: 0,
+                afi: Afi::Ipv4,
+                peer_ip: IpAddr::from(Ipv4Addr::from([10, 0, 0, 1])),
+                local_ip: IpAddr::from(Ipv4Addr::from([10, 0, 0, 2])),
+                bgp_message: BgpMessage::Update(BgpUpdateMessage {
+                    withdrawn_prefixes: vec![NetworkPrefix::from_str("10.2.0.0/16").unwrap()],
+                    attributes: attributes.clone(),
+                    announced_prefixes: vec![NetworkPrefix::from_str("10.1.0.0/16").unwrap()],
+                }),
+            })),
+        };
+        let rib = MrtRecord {
+            common_header: CommonHeader {
+                timestamp: 1,
+                microsecond_timestamp: None,
+                entry_type: EntryType::TABLE_DUMP_V2,
+                entry_subtype: TableDumpV2Type::RibIpv4Unicast as u16,
+                length: 0,
+            },
+            message: MrtMessage::TableDumpV2Message(TableDumpV2Message::RibAfiEntries(RibAfiEntries {
+                rib_type: TableDumpV2Type::RibIpv4Unicast,
+                sequence_number: 0,
+                prefix: NetworkPrefix::from_str("10.1.0.0/16").unwrap(),
+                rib_entries: vec![
+                    RibEntry { peer_index: 0, originated_time: 1, attributes: attributes.clone() },
+                    RibEntry { peer_index: 1, originated_time: 1, attributes: attributes.clone() },
+                ],
+            })),
+        };
+
+        let mut input = vec![];
+        for (record, extra) in [(&update, &update_extra), (&rib, &rib_extra)] {
+            let bytes = RawMrtRecord::new(record.clone(), extra.clone()).to_bytes(0).unwrap();
+            input.extend(&bytes);
+
+            let mut raw_record = RawRecordIterator::new(bytes.as_slice()).next().unwrap().unwrap();
+            assert_eq!(raw_record.raw_bytes(), Some(bytes.as_slice()));
+            assert_eq!(raw_record.extra_attributes(), extra);
+            // the parsed length does not count the microsecond timestamp of BGP4MP_ET records
+            let timestamp_len = if record.common_header.microsecond_timestamp.is_some() { 4 } else { 0 };
+            assert_eq!(raw_record.record().common_header.length as usize, bytes.len() - 12 - timestamp_len);
+            raw_record.record_mut().common_header.timestamp = 2;
+            let modified = raw_record.to_bytes(0).unwrap();
+            assert_eq!(modified[..4], [0, 0, 0, 2]);
+            assert_eq!(modified[4..], bytes[4..]);
+        }
+
+        let records = RawRecordIterator::new(input.as_slice()).collect::<Result<Vec<RawMrtRecord>, ParserError>>().unwrap();
+        let mut output = vec![];
+        for record in &records {
+            record.write_to(&mut output).unwrap();
+        }
+        assert_eq!(output, input);
+        assert_eq!(records[1].record().message, rib.message);
+        assert!(records[1].extra_attributes().list(0).is_empty());
+        assert_eq!(records[1].extra_attributes().list(1).len(), unknown.len());
+    }
+}
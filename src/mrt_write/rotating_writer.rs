@@ -0,0 +1,375 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, ErrorKind, Write};
+use std::path::PathBuf;
+
+use bgp_models::mrt::MrtRecord;
+use bzip2::write::BzEncoder;
+use chrono::DateTime;
+use chrono::format::{Item, StrftimeItems};
+use flate2::write::GzEncoder;
+
+use crate::{DumpError, ExtraAttributes, MrtDump, RawMrtRecord, RecordValidator};
+use crate::mrt_dump::record_context;
+
+/// When [RotatingWriter] starts a new output file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RotationPolicy {
+    /// Start a new file every N minutes of record time. Windows are aligned to the epoch, so
+    /// `Minutes(15)` produces files starting at HH:00, HH:15, HH:30 and HH:45.
+    Minutes(u32),
+    /// Start a new file after N records.
+    Records(u64),
+    /// Start a new file before the (uncompressed) file size would exceed N bytes.
+    Bytes(u64),
+}
+
+enum OutputStream {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+    Bzip2(BzEncoder<BufWriter<File>>),
+}
+
+impl OutputStream {
+    /// choose compression based on the file extension, the same way the parser reads files
+    fn create(path: &PathBuf) -> Result<OutputStream, DumpError> {
+        // never truncate an existing archive, e.g. when a restarted writer reproduces a file name
+        let writer = BufWriter::new(OpenOptions::new().write(true).create_new(true).open(path)?);
+        Ok(
+            match path.extension().and_then(|e| e.to_str()) {
+                Some("gz") => OutputStream::Gzip(GzEncoder::new(writer, flate2::Compression::default())),
+                Some("bz2") => OutputStream::Bzip2(BzEncoder::new(writer, bzip2::Compression::default())),
+                _ => OutputStream::Plain(writer),
+            }
+        )
+    }
+
+    fn write_all(&mut self, bytes: &[u8]) -> Result<(), DumpError> {
+        match self {
+            OutputStream::Plain(w) => w.write_all(bytes)?,
+            OutputStream::Gzip(w) => w.write_all(bytes)?,
+            OutputStream::Bzip2(w) => w.write_all(bytes)?,
+        }
+        Ok(())
+    }
+
+    /// write the compression trailer (if any) and flush everything to disk
+    fn finish(self) -> Result<(), DumpError> {
+        let mut writer = match self {
+            OutputStream::Plain(w) => w,
+            OutputStream::Gzip(w) => w.finish()?,
+            OutputStream::Bzip2(w) => w.finish()?,
+        };
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+struct OutputFile {
+    path: PathBuf,
+    stream: OutputStream,
+    window_start: u32,
+    records: u64,
+    bytes: u64,
+}
+
+/// Write MRT records into a series of files, starting a new file based on a [RotationPolicy].
+///
+/// File names are produced from a template that is formatted with the start time of the file
+/// using `strftime`-style specifiers, e.g. `updates.%Y%m%d.%H%M.bz2` gives RouteViews-style names.
+/// `{seq}` in the template is replaced by the sequence number of the file, starting from 0.
+/// Files ending with `.gz` or `.bz2` are compressed accordingly. Existing files are never
+/// overwritten, rotating to a file that already exists fails with an I/O error.
+///
+/// For [RotationPolicy::Minutes], the start time is the beginning of the time window; otherwise
+/// it is the timestamp of the first record in the file.
+pub struct RotatingWriter {
+    template: String,
+    policy: RotationPolicy,
+    current: Option<OutputFile>,
+    finished: Vec<PathBuf>,
+    /// number of records written to all files
+    records: usize,
+    validator: Option<RecordValidator>,
+}
+
+impl RotatingWriter {
+    pub fn new(template: &str, policy: RotationPolicy) -> Result<Self, DumpError> {
+        if StrftimeItems::new(template).any(|item| item == Item::Error) {
+            return Err(DumpError::InvalidArgument { name: "file name template", value: template.to_string() })
+        }
+        let invalid = match policy {
+            // the window length in seconds must fit in a u32 timestamp
+            RotationPolicy::Minutes(n) => n == 0 || n.checked_mul(60).is_none(),
+            RotationPolicy::Records(n) | RotationPolicy::Bytes(n) => n == 0,
+        };
+        if invalid {
+            return Err(DumpError::InvalidArgument { name: "rotation policy", value: format!("{:?}", policy) })
+        }
+        Ok(RotatingWriter { template: template.to_string(), policy, current: None, finished: vec![], records: 0, validator: None })
+    }
+
+    /// Validate records with `validator` before writing them.
+    ///
+    /// Records with findings are not written, [RotatingWriter::write_record] and
+    /// [RotatingWriter::write_raw_record] return [DumpError::ValidationFailed] with the record
+    /// context instead, so that the caller can reject or quarantine them.
+    pub fn set_validator(&mut self, validator: RecordValidator) {
+        self.validator = Some(validator);
+    }
+
+    /// Encode and write one record, rotating to a new file first if needed.
+    ///
+    /// Encoding errors carry the index of the record among all records written by this writer.
+    pub fn write_record(&mut self, record: &MrtRecord) -> Result<(), DumpError> {
+        self.validate(record, &ExtraAttributes::new())?;
+        self.write_record_unchecked(record, record.common_header.timestamp)
+    }
+
+    /// Write a [RawMrtRecord], using its original bytes if the record has not been modified.
+    pub fn write_raw_record(&mut self, record: &RawMrtRecord) -> Result<(), DumpError> {
+        self.validate(record.record(), record.extra_attributes())?;
+        match record.raw_bytes() {
+            Some(bytes) => self.write_bytes(record.record().common_header.timestamp, bytes),
+            // encoded with the extra attributes of the record
+            None => self.write_record_unchecked(record, record.record().common_header.timestamp),
+        }
+    }
+
+    /// Encode and write one record that has already been validated.
+    fn write_record_unchecked<T: MrtDump>(&mut self, record: &T, timestamp: u32) -> Result<(), DumpError> {
+        let bytes = record.to_bytes(0).map_err(|e| e.with_record_index(self.records))?;
+        self.write_bytes(timestamp, &bytes)
+    }
+
+    /// Write already encoded record bytes with the given record timestamp.
+    pub fn write_bytes(&mut self, timestamp: u32, bytes: &[u8]) -> Result<(), DumpError> {
+        let window_start = match self.policy {
+            RotationPolicy::Minutes(n) => timestamp - timestamp % (n * 60),
+            _ => timestamp,
+        };
+
+        let rotate = match (&self.current, self.policy) {
+            (None, _) => true,
+            // records going back in time stay in the current file
+            (Some(f), RotationPolicy::Minutes(_)) => window_start > f.window_start,
+            (Some(f), RotationPolicy::Records(n)) => f.records >= n,
+            (Some(f), RotationPolicy::Bytes(n)) => f.bytes > 0 && f.bytes + bytes.len() as u64 > n,
+        };
+        if rotate {
+            self.rotate(window_start)?;
+        }
+
+        // the current file is always set after rotating
+        if let Some(file) = self.current.as_mut() {
+            file.stream.write_all(bytes)?;
+            file.records += 1;
+            file.bytes += bytes.len() as u64;
+        }
+        self.records += 1;
+        Ok(())
+    }
+
+    fn validate(&mut self, record: &MrtRecord, extra: &ExtraAttributes) -> Result<(), DumpError> {
+        if let Some(validator) = self.validator.as_mut() {
+            let findings = validator.validate_with_attributes(record, extra);
+            if !findings.is_empty() {
+                return Err(
+                    DumpError::ValidationFailed(findings)
+                        .in_record(record_context(record))
+                        .with_record_index(self.records)
+                )
+            }
+        }
+        Ok(())
+    }
+
+    /// Paths of the files that have been completely written so far.
+    pub fn finished_files(&self) -> &[PathBuf] {
+        &self.finished
+    }
+
+    /// Finalize the current file and return the paths of all written files.
+    pub fn finish(mut self) -> Result<Vec<PathBuf>, DumpError> {
+        self.finish_current()?;
+        Ok(self.finished)
+    }
+
+    fn finish_current(&mut self) -> Result<(), DumpError> {
+        if let Some(file) = self.current.take() {
+            file.stream.finish()?;
+            self.finished.push(file.path);
+        }
+        Ok(())
+    }
+
+    fn rotate(&mut self, window_start: u32) -> Result<(), DumpError> {
+        self.finish_current()?;
+
+        let path = PathBuf::from(self.file_name(window_start));
+        if self.finished.contains(&path) {
+            return Err(DumpError::IOError(std::io::Error::new(
+                ErrorKind::AlreadyExists,
+                format!("output file {} already written, consider adding {{seq}} to the template", path.display()),
+            )))
+        }
+
+        let stream = OutputStream::create(&path)?;
+        self.current = Some(OutputFile { path, stream, window_start, records: 0, bytes: 0 });
+        Ok(())
+    }
+
+    fn file_name(&self, timestamp: u32) -> String {
+        // u32 timestamps are always within chrono's supported range
+        let time = DateTime::from_timestamp(timestamp as i64, 0).unwrap_or_default();
+        let template = self.template.replace("{seq}", self.finished.len().to_string().as_str());
+        time.format(template.as_str()).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::str::FromStr;
+
+    use bgp_models::prelude::*;
+    use bgpkit_parser::BgpkitParser;
+
+    use crate::{RawAttribute, ValidationFinding};
+
+    use super::*;
+
+    fn update_record(timestamp: u32) -> MrtRecord {
+        MrtRecord {
+            common_header: CommonHeader {
+                timestamp,
+                microsecond_timestamp: None,
+                entry_type: EntryType::BGP4MP,
+                entry_subtype: 4,
+                length: 0,
+            },
+            message: MrtMessage::Bgp4Mp(Bgp4Mp::Bgp4MpMessageAs4(Bgp4MpMessage {
+                msg_type: Bgp4MpType::Bgp4MpMessageAs4,
+                peer_asn: Asn::from(65000),
+                local_asn: Asn::from(65001),
+                interface_index: 0,
+                afi: Afi::Ipv4,
+                peer_ip: IpAddr::from(Ipv4Addr::from([10, 0, 0, 1])),
+                local_ip: IpAddr::from(Ipv4Addr::from([10, 0, 0, 2])),
+                bgp_message: BgpMessage::Update(BgpUpdateMessage {
+                    withdrawn_prefixes: vec![NetworkPrefix::from_str("10.1.0.0/16").unwrap()],
+                    attributes: vec![],
+                    announced_prefixes: vec![],
+                }),
+            })),
+        }
+    }
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("bgpkit-parser-dump-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn count_records(path: &std::path::Path) -> usize {
+        BgpkitParser::new(path.to_str().unwrap()).unwrap().into_record_iter().count()
+    }
+
+    #[test]
+    fn test_rotate_by_minutes() {
+        let dir = test_dir("minutes");
+        let template = dir.join("updates.%Y%m%d.%H%M.gz");
+        let mut writer = RotatingWriter::new(template.to_str().unwrap(), RotationPolicy::Minutes(15)).unwrap();
+
+        // 2021-12-05 04:50:00 UTC
+        let start = 1638679800;
+        for ts in [start, start + 60, start + 600, start + 15 * 60, start + 540, start + 3600] {
+            writer.write_record(&update_record(ts)).unwrap();
+        }
+        let files = writer.finish().unwrap();
+
+        let names: Vec<String> = files.iter().map(|f| f.file_name().unwrap().to_str().unwrap().to_string()).collect();
+        assert_eq!(names, vec!["updates.20211205.0445.gz", "updates.20211205.0500.gz", "updates.20211205.0545.gz"]);
+        assert_eq!(files.iter().map(|f| count_records(f)).collect::<Vec<usize>>(), vec![2, 3, 1]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_rotate_by_records_and_bytes() {
+        let dir = test_dir("records");
+        let template = dir.join("updates.{seq}.bz2");
+        let mut writer = RotatingWriter::new(template.to_str().unwrap(), RotationPolicy::Records(2)).unwrap();
+        for ts in 0..5 {
+            writer.write_record(&update_record(ts)).unwrap();
+        }
+        let files = writer.finish().unwrap();
+        assert_eq!(files.iter().map(|f| count_records(f)).collect::<Vec<usize>>(), vec![2, 2, 1]);
+
+        let record_len = update_record(0).to_bytes(0).unwrap().len() as u64;
+        let template = dir.join("updates.{seq}");
+        let mut writer = RotatingWriter::new(template.to_str().unwrap(), RotationPolicy::Bytes(record_len * 3)).unwrap();
+        for ts in 0..5 {
+            writer.write_record(&update_record(ts)).unwrap();
+        }
+        let files = writer.finish().unwrap();
+        assert_eq!(files.iter().map(|f| count_records(f)).collect::<Vec<usize>>(), vec![3, 2]);
+
+        // without a sequence number, file names collide
+        let template = dir.join("updates.%Y");
+        let mut writer = RotatingWriter::new(template.to_str().unwrap(), RotationPolicy::Records(1)).unwrap();
+        writer.write_record(&update_record(0)).unwrap();
+        assert!(writer.write_record(&update_record(1)).is_err());
+
+        // existing files are not overwritten
+        let mut writer = RotatingWriter::new(template.to_str().unwrap(), RotationPolicy::Records(1)).unwrap();
+        assert!(matches!(writer.write_record(&update_record(0)), Err(DumpError::IOError(e)) if e.kind() == ErrorKind::AlreadyExists));
+        assert_eq!(count_records(&dir.join("updates.1970")), 1);
+
+        assert!(RotatingWriter::new(template.to_str().unwrap(), RotationPolicy::Minutes(u32::MAX)).is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_validate_before_writing() {
+        let dir = test_dir("validate");
+        let template = dir.join("updates.{seq}");
+        let mut writer = RotatingWriter::new(template.to_str().unwrap(), RotationPolicy::Records(10)).unwrap();
+        writer.set_validator(RecordValidator::new());
+        writer.write_record(&update_record(0)).unwrap();
+
+        let mut record = update_record(1);
+        if let MrtMessage::Bgp4Mp(Bgp4Mp::Bgp4MpMessageAs4(m)) = &mut record.message {
+            m.bgp_message = BgpMessage::Update(BgpUpdateMessage {
+                withdrawn_prefixes: vec![NetworkPrefix::from_str("2001:db8::/32").unwrap()],
+                attributes: vec![],
+                announced_prefixes: vec![],
+            });
+        }
+        match writer.write_record(&record) {
+            Err(DumpError::Record { context, source }) => {
+                assert_eq!(context.index, Some(1));
+                assert!(matches!(*source, DumpError::ValidationFailed(f) if f.len() == 1));
+            }
+            _ => panic!("expecting validation error"),
+        }
+
+        // raw records are validated with their extra attributes
+        let otc = RawAttribute::new(35, 0xC0, vec![0, 0, 0xFD, 0xE8]);
+        let mut extra = ExtraAttributes::new();
+        extra.insert(0, 0, otc.clone().into());
+        extra.insert(0, 0, otc.into());
+        match writer.write_raw_record(&RawMrtRecord::new(update_record(2), extra)) {
+            Err(DumpError::Record { source, .. }) => {
+                assert!(matches!(*source, DumpError::ValidationFailed(f) if f == vec![ValidationFinding::DuplicateAttribute(35)]));
+            }
+            _ => panic!("expecting validation error"),
+        }
+
+        // rejected records are not written
+        let files = writer.finish().unwrap();
+        assert_eq!(count_records(&files[0]), 1);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,137 @@
+#!/usr/bin/env python3
+# Generate the synthetic MRT fixtures of this directory. The bytes are assembled field by field
+# from the RFC 6396/4271/4760 layouts, independently of the encoder under test.
+import ipaddress, os, struct
+
+DIR = os.path.dirname(os.path.abspath(__file__))
+
+def u8(v): return struct.pack('!B', v)
+def u16(v): return struct.pack('!H', v)
+def u32(v): return struct.pack('!I', v)
+def ip(s): return ipaddress.ip_address(s).packed
+
+def attr(flag, typ, value):
+    if flag & 0x10:
+        return u8(flag)+u8(typ)+u16(len(value))+value
+    assert len(value) < 256
+    return u8(flag)+u8(typ)+u8(len(value))+value
+
+def nlri(p, path_id=None):
+    n = ipaddress.ip_network(p, strict=False)
+    b = (n.prefixlen+7)//8
+    out = b'' if path_id is None else u32(path_id)
+    return out + u8(n.prefixlen) + ipaddress.ip_address(p.split('/')[0]).packed[:b]
+
+def aspath(segs, w):
+    out=b''
+    for t, asns in segs:
+        out += u8(t)+u8(len(asns))
+        for a in asns:
+            out += u32(a) if w==4 else u16(a)
+    return out
+
+def bgp_msg(typ, body):
+    return b'\xff'*16 + u16(19+len(body)) + u8(typ) + body
+
+def update(withdrawn, attrs, announced):
+    w = b''.join(withdrawn); a = b''.join(attrs); n=b''.join(announced)
+    return u16(len(w))+w+u16(len(a))+a+n
+
+def bgp4mp_msg(w, peer_asn, local_asn, afi, peer_ip, local_ip, msg):
+    asn = (lambda x: u32(x)) if w==4 else (lambda x: u16(x))
+    return asn(peer_asn)+asn(local_asn)+u16(0)+u16(afi)+ip(peer_ip)+ip(local_ip)+msg
+
+def mrt(ts, typ, sub, body, usec=None):
+    if usec is not None:
+        return u32(ts)+u16(typ)+u16(sub)+u32(len(body)+4)+u32(usec)+body
+    return u32(ts)+u16(typ)+u16(sub)+u32(len(body))+body
+
+out = b''
+# 1. BGP4MP_ET AS4 update, IPv4, with most attribute types
+attrs = [
+    attr(0x40, 1, u8(0)),
+    attr(0x50, 2, aspath([(2, [65001, 4200000001, 3356]), (1, [64512, 64513])], 4)),  # extended length bit set on a short attribute
+    attr(0x40, 3, ip('192.0.2.1')),
+    attr(0x80, 4, u32(100)),
+    attr(0x40, 5, u32(200)),
+    attr(0x40, 6, b''),
+    attr(0xc0, 7, u32(4200000001)+ip('192.0.2.2')),
+    attr(0xc0, 8, u32(0xFFFFFF01)+u16(3356)+u16(100)+u16(65001)+u16(2)),
+    attr(0x80, 9, ip('10.10.10.10')),
+    attr(0x80, 10, ip('10.0.0.1')+ip('10.0.0.2')),
+    attr(0xc0, 16, bytes([0x00,0x02])+u16(65001)+u32(100) + bytes([0x02,0x02])+u32(4200000001)+u16(7) + bytes([0x01,0x03])+ip('192.0.2.9')+u16(5) + bytes([0x43,0x0b])+bytes(6) + bytes([0x06,0x00,1,2,3,4,5,6])),
+    attr(0xe0, 32, u32(4200000001)+u32(1)+u32(2)),  # partial bit set
+]
+msg = bgp_msg(2, update([nlri('10.1.0.0/16'), nlri('10.2.3.0/24')], attrs, [nlri('203.0.113.0/24'), nlri('198.51.100.128/25'), nlri('100.64.0.0/10')]))
+out += mrt(1638679800, 17, 4, bgp4mp_msg(4, 65001, 65000, 1, '192.0.2.1', '192.0.2.254', msg), usec=123456)
+
+# 2. BGP4MP AS4 update, IPv6 peer, MP_REACH with link-local next hop, MP_UNREACH, IPv6 specific ext communities
+attrs = [
+    attr(0x40, 1, u8(2)),
+    attr(0x40, 2, aspath([(2, [65002, 65003, 65003, 65003])], 4)),
+    attr(0x80, 14, u16(2)+u8(1)+u8(32)+ip('2001:db8::1')+ip('fe80::1')+u8(0)+nlri('2001:db8:1::/48')+nlri('2001:db8:2:3::/64')),
+    attr(0x80, 15, u16(2)+u8(1)+nlri('2001:db8:ffff::/48')),
+    attr(0xc0, 25, bytes([0x00,0x02])+ip('2001:db8::9')+u16(10)),
+]
+msg = bgp_msg(2, update([], attrs, []))
+out += mrt(1638679801, 16, 4, bgp4mp_msg(4, 65002, 65000, 2, '2001:db8::1', '2001:db8::fe', msg))
+
+# 3. BGP4MP (2-byte) update with AS_PATH using AS_TRANS and AS4_PATH, AGGREGATOR/AS4_AGGREGATOR
+attrs = [
+    attr(0x40, 1, u8(1)),
+    attr(0x40, 2, aspath([(2, [65004, 23456, 3356])], 2)),
+    attr(0x40, 3, ip('192.0.2.5')),
+    attr(0xc0, 7, u16(23456)+ip('192.0.2.6')),
+    attr(0xc0, 17, aspath([(2, [4200000002, 3356])], 4)),
+    attr(0xc0, 18, u32(4200000002)+ip('192.0.2.6')),
+]
+msg = bgp_msg(2, update([], attrs, [nlri('192.0.2.0/24')]))
+out += mrt(1638679802, 16, 1, bgp4mp_msg(2, 65004, 65000, 1, '192.0.2.5', '192.0.2.254', msg))
+
+# 4. add-path AS4 update
+attrs = [
+    attr(0x40, 1, u8(0)),
+    attr(0x40, 2, aspath([(2, [65005])], 4)),
+    attr(0x40, 3, ip('192.0.2.7')),
+]
+msg = bgp_msg(2, update([nlri('10.9.0.0/16', 7)], attrs, [nlri('10.8.0.0/16', 1), nlri('10.8.0.0/16', 2)]))
+out += mrt(1638679803, 16, 9, bgp4mp_msg(4, 65005, 65000, 1, '192.0.2.7', '192.0.2.254', msg))
+
+# 5. OPEN with capabilities, KEEPALIVE, NOTIFICATION
+open_body = u8(4)+u16(23456)+u16(180)+ip('192.0.2.8')
+params = u8(2)+u8(6)+u8(1)+u8(4)+u16(1)+u8(0)+u8(1) + u8(2)+u8(6)+u8(65)+u8(4)+u32(4200000003) + u8(2)+u8(2)+u8(2)+u8(0)
+open_body += u8(len(params))+params
+out += mrt(1638679804, 16, 4, bgp4mp_msg(4, 4200000003, 65000, 1, '192.0.2.8', '192.0.2.254', bgp_msg(1, open_body)))
+out += mrt(1638679805, 16, 4, bgp4mp_msg(4, 4200000003, 65000, 1, '192.0.2.8', '192.0.2.254', bgp_msg(4, b'')))
+out += mrt(1638679806, 16, 4, bgp4mp_msg(4, 4200000003, 65000, 1, '192.0.2.8', '192.0.2.254', bgp_msg(3, u8(6)+u8(2)+b'\x01\x02')))
+
+# 6. state changes, 2-byte and AS4
+out += mrt(1638679807, 16, 0, u16(65004)+u16(65000)+u16(0)+u16(1)+ip('192.0.2.5')+ip('192.0.2.254')+u16(1)+u16(2))
+out += mrt(1638679808, 16, 5, u32(4200000003)+u32(65000)+u16(3)+u16(2)+ip('2001:db8::1')+ip('2001:db8::fe')+u16(6)+u16(1))
+
+open(os.path.join(DIR, 'updates-example.mrt'),'wb').write(out)
+
+# RIB
+out = b''
+peers = [
+    (2, '10.0.0.1', '192.0.2.1', 4200000001, 4),
+    (0, '10.0.0.2', '192.0.2.2', 65002, 2),
+    (3, '10.0.0.3', '2001:db8::3', 65003, 4),
+]
+body = ip('10.255.0.1') + u16(0) + u16(len(peers))
+for t, bid, addr, asn, w in peers:
+    body += u8(t)+ip(bid)+ip(addr)+(u32(asn) if w==4 else u16(asn))
+out += mrt(1638662400, 13, 1, body)
+
+def rib_entry(peer, t, attrs):
+    a = b''.join(attrs)
+    return u16(peer)+u32(t)+u16(len(a))+a
+
+e1 = rib_entry(0, 1638600000, [attr(0x40,1,u8(0)), attr(0x40,2,aspath([(2,[4200000001, 3356, 15169])],4)), attr(0x40,3,ip('192.0.2.1')), attr(0xc0,8,u16(3356)+u16(1))])
+e2 = rib_entry(1, 1638600001, [attr(0x40,1,u8(2)), attr(0x50,2,aspath([(2,[65002, 15169]), (1, [1, 2])],4)), attr(0x40,3,ip('192.0.2.2')), attr(0x80,4,u32(0))])
+out += mrt(1638662400, 13, 2, u32(0)+nlri('8.8.8.0/24')+u16(2)+e1+e2)
+e3 = rib_entry(2, 1638600002, [attr(0x40,1,u8(0)), attr(0x40,2,aspath([(2,[65003, 6939])],4)), attr(0x80,14,u8(32)+ip('2001:db8::3')+ip('fe80::3'))])
+e4 = rib_entry(2, 1638600003, [attr(0x40,1,u8(0)), attr(0x40,2,aspath([(2,[65003, 174])],4)), attr(0x80,14,u8(16)+ip('2001:db8::3')), attr(0xc0,32,u32(65003)+u32(0)+u32(9))])
+out += mrt(1638662400, 13, 4, u32(1)+nlri('2001:4860::/32')+u16(1)+e3)
+out += mrt(1638662400, 13, 4, u32(2)+nlri('2600::/12')+u16(1)+e4)
+open(os.path.join(DIR, 'rib-example.mrt'),'wb').write(out)
@@ -1,13 +1,28 @@
 use std::fs::File;
-use std::io::Write;
+use std::io::{Read, Write};
 use bgp_models::bgp::BgpElem;
 use bgpkit_parser::BgpkitParser;
 use bgpkit_parser::parser::mrt::mrt_record::parse_mrt_record;
+use bzip2::read::BzDecoder;
+use flate2::read::GzDecoder;
 use flate2::write::GzEncoder;
 use flate2::Compression;
-use bgpkit_parser_dump::{BgpUpdatesComposer, MrtCompose, MrtDump, TableDumpComposer};
+use bgpkit_parser_dump::{BgpUpdatesComposer, MrtCompose, MrtDump, RawRecordIterator, TableDumpComposer};
+
+/// Re-encode every record of an uncompressed MRT stream and compare it with the original bytes.
+fn assert_byte_exact<R: Read>(reader: R, path: &str) {
+    let mut count = 0;
+    for record in RawRecordIterator::new(reader) {
+        let record = record.unwrap();
+        let bytes = record.record().to_bytes(0).unwrap();
+        assert_eq!(bytes.as_slice(), record.raw_bytes().unwrap(), "record {} of {} differs", count, path);
+        count += 1;
+    }
+    assert!(count > 0);
+}
 
 #[test]
+#[allow(clippy::explicit_counter_loop)]
 fn test_updates() {
     let url = "http://data.ris.ripe.net/rrc23/2021.12/updates.20211205.0450.gz";
     let parser = BgpkitParser::new(url).unwrap();
@@ -34,7 +49,8 @@ fn test_rib_v2() {
     for record in parser.into_record_iter() {
         let bytes = record.to_bytes(0).unwrap();
         let record2 = parse_mrt_record(&mut bytes.as_slice()).unwrap();
-        // NOTE: number of bytes are different, thus only comparing the parsed messages.
+        // NOTE: only comparing the parsed messages here, see `test_byte_exact_remote_rib` for the
+        // byte-level comparison.
         assert_eq!(record.message, record2.message);
     }
 }
@@ -83,3 +99,27 @@ fn test_filtered_rib() {
     let elem_count = parser.into_elem_iter().count();
     assert_eq!(elem_count, 2507);
 }
+
+#[test]
+fn test_byte_exact_updates() {
+    let path = "tests/fixtures/updates-example.mrt";
+    assert_byte_exact(File::open(path).unwrap(), path);
+}
+
+#[test]
+fn test_byte_exact_rib() {
+    let path = "tests/fixtures/rib-example.mrt";
+    assert_byte_exact(File::open(path).unwrap(), path);
+}
+
+#[test]
+fn test_byte_exact_remote_updates() {
+    let url = "http://data.ris.ripe.net/rrc23/2021.12/updates.20211205.0450.gz";
+    assert_byte_exact(GzDecoder::new(reqwest::blocking::get(url).unwrap()), url);
+}
+
+#[test]
+fn test_byte_exact_remote_rib() {
+    let url = "http://archive.routeviews.org/route-views.sg/bgpdata/2018.07/RIBS/rib.20180701.0000.bz2";
+    assert_byte_exact(BzDecoder::new(reqwest::blocking::get(url).unwrap()), url);
+}